@@ -0,0 +1,653 @@
+//! `xpt`: inspect, validate and convert SAS XPORT transport files without the desktop app
+//!
+//! Files are read with [`XptReader`], so rows are streamed and memory use does not depend on
+//! the size of the file.
+//!
+//! Exit status: 0 on success, 1 when a file cannot be decoded or `validate` finds problems,
+//! 2 for invalid arguments and 3 when a file cannot be read or written.
+
+mod output;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use output::{display_value, Align, TableWriter};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use xpt::{
+    write_arrow_ipc, write_dataset_json, write_delimited, write_parquet, ArrowOptions,
+    DatasetJsonOptions, DelimitedOptions, ErrorCode, MissingValues, ParquetOptions, ParseOptions,
+    Section, TextEncoding, VariableType, XPTDataset, XPTWriter, XlsxOptions, XlsxWriter,
+    XportVersion, XptError, XptReader,
+};
+
+/// Exit status when a file cannot be decoded or `validate` finds problems
+const EXIT_INVALID: u8 = 1;
+/// Exit status for invalid arguments, the status clap uses for its own usage errors
+const EXIT_USAGE: u8 = 2;
+/// Exit status when a file cannot be read or written
+const EXIT_IO: u8 = 3;
+
+#[derive(Parser)]
+#[command(
+    name = "xpt",
+    version,
+    about = "Inspect, validate and convert SAS XPORT transport files"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the members of a file with their row counts and variables
+    Info {
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Print the first rows of a member
+    Head {
+        #[command(flatten)]
+        input: Input,
+        #[command(flatten)]
+        rows: RowOptions,
+        /// Number of rows to print
+        #[arg(short = 'n', long = "lines", default_value_t = 10)]
+        count: usize,
+    },
+    /// Print every row of a member
+    Dump {
+        #[command(flatten)]
+        input: Input,
+        #[command(flatten)]
+        rows: RowOptions,
+    },
+    /// Write a member to a CSV, TSV, Dataset-JSON, Parquet or Arrow IPC (Feather) file, with
+    /// numbers at full precision, or to a transport file; or write members to an Excel workbook
+    Convert {
+        #[command(flatten)]
+        input: Input,
+        /// Member to convert, by name or 1-based number (default: the first, or every member
+        /// for an Excel workbook)
+        #[arg(short, long)]
+        member: Option<String>,
+        /// File to write
+        output: PathBuf,
+        /// Output format (default: from the output file extension)
+        #[arg(long, value_enum)]
+        to: Option<ExportFormat>,
+        /// Transport format revision of an XPT output (default: that of the input)
+        #[arg(long, value_enum)]
+        xpt_version: Option<Revision>,
+        /// Write dates and datetimes of a Parquet, Arrow or Excel output as SAS day and second
+        /// counts instead of dates and timestamps
+        #[arg(long)]
+        sas_dates: bool,
+    },
+    /// Check that every member decodes cleanly, listing any problem found
+    Validate {
+        #[command(flatten)]
+        input: Input,
+    },
+}
+
+#[derive(Args)]
+struct Input {
+    /// Transport file to read
+    file: PathBuf,
+    /// Encoding of names, labels and character values (default: detected)
+    #[arg(long, value_enum)]
+    encoding: Option<Encoding>,
+    /// Load as much of a damaged file as possible, reporting every repair
+    #[arg(long)]
+    recover: bool,
+}
+
+#[derive(Args)]
+struct RowOptions {
+    /// Member to print, by name or 1-based number (default: the first)
+    #[arg(short, long)]
+    member: Option<String>,
+    /// How to print the rows
+    #[arg(long, value_enum, default_value_t = RowFormat::Table)]
+    format: RowFormat,
+    /// Print numbers as stored instead of with their SAS format
+    #[arg(long)]
+    raw: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Encoding {
+    #[value(name = "utf-8")]
+    Utf8,
+    #[value(name = "latin-1")]
+    Latin1,
+    #[value(name = "windows-1252")]
+    Windows1252,
+}
+
+impl From<Encoding> for TextEncoding {
+    fn from(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Utf8 => TextEncoding::Utf8,
+            Encoding::Latin1 => TextEncoding::Latin1,
+            Encoding::Windows1252 => TextEncoding::Windows1252,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Revision {
+    #[value(name = "5")]
+    V5,
+    #[value(name = "8")]
+    V8,
+}
+
+impl From<Revision> for XportVersion {
+    fn from(revision: Revision) -> Self {
+        match revision {
+            Revision::V5 => XportVersion::V5,
+            Revision::V8 => XportVersion::V8,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RowFormat {
+    Table,
+    Csv,
+    Tsv,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Csv,
+    Tsv,
+    /// CDISC Dataset-JSON v1.1
+    Json,
+    /// CDISC Dataset-JSON v1.1 in its NDJSON form, one row per line
+    Ndjson,
+    Parquet,
+    Arrow,
+    /// Excel workbook with a sheet per member and a Variables sheet
+    Xlsx,
+    Xpt,
+}
+
+impl ExportFormat {
+    fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "tsv" | "tab" => Some(ExportFormat::Tsv),
+            "json" => Some(ExportFormat::Json),
+            "ndjson" => Some(ExportFormat::Ndjson),
+            "parquet" => Some(ExportFormat::Parquet),
+            "arrow" | "feather" | "ipc" => Some(ExportFormat::Arrow),
+            "xlsx" => Some(ExportFormat::Xlsx),
+            "xpt" => Some(ExportFormat::Xpt),
+            _ => None,
+        }
+    }
+}
+
+/// Why a command failed, which decides the exit status
+enum Failure {
+    /// The input could not be decoded, or could not be read
+    Decode(XptError),
+    /// A file or the standard output could not be written
+    Io(String),
+    /// The arguments do not fit the file, e.g. an unknown member
+    Usage(String),
+    /// `validate` found problems, which have already been printed
+    Invalid,
+    /// The reader of the standard output went away, as `head` does in `xpt dump f.xpt | head`
+    OutputClosed,
+}
+
+impl From<XptError> for Failure {
+    fn from(error: XptError) -> Self {
+        Failure::Decode(error)
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(error: io::Error) -> Self {
+        if error.kind() == io::ErrorKind::BrokenPipe {
+            Failure::OutputClosed
+        } else {
+            Failure::Io(format!("Unable to write the output: {}", error))
+        }
+    }
+}
+
+impl Command {
+    fn input(&self) -> &Input {
+        match self {
+            Command::Info { input }
+            | Command::Head { input, .. }
+            | Command::Dump { input, .. }
+            | Command::Convert { input, .. }
+            | Command::Validate { input } => input,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let file = cli.command.input().file.clone();
+    let result = match cli.command {
+        Command::Info { input } => info(&input),
+        Command::Head { input, rows, count } => print_rows(&input, &rows, Some(count)),
+        Command::Dump { input, rows } => print_rows(&input, &rows, None),
+        Command::Convert {
+            input,
+            member,
+            output,
+            to,
+            xpt_version,
+            sas_dates,
+        } => convert(
+            &input,
+            member.as_deref(),
+            &output,
+            to,
+            xpt_version,
+            sas_dates,
+        ),
+        Command::Validate { input } => validate(&input),
+    };
+
+    match result {
+        Ok(()) | Err(Failure::OutputClosed) => ExitCode::SUCCESS,
+        Err(Failure::Decode(error)) => {
+            eprintln!("xpt: {}: {}", file.display(), error);
+            let status = if error.code == ErrorCode::Io {
+                EXIT_IO
+            } else {
+                EXIT_INVALID
+            };
+            ExitCode::from(status)
+        }
+        Err(Failure::Io(message)) => {
+            eprintln!("xpt: {}", message);
+            ExitCode::from(EXIT_IO)
+        }
+        Err(Failure::Usage(message)) => {
+            eprintln!("xpt: {}", message);
+            ExitCode::from(EXIT_USAGE)
+        }
+        Err(Failure::Invalid) => ExitCode::from(EXIT_INVALID),
+    }
+}
+
+fn open(input: &Input) -> Result<XptReader<File>, Failure> {
+    let file = File::open(&input.file).map_err(|error| {
+        Failure::Decode(XptError::new(
+            ErrorCode::Io,
+            Section::File,
+            format!("Unable to read the file: {}", error),
+        ))
+    })?;
+    let options = ParseOptions {
+        recover: input.recover,
+        encoding: input.encoding.map(TextEncoding::from),
+    };
+    let suggested_filename = input.file.file_name().and_then(|name| name.to_str());
+    Ok(XptReader::with_options(file, suggested_filename, options)?)
+}
+
+/// Finds a member by name, ignoring case, or by 1-based number
+///
+/// Members are read one at a time, so the file is only read up to the member named.
+fn select_member(reader: &mut XptReader<File>, member: Option<&str>) -> Result<usize, Failure> {
+    let Some(member) = member else {
+        return Ok(0);
+    };
+    let mut names = Vec::new();
+    while let Some(dataset) = reader.dataset(names.len())? {
+        if dataset.title.eq_ignore_ascii_case(member) {
+            return Ok(names.len());
+        }
+        names.push(dataset.title.clone());
+    }
+    match member.parse::<usize>() {
+        Ok(number) if (1..=names.len()).contains(&number) => Ok(number - 1),
+        _ => Err(Failure::Usage(format!(
+            "No member {} in the file; it holds {}",
+            member,
+            names.join(", ")
+        ))),
+    }
+}
+
+fn info(input: &Input) -> Result<(), Failure> {
+    let mut reader = open(input)?;
+    let mut out = BufWriter::new(io::stdout().lock());
+
+    let library = reader.library();
+    writeln!(out, "File: {}", input.file.display())?;
+    let mut written_by = format!("SAS {}", library.sas_version);
+    if !library.os_name.is_empty() {
+        written_by.push_str(&format!(" on {}", library.os_name));
+    }
+    writeln!(out, "Written by: {}", written_by.trim())?;
+    if let Some(created) = library.created {
+        writeln!(out, "Created: {}", created)?;
+    }
+    writeln!(out, "Encoding: {}", encoding_name(reader.encoding()))?;
+    let datasets: Vec<XPTDataset> = reader.datasets()?.cloned().collect();
+    writeln!(out, "Members: {}", datasets.len())?;
+
+    for (index, dataset) in datasets.iter().enumerate() {
+        writeln!(out)?;
+        let mut heading = format!("{}. {}", index + 1, dataset.title);
+        if !dataset.label.is_empty() {
+            heading.push_str(&format!(" ({})", dataset.label));
+        }
+        writeln!(out, "{}", heading)?;
+        writeln!(
+            out,
+            "   {}, {}",
+            count(reader.row_count(index)?.unwrap_or(0), "row"),
+            count(dataset.variables.len(), "variable")
+        )?;
+        for warning in &dataset.warnings {
+            writeln!(out, "   warning: {}", warning)?;
+        }
+        writeln!(out)?;
+
+        let columns = [
+            ("#", Align::Right),
+            ("Name", Align::Left),
+            ("Type", Align::Left),
+            ("Length", Align::Right),
+            ("Format", Align::Left),
+            ("Informat", Align::Left),
+            ("Label", Align::Left),
+        ];
+        let mut table = TableWriter::new(
+            &mut out,
+            columns
+                .iter()
+                .map(|&(name, align)| (name.to_string(), align))
+                .collect(),
+        );
+        for (number, variable) in dataset.variables.iter().enumerate() {
+            let kind = match variable.var_type {
+                VariableType::Numeric => "Num",
+                VariableType::Character => "Char",
+            };
+            table.write_row(vec![
+                (number + 1).to_string(),
+                variable.name.clone(),
+                kind.to_string(),
+                variable.length.to_string(),
+                variable.format.to_string(),
+                variable.informat.to_string(),
+                variable.label.clone(),
+            ])?;
+        }
+        table.finish()?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Prints the rows of a member, or only the first `limit` of them
+fn print_rows(input: &Input, options: &RowOptions, limit: Option<usize>) -> Result<(), Failure> {
+    let mut reader = open(input)?;
+    let member = select_member(&mut reader, options.member.as_deref())?;
+    let Some(dataset) = reader.dataset(member)?.cloned() else {
+        return Ok(());
+    };
+    let rows = reader
+        .rows(member)?
+        .into_iter()
+        .flatten()
+        .map(|row| row.map_err(Failure::from));
+    let out = BufWriter::new(io::stdout().lock());
+
+    let delimited = match options.format {
+        RowFormat::Table => None,
+        RowFormat::Csv => Some(DelimitedOptions::default()),
+        RowFormat::Tsv => Some(DelimitedOptions::tsv()),
+    };
+    let mut out = match delimited {
+        // Cells as the table shows them, so missing values keep their code
+        Some(delimited) => {
+            let delimited = DelimitedOptions {
+                missing: MissingValues::Codes,
+                formatted: !options.raw,
+                rows: limit.map(|limit| 0..limit),
+                ..delimited
+            };
+            write_delimited(out, &dataset, rows, &delimited)?
+        }
+        None => {
+            let variables = &dataset.variables;
+            let columns = variables
+                .iter()
+                .map(|variable| {
+                    let align = match variable.var_type {
+                        VariableType::Numeric => Align::Right,
+                        VariableType::Character => Align::Left,
+                    };
+                    (variable.name.clone(), align)
+                })
+                .collect();
+            let mut table = TableWriter::new(out, columns);
+            for row in rows.take(limit.unwrap_or(usize::MAX)) {
+                let cells = row?
+                    .values
+                    .iter()
+                    .zip(variables)
+                    .map(|(value, variable)| display_value(value, variable, options.raw))
+                    .collect();
+                table.write_row(cells)?;
+            }
+            table.finish()?
+        }
+    };
+    out.flush()?;
+    Ok(())
+}
+
+fn convert(
+    input: &Input,
+    member: Option<&str>,
+    output: &Path,
+    format: Option<ExportFormat>,
+    xpt_version: Option<Revision>,
+    sas_dates: bool,
+) -> Result<(), Failure> {
+    let format = format
+        .or_else(|| ExportFormat::from_extension(output))
+        .ok_or_else(|| {
+            Failure::Usage(format!(
+                "Cannot tell the format of {} from its extension; pass --to csv, tsv, json, ndjson, parquet, arrow, xlsx or xpt",
+                output.display()
+            ))
+        })?;
+    let mut reader = open(input)?;
+    let every_member = member.is_none();
+    let member = select_member(&mut reader, member)?;
+    let Some(dataset) = reader.dataset(member)?.cloned() else {
+        return Ok(());
+    };
+
+    let write_error = |error: io::Error| {
+        Failure::Io(format!(
+            "Unable to write file {}: {}",
+            output.display(),
+            error
+        ))
+    };
+    let file = BufWriter::new(File::create(output).map_err(write_error)?);
+    let delimited = match format {
+        ExportFormat::Csv => DelimitedOptions::default(),
+        ExportFormat::Tsv => DelimitedOptions::tsv(),
+        ExportFormat::Json | ExportFormat::Ndjson => {
+            let options = DatasetJsonOptions {
+                ndjson: matches!(format, ExportFormat::Ndjson),
+                ..DatasetJsonOptions::default()
+            };
+            let records = reader.row_count(member)?.unwrap_or(0);
+            let rows = reader
+                .rows(member)?
+                .into_iter()
+                .flatten()
+                .map(|row| row.map_err(Failure::from));
+            write_dataset_json(file, &dataset, records, rows, &options)?;
+            return Ok(());
+        }
+        ExportFormat::Parquet => {
+            let options = ParquetOptions {
+                logical_dates: !sas_dates,
+                ..ParquetOptions::default()
+            };
+            let rows = reader
+                .rows(member)?
+                .into_iter()
+                .flatten()
+                .map(|row| row.map_err(Failure::from));
+            write_parquet(file, &dataset, rows, &options)?.flush()?;
+            return Ok(());
+        }
+        ExportFormat::Arrow => {
+            let options = ArrowOptions {
+                logical_dates: !sas_dates,
+                ..ArrowOptions::default()
+            };
+            let rows = reader
+                .rows(member)?
+                .into_iter()
+                .flatten()
+                .map(|row| row.map_err(Failure::from));
+            write_arrow_ipc(file, &dataset, rows, &options)?.flush()?;
+            return Ok(());
+        }
+        ExportFormat::Xlsx => {
+            // Every member is read in turn until there are no more
+            let members = if every_member {
+                0..usize::MAX
+            } else {
+                member..member + 1
+            };
+            let mut workbook = XlsxWriter::new(XlsxOptions {
+                excel_dates: !sas_dates,
+                ..XlsxOptions::default()
+            });
+            for member in members {
+                let Some(dataset) = reader.dataset(member)?.cloned() else {
+                    break;
+                };
+                let rows = reader
+                    .rows(member)?
+                    .into_iter()
+                    .flatten()
+                    .map(|row| row.map_err(Failure::from));
+                workbook.add_dataset(&dataset, rows)?;
+            }
+            workbook.finish(file)?.flush()?;
+            return Ok(());
+        }
+        ExportFormat::Xpt => {
+            // Metadata or values the revision cannot hold, such as long names in Version 5
+            let xpt_error = |error: XptError| match error.code {
+                ErrorCode::Io => Failure::Io(format!(
+                    "Unable to write file {}: {}",
+                    output.display(),
+                    error
+                )),
+                _ => Failure::Usage(format!("Cannot write {}: {}", output.display(), error)),
+            };
+            let library = reader.library().clone();
+            let version = xpt_version.map_or(reader.version(), XportVersion::from);
+            let mut writer = XPTWriter::with_version(file, &library, version).map_err(xpt_error)?;
+            let mut rows = writer.begin_member(&dataset).map_err(xpt_error)?;
+            for row in reader.rows(member)?.into_iter().flatten() {
+                rows.write_row(&row?).map_err(xpt_error)?;
+            }
+            writer.finish().map_err(xpt_error)?;
+            return Ok(());
+        }
+    };
+
+    let rows = reader
+        .rows(member)?
+        .into_iter()
+        .flatten()
+        .map(|row| row.map_err(Failure::from));
+    write_delimited(file, &dataset, rows, &delimited)?;
+    Ok(())
+}
+
+fn validate(input: &Input) -> Result<(), Failure> {
+    let mut reader = open(input)?;
+    let mut problems = Vec::new();
+
+    let mut member_count = 0;
+    while let Some(dataset) = reader.dataset(member_count)?.cloned() {
+        let member = member_count;
+        member_count += 1;
+        let mut report = |message: String| problems.push(format!("{}: {}", dataset.title, message));
+        for warning in &dataset.warnings {
+            report(warning.clone());
+        }
+
+        let mut seen = HashSet::new();
+        for variable in &dataset.variables {
+            if !seen.insert(variable.name.to_ascii_uppercase()) {
+                report(format!(
+                    "variable {} is defined more than once",
+                    variable.name
+                ));
+            }
+        }
+
+        let Some(mut rows) = reader.rows(member)? else {
+            continue;
+        };
+        for row in rows.by_ref() {
+            row?;
+        }
+        for warning in rows.warnings() {
+            report(warning);
+        }
+    }
+
+    if problems.is_empty() {
+        println!(
+            "{}: valid, {}",
+            input.file.display(),
+            count(member_count, "member")
+        );
+        Ok(())
+    } else {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        Err(Failure::Invalid)
+    }
+}
+
+/// `1 row`, `2 rows`
+fn count(number: usize, noun: &str) -> String {
+    if number == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", number, noun)
+    }
+}
+
+fn encoding_name(encoding: TextEncoding) -> &'static str {
+    match encoding {
+        TextEncoding::Utf8 => "utf-8",
+        TextEncoding::Latin1 => "latin-1",
+        TextEncoding::Windows1252 => "windows-1252",
+    }
+}
@@ -0,0 +1,148 @@
+//! Rendering of cells and aligned tables for the terminal
+
+use std::io::{self, Write};
+use xpt::{format_numeric, XPTValue, XPTVariable};
+
+/// Rows a table reads before fixing its column widths; longer values after that are cut
+const TABLE_SAMPLE_ROWS: usize = 1_000;
+/// Widest a table column grows, in characters
+const MAX_COLUMN_WIDTH: usize = 40;
+
+/// Renders a cell for display: numbers with their SAS format unless `raw`, missing values as
+/// SAS prints them (`.`, `.A`, `._`)
+pub fn display_value(value: &XPTValue, variable: &XPTVariable, raw: bool) -> String {
+    match value {
+        XPTValue::Character(text) => text.clone(),
+        XPTValue::Numeric(number) if !raw => {
+            format_numeric(*number, &variable.format).unwrap_or_else(|| number.to_string())
+        }
+        XPTValue::Numeric(number) => number.to_string(),
+        XPTValue::Missing(missing) => missing.code(),
+    }
+}
+
+/// Horizontal alignment of a table column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// Writes rows as a table with aligned columns
+///
+/// Column widths come from the header and the first rows, so the table can stream any number
+/// of rows; values wider than their column after that are cut short with `…`.
+pub struct TableWriter<W> {
+    out: W,
+    headers: Vec<String>,
+    alignments: Vec<Align>,
+    pending: Vec<Vec<String>>,
+    /// Column widths, fixed once the sample rows have been seen
+    widths: Option<Vec<usize>>,
+}
+
+impl<W: Write> TableWriter<W> {
+    pub fn new(out: W, columns: Vec<(String, Align)>) -> Self {
+        let (headers, alignments) = columns.into_iter().unzip();
+        TableWriter {
+            out,
+            headers,
+            alignments,
+            pending: Vec::new(),
+            widths: None,
+        }
+    }
+
+    pub fn write_row(&mut self, row: Vec<String>) -> io::Result<()> {
+        match &self.widths {
+            Some(widths) => {
+                let line = self.line(&row, widths);
+                writeln!(self.out, "{}", line)
+            }
+            None => {
+                self.pending.push(row);
+                if self.pending.len() >= TABLE_SAMPLE_ROWS {
+                    self.flush_pending()?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes any rows still held back and gives back the output
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.widths.is_none() {
+            self.flush_pending()?;
+        }
+        Ok(self.out)
+    }
+
+    fn flush_pending(&mut self) -> io::Result<()> {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.pending {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count().min(MAX_COLUMN_WIDTH));
+            }
+        }
+
+        let header = self.line(&self.headers, &widths);
+        writeln!(self.out, "{}", header)?;
+        let rule: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+        writeln!(self.out, "{}", rule.join("  "))?;
+        for row in std::mem::take(&mut self.pending) {
+            let line = self.line(&row, &widths);
+            writeln!(self.out, "{}", line)?;
+        }
+        self.widths = Some(widths);
+        Ok(())
+    }
+
+    fn line(&self, row: &[String], widths: &[usize]) -> String {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .zip(&self.alignments)
+            .map(|((cell, &width), align)| {
+                let cell = truncate(cell, width);
+                match align {
+                    Align::Left => format!("{:<width$}", cell, width = width),
+                    Align::Right => format!("{:>width$}", cell, width = width),
+                }
+            })
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    }
+}
+
+/// Cuts `text` to `width` characters, ending it with `…` when anything was dropped
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let kept: String = text.chars().take(width.saturating_sub(1)).collect();
+        format!("{}…", kept)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_table_columns() {
+        let columns = vec![
+            ("NAME".to_string(), Align::Left),
+            ("AGE".to_string(), Align::Right),
+        ];
+        let mut table = TableWriter::new(Vec::new(), columns);
+        table
+            .write_row(vec!["Alice".to_string(), "34".to_string()])
+            .unwrap();
+        table
+            .write_row(vec!["Bo".to_string(), "101".to_string()])
+            .unwrap();
+        let text = String::from_utf8(table.finish().unwrap()).unwrap();
+        assert_eq!(text, "NAME   AGE\n-----  ---\nAlice   34\nBo     101\n");
+        assert_eq!(truncate("abcdef", 4), "abc…");
+    }
+}
@@ -0,0 +1,124 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../xpt/tests/fixtures/truncated_numerics.xpt")
+}
+
+fn xpt(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_xpt"))
+        .args(args)
+        .output()
+        .expect("the xpt binary runs")
+}
+
+/// A path in the temp directory no other test or concurrent run writes to
+fn temp_path(test: &str, extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "xpt-cli-{}-{}.{}",
+        std::process::id(),
+        test,
+        extension
+    ))
+}
+
+/// Converts the fixture to a file with the given extension and checks what was written
+fn converts_to(test: &str, extension: &str, check: impl FnOnce(&[u8])) {
+    let output = temp_path(test, extension);
+    let status = xpt(&[
+        "convert",
+        fixture().to_str().unwrap(),
+        output.to_str().unwrap(),
+    ])
+    .status;
+    let written = std::fs::read(&output).unwrap_or_default();
+    std::fs::remove_file(&output).ok();
+    assert!(status.success());
+    check(&written);
+}
+
+#[test]
+fn lists_members_and_prints_rows() {
+    let fixture = fixture();
+    let fixture = fixture.to_str().unwrap();
+
+    let info = xpt(&["info", fixture]);
+    assert!(info.status.success());
+    let text = String::from_utf8(info.stdout).unwrap();
+    assert!(text.contains("1. TRUNC (Truncated numerics)"), "{}", text);
+
+    let head = xpt(&["head", fixture, "-n", "1", "--format", "csv"]);
+    assert!(head.status.success());
+    let csv = String::from_utf8(head.stdout).unwrap();
+    assert_eq!(csv.lines().count(), 2, "{}", csv);
+}
+
+#[test]
+fn exit_status_tells_valid_invalid_and_unreadable_files_apart() {
+    let fixture = fixture();
+    assert_eq!(
+        xpt(&["validate", fixture.to_str().unwrap()]).status.code(),
+        Some(0)
+    );
+
+    let truncated = temp_path(
+        "exit_status_tells_valid_invalid_and_unreadable_files_apart",
+        "xpt",
+    );
+    let data = std::fs::read(&fixture).unwrap();
+    std::fs::write(&truncated, &data[..data.len() - 13]).unwrap();
+    let status = xpt(&["validate", truncated.to_str().unwrap()]).status;
+    std::fs::remove_file(&truncated).ok();
+    assert_eq!(status.code(), Some(1));
+
+    let missing = xpt(&["validate", "/nonexistent/file.xpt"]);
+    assert_eq!(missing.status.code(), Some(3));
+}
+
+#[test]
+fn converts_a_member_to_csv() {
+    converts_to("converts_a_member_to_csv", "csv", |written| {
+        let text = String::from_utf8_lossy(written);
+        assert_eq!(text.lines().count(), 4, "{}", text);
+    });
+}
+
+#[test]
+fn converts_a_member_to_a_transport_file() {
+    let original = std::fs::read(fixture()).unwrap();
+    converts_to("converts_a_member_to_a_transport_file", "xpt", |written| {
+        assert_eq!(written, original)
+    });
+}
+
+#[test]
+fn converts_a_member_to_parquet() {
+    converts_to("converts_a_member_to_parquet", "parquet", |written| {
+        assert!(written.starts_with(b"PAR1") && written.ends_with(b"PAR1"))
+    });
+}
+
+#[test]
+fn converts_a_member_to_an_arrow_file() {
+    converts_to("converts_a_member_to_an_arrow_file", "feather", |written| {
+        assert!(written.starts_with(b"ARROW1") && written.ends_with(b"ARROW1"))
+    });
+}
+
+#[test]
+fn converts_every_member_to_an_excel_workbook() {
+    converts_to(
+        "converts_every_member_to_an_excel_workbook",
+        "xlsx",
+        |written| assert!(written.starts_with(b"PK\x03\x04")),
+    );
+}
+
+#[test]
+fn converts_a_member_to_dataset_json() {
+    converts_to("converts_a_member_to_dataset_json", "json", |written| {
+        let text = String::from_utf8_lossy(written);
+        assert!(text.contains(r#""datasetJSONVersion":"1.1.0""#), "{}", text);
+        assert!(text.contains(r#""records":3"#), "{}", text);
+    });
+}
@@ -0,0 +1,381 @@
+//! Columnar Apache Arrow representation of datasets and Arrow IPC (Feather) export, behind the
+//! `arrow` feature
+//!
+//! Numeric variables become `Float64` columns and character variables `Utf8` columns. With
+//! [`ArrowOptions::logical_dates`], variables with a SAS date format become `Date32` columns and
+//! those with a datetime format `Timestamp(Microsecond)` columns, and a date or datetime beyond
+//! the years SAS supports is an error. Missing values of every kind become nulls, so which
+//! special missing value a cell held, such as `.A`, is lost; export to a transport file to keep
+//! it.
+//!
+//! Every field carries the `label`, `format` and `informat` of its variable as field metadata,
+//! and the schema carries the dataset `name` and `label`, leaving out empty values.
+
+use crate::sas_format::{unix_days, unix_micros, Temporal};
+use crate::xpt_error::{ErrorCode, Section, XptError, XptResult};
+use crate::xpt_parser::{VariableType, XPTDataset, XPTRow, XPTValue, XPTVariable};
+use arrow_array::builder::{
+    ArrayBuilder, Date32Builder, Float64Builder, StringBuilder, TimestampMicrosecondBuilder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+/// Options for [`to_record_batch`] and [`write_arrow_ipc`]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ArrowOptions {
+    /// Convert variables with a SAS date or datetime format to Arrow dates and timestamps
+    /// instead of SAS day and second counts
+    pub logical_dates: bool,
+    /// Largest number of rows in a record batch written by [`write_arrow_ipc`], which is also
+    /// the number of rows held in memory
+    pub batch_size: usize,
+}
+
+impl Default for ArrowOptions {
+    fn default() -> Self {
+        ArrowOptions {
+            logical_dates: true,
+            batch_size: 65_536,
+        }
+    }
+}
+
+/// Arrow schema of the columns a dataset converts to
+pub fn arrow_schema(dataset: &XPTDataset, options: &ArrowOptions) -> SchemaRef {
+    BatchBuilder::new(dataset, options.logical_dates).schema
+}
+
+/// Converts the rows of a parsed dataset into a single record batch
+pub fn to_record_batch(dataset: &XPTDataset, options: &ArrowOptions) -> XptResult<RecordBatch> {
+    let mut builder = BatchBuilder::new(dataset, options.logical_dates);
+    for row in &dataset.rows {
+        builder.append(row)?;
+    }
+    builder.finish()
+}
+
+/// Writes the variables of `dataset` and the rows yielded by `rows` as an Arrow IPC file, the
+/// format Feather version 2 files use
+///
+/// Only the metadata of `dataset` is used; rows are converted one record batch at a time.
+/// Errors yielded by `rows` stop the export and are passed through. The output is buffered, so
+/// `out` need not be.
+pub fn write_arrow_ipc<W, E>(
+    out: W,
+    dataset: &XPTDataset,
+    rows: impl IntoIterator<Item = Result<XPTRow, E>>,
+    options: &ArrowOptions,
+) -> Result<W, E>
+where
+    W: Write,
+    E: From<io::Error> + From<XptError>,
+{
+    let mut batches = BatchBuilder::new(dataset, options.logical_dates);
+    let mut writer = FileWriter::try_new_buffered(out, &batches.schema).map_err(ipc_error)?;
+    let batch_size = options.batch_size.max(1);
+    for row in rows {
+        batches.append(&row?)?;
+        if batches.len() == batch_size {
+            writer.write(&batches.finish()?).map_err(ipc_error)?;
+        }
+    }
+    if batches.len() > 0 {
+        writer.write(&batches.finish()?).map_err(ipc_error)?;
+    }
+    let out = writer.into_inner().map_err(ipc_error)?;
+    Ok(out.into_inner().map_err(|error| error.into_error())?)
+}
+
+fn ipc_error(error: ArrowError) -> XptError {
+    XptError::new(
+        ErrorCode::Io,
+        Section::File,
+        format!("Unable to write the Arrow output: {}", error),
+    )
+}
+
+/// Gathers rows into the columns of the next record batch
+pub(crate) struct BatchBuilder {
+    pub(crate) schema: SchemaRef,
+    builders: Vec<Builder>,
+    rows: usize,
+    /// Rows appended since the builder was created, to name the row of a value in errors
+    appended: usize,
+}
+
+impl BatchBuilder {
+    pub(crate) fn new(dataset: &XPTDataset, logical_dates: bool) -> Self {
+        let columns: Vec<Column> = dataset
+            .variables
+            .iter()
+            .map(|variable| Column::new(variable, logical_dates))
+            .collect();
+        let fields: Vec<Field> = dataset
+            .variables
+            .iter()
+            .zip(&columns)
+            .map(|(variable, column)| {
+                Field::new(&variable.name, column.data_type(), true)
+                    .with_metadata(variable_metadata(variable))
+            })
+            .collect();
+        let metadata = [("name", &dataset.title), ("label", &dataset.label)]
+            .into_iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect();
+        BatchBuilder {
+            schema: Arc::new(Schema::new_with_metadata(fields, metadata)),
+            builders: columns.into_iter().map(Column::builder).collect(),
+            rows: 0,
+            appended: 0,
+        }
+    }
+
+    /// Number of rows gathered since the last batch
+    pub(crate) fn len(&self) -> usize {
+        self.rows
+    }
+
+    /// Appends a row, failing on a date or datetime its column cannot hold
+    pub(crate) fn append(&mut self, row: &XPTRow) -> XptResult<()> {
+        self.appended += 1;
+        for (index, builder) in self.builders.iter_mut().enumerate() {
+            builder
+                .append(row.values.get(index))
+                .map_err(|(number, kind)| {
+                    XptError::new(
+                        ErrorCode::InvalidValue,
+                        Section::Observations,
+                        format!(
+                            "{} in row {} holds {}, which is outside the {} range SAS supports",
+                            self.schema.field(index).name(),
+                            self.appended,
+                            number,
+                            kind
+                        ),
+                    )
+                })?;
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Moves the gathered rows into a record batch, leaving the builder empty
+    pub(crate) fn finish(&mut self) -> XptResult<RecordBatch> {
+        let arrays: Vec<ArrayRef> = self.builders.iter_mut().map(Builder::finish).collect();
+        self.rows = 0;
+        RecordBatch::try_new(self.schema.clone(), arrays).map_err(|error| {
+            XptError::new(
+                ErrorCode::InvalidValue,
+                Section::Observations,
+                format!("Unable to assemble the Arrow columns: {}", error),
+            )
+        })
+    }
+}
+
+/// Label, format and informat of a variable, leaving out those that are not set
+pub(crate) fn variable_metadata(variable: &XPTVariable) -> HashMap<String, String> {
+    [
+        ("label", variable.label.clone()),
+        ("format", variable.format.to_string()),
+        ("informat", variable.informat.to_string()),
+    ]
+    .into_iter()
+    .filter(|(_, value)| !value.is_empty())
+    .map(|(key, value)| (key.to_string(), value))
+    .collect()
+}
+
+/// Arrow type a variable converts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Number,
+    Text,
+    Date,
+    Timestamp,
+}
+
+impl Column {
+    fn new(variable: &XPTVariable, logical_dates: bool) -> Self {
+        match variable.var_type {
+            VariableType::Character => Column::Text,
+            VariableType::Numeric if logical_dates => match Temporal::of(&variable.format) {
+                Some(Temporal::Date) => Column::Date,
+                Some(Temporal::DateTime) => Column::Timestamp,
+                Some(Temporal::Time) | None => Column::Number,
+            },
+            VariableType::Numeric => Column::Number,
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            Column::Number => DataType::Float64,
+            Column::Text => DataType::Utf8,
+            Column::Date => DataType::Date32,
+            Column::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, None),
+        }
+    }
+
+    fn builder(self) -> Builder {
+        match self {
+            Column::Number => Builder::Number(Float64Builder::new()),
+            Column::Text => Builder::Text(StringBuilder::new()),
+            Column::Date => Builder::Date(Date32Builder::new()),
+            Column::Timestamp => Builder::Timestamp(TimestampMicrosecondBuilder::new()),
+        }
+    }
+}
+
+/// Values of one column gathered for the next record batch
+enum Builder {
+    Number(Float64Builder),
+    Text(StringBuilder),
+    Date(Date32Builder),
+    Timestamp(TimestampMicrosecondBuilder),
+}
+
+impl Builder {
+    /// Appends a cell, as a null when it is missing, whatever its missing value code, absent
+    /// from a short row, or of the wrong type for its column
+    ///
+    /// A date or datetime out of range is not appended; the number and the kind of value its
+    /// column holds are returned instead.
+    fn append(&mut self, value: Option<&XPTValue>) -> Result<(), (f64, &'static str)> {
+        let number = match value {
+            Some(XPTValue::Numeric(number)) => Some(*number),
+            _ => None,
+        };
+        match self {
+            Builder::Number(builder) => builder.append_option(number),
+            Builder::Text(builder) => match value {
+                Some(XPTValue::Character(text)) => builder.append_value(text),
+                _ => builder.append_null(),
+            },
+            Builder::Date(builder) => {
+                let days = number.map(|number| {
+                    unix_days(number)
+                        .and_then(|days| i32::try_from(days).ok())
+                        .ok_or((number, "date"))
+                });
+                builder.append_option(days.transpose()?);
+            }
+            Builder::Timestamp(builder) => {
+                let micros = number.map(|number| unix_micros(number).ok_or((number, "datetime")));
+                builder.append_option(micros.transpose()?);
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Builder::Number(builder) => ArrayBuilder::finish(builder),
+            Builder::Text(builder) => ArrayBuilder::finish(builder),
+            Builder::Date(builder) => ArrayBuilder::finish(builder),
+            Builder::Timestamp(builder) => ArrayBuilder::finish(builder),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::visits;
+    use crate::xpt_parser::{MissingValue, XPTParser};
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Date32Type, Float64Type, TimestampMicrosecondType};
+    use arrow_array::Array;
+    use arrow_ipc::reader::FileReader;
+    use std::io::Cursor;
+
+    const TRUNCATED_NUMERICS: &[u8] = include_bytes!("../tests/fixtures/truncated_numerics.xpt");
+
+    #[test]
+    fn converts_rows_to_typed_columns_with_metadata() {
+        let batch = to_record_batch(&visits(), &ArrowOptions::default()).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+
+        let dates = batch.column(1).as_primitive::<Date32Type>();
+        assert_eq!(dates.value(0), 18_993);
+        assert!(dates.is_null(1));
+        let times = batch.column(2).as_primitive::<TimestampMicrosecondType>();
+        assert_eq!(times.value(0), 1_641_040_496_000_000);
+        assert!(times.is_null(1));
+
+        let schema = batch.schema();
+        assert_eq!(schema.metadata()["label"], "Subject Visits");
+        let field = schema.field(1);
+        assert_eq!(field.metadata()["label"], "Start date");
+        assert_eq!(field.metadata()["format"], "DATE9.");
+        assert!(!field.metadata().contains_key("informat"));
+
+        let options = ArrowOptions {
+            logical_dates: false,
+            ..ArrowOptions::default()
+        };
+        let batch = to_record_batch(&visits(), &options).unwrap();
+        assert_eq!(
+            batch.column(1).as_primitive::<Float64Type>().value(0),
+            22_646.0
+        );
+    }
+
+    #[test]
+    fn writes_an_ipc_file_in_batches() {
+        let dataset = &XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap()[0];
+        let options = ArrowOptions {
+            batch_size: 2,
+            ..ArrowOptions::default()
+        };
+        let rows = dataset.rows.iter().cloned().map(Ok::<_, XptError>);
+        let file = write_arrow_ipc(Vec::new(), dataset, rows, &options).unwrap();
+
+        let reader = FileReader::try_new(Cursor::new(file), None).unwrap();
+        assert_eq!(reader.schema().metadata()["name"], dataset.title);
+        let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        assert_eq!(
+            batches
+                .iter()
+                .map(RecordBatch::num_rows)
+                .collect::<Vec<_>>(),
+            [2, 1]
+        );
+        assert_eq!(
+            batches[0],
+            to_record_batch(dataset, &options).unwrap().slice(0, 2)
+        );
+    }
+
+    #[test]
+    fn drops_missing_value_codes_and_rejects_dates_out_of_range() {
+        // `.A` and `.Z` become nulls that cannot be told from a standard missing value
+        let mut dataset = visits();
+        dataset.rows[0].values[3] = XPTValue::Missing(MissingValue::Letter('Z'));
+        let options = ArrowOptions {
+            logical_dates: false,
+            ..ArrowOptions::default()
+        };
+        let batch = to_record_batch(&dataset, &options).unwrap();
+        assert_eq!(batch.column(2).null_count(), 1);
+        assert_eq!(batch.column(3).null_count(), 1);
+        assert!(batch.column(2).is_null(1) && batch.column(3).is_null(0));
+
+        dataset.rows[1].values[1] = XPTValue::Numeric(1e10);
+        let error = to_record_batch(&dataset, &ArrowOptions::default()).unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidValue);
+        assert_eq!(
+            error.message,
+            "SVSTDT in row 2 holds 10000000000, which is outside the date range SAS supports"
+        );
+        assert!(to_record_batch(&dataset, &options).is_ok());
+    }
+}
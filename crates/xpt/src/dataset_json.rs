@@ -0,0 +1,702 @@
+//! Reading and writing CDISC Dataset-JSON v1.1 files
+//!
+//! [`read_dataset_json`] reads a file in either the JSON or the NDJSON form into the same
+//! [`XPTDataset`] model the transport file parser produces, and [`write_dataset_json`] writes
+//! any dataset out as Dataset-JSON, streaming its rows.
+//!
+//! `string` and `URI` columns are read as character variables, and `integer`, `decimal`,
+//! `float`, `double` and `boolean` columns as numeric ones. `date`, `datetime` and `time` columns
+//! whose `targetDataType` is `integer` are read as numeric variables holding SAS dates, datetimes
+//! or times; without it they keep their ISO 8601 text in a character variable. Writing maps the
+//! other way: numeric variables with a SAS date, datetime or time format are written as ISO 8601
+//! text in `date`, `datetime` or `time` columns with a `targetDataType` of `integer`, other
+//! numeric variables as `double` columns and character variables as `string` columns. Variables
+//! with a duration format such as `TIME`, whose values may run past a day, are `double` columns
+//! keeping their format in `displayFormat`, and only time-of-day formats such as `TOD` or
+//! `E8601TM` make `time` columns. A date or time its column cannot show as ISO 8601 text stops
+//! the export with an error. Missing values, special ones included, are written as `null`.
+
+use crate::sas_format::{
+    format_numeric, parse_iso_date, parse_iso_datetime, parse_iso_time, Temporal,
+    SAS_EPOCH_OFFSET_DAYS, SECONDS_PER_DAY,
+};
+use crate::text_encoding::TextEncoding;
+use crate::xpt_error::{ErrorCode, Section, XptError, XptResult};
+use crate::xpt_parser::{
+    Justification, MissingValue, SasDateTime, VariableFormat, VariableType, XPTDataset,
+    XPTLibraryHeader, XPTRow, XPTValue, XPTVariable,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version written to `datasetJSONVersion`; files of any 1.1.x version are read
+const VERSION: &str = "1.1.0";
+/// Length given to numeric variables read from Dataset-JSON, that of a full double
+const NUMERIC_LENGTH: usize = 8;
+
+/// Options for [`write_dataset_json`]; the default writes the JSON form without study metadata
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DatasetJsonOptions {
+    /// Write the NDJSON form: the metadata on the first line, then one row per line
+    pub ndjson: bool,
+    /// Organization that generated the file
+    pub originator: Option<String>,
+    /// OID of the study in the Define-XML the dataset is described by
+    pub study_oid: Option<String>,
+    /// OID of the metadata version in that Define-XML
+    pub metadata_version_oid: Option<String>,
+}
+
+/// Top-level attributes of a Dataset-JSON file
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Metadata {
+    #[serde(rename = "datasetJSONCreationDateTime")]
+    creation_date_time: String,
+    #[serde(rename = "datasetJSONVersion")]
+    version: String,
+    #[serde(rename = "fileOID", default, skip_serializing_if = "Option::is_none")]
+    file_oid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    db_last_modified_date_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    originator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_system: Option<SourceSystem>,
+    #[serde(rename = "studyOID", default, skip_serializing_if = "Option::is_none")]
+    study_oid: Option<String>,
+    #[serde(
+        rename = "metaDataVersionOID",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    metadata_version_oid: Option<String>,
+    #[serde(rename = "itemGroupOID")]
+    item_group_oid: String,
+    records: usize,
+    name: String,
+    label: String,
+    columns: Vec<Column>,
+    /// Rows of the JSON form; NDJSON files hold them on the lines after the metadata, and the
+    /// writer streams them after the other attributes
+    #[serde(default, skip_serializing)]
+    rows: Option<Vec<Vec<Value>>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SourceSystem {
+    name: String,
+    version: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Column {
+    #[serde(rename = "itemOID")]
+    item_oid: String,
+    name: String,
+    label: String,
+    data_type: DataType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target_data_type: Option<TargetDataType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    length: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    display_format: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_sequence: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DataType {
+    String,
+    Integer,
+    Decimal,
+    Float,
+    Double,
+    Boolean,
+    Datetime,
+    Date,
+    Time,
+    #[serde(rename = "URI")]
+    Uri,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TargetDataType {
+    Integer,
+    Decimal,
+}
+
+/// How the cells of a column are read into the dataset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+    Text,
+    Number,
+    Boolean,
+    Temporal(Temporal),
+}
+
+impl Cell {
+    fn of(column: &Column) -> Self {
+        let temporal = |kind| {
+            if column.target_data_type == Some(TargetDataType::Integer) {
+                Cell::Temporal(kind)
+            } else {
+                Cell::Text
+            }
+        };
+        match column.data_type {
+            DataType::String | DataType::Uri => Cell::Text,
+            DataType::Integer | DataType::Decimal | DataType::Float | DataType::Double => {
+                Cell::Number
+            }
+            DataType::Boolean => Cell::Boolean,
+            DataType::Date => temporal(Temporal::Date),
+            DataType::Datetime => temporal(Temporal::DateTime),
+            DataType::Time => temporal(Temporal::Time),
+        }
+    }
+
+    /// Reads one cell, or returns `None` when it does not hold a value of the column's type
+    fn read(self, value: &Value) -> Option<XPTValue> {
+        let missing = XPTValue::Missing(MissingValue::Standard);
+        let value = match (self, value) {
+            (Cell::Text, Value::Null) => XPTValue::Character(String::new()),
+            (Cell::Text, Value::String(text)) => XPTValue::Character(text.clone()),
+            (Cell::Text, other) => XPTValue::Character(other.to_string()),
+            (_, Value::Null) => missing,
+            (Cell::Number, Value::Number(number)) => XPTValue::Numeric(number.as_f64()?),
+            // Decimals are written as strings so that no precision is lost in transit
+            (Cell::Number, Value::String(text)) => XPTValue::Numeric(text.trim().parse().ok()?),
+            (Cell::Boolean, Value::Bool(flag)) => XPTValue::Numeric(f64::from(u8::from(*flag))),
+            (Cell::Temporal(kind), Value::String(text)) => XPTValue::Numeric(match kind {
+                Temporal::Date => parse_iso_date(text)?,
+                Temporal::DateTime => parse_iso_datetime(text)?,
+                Temporal::Time => parse_iso_time(text)?,
+            }),
+            (Cell::Temporal(_), Value::Number(number)) => XPTValue::Numeric(number.as_f64()?),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Format shown for the variable when the column has no `displayFormat`
+    fn default_format(self) -> VariableFormat {
+        let (name, width) = match self {
+            Cell::Temporal(Temporal::Date) => ("E8601DA", 10),
+            Cell::Temporal(Temporal::DateTime) => ("E8601DT", 19),
+            Cell::Temporal(Temporal::Time) => ("E8601TM", 8),
+            Cell::Text | Cell::Number | Cell::Boolean => return VariableFormat::default(),
+        };
+        VariableFormat {
+            name: name.to_string(),
+            width,
+            decimals: 0,
+        }
+    }
+}
+
+/// Reads a Dataset-JSON v1.1 file, in the JSON or the NDJSON form
+///
+/// Cells that do not hold a value of their column's type are read as missing and reported in
+/// the dataset's warnings, as is a `records` count that does not match the rows.
+pub fn read_dataset_json(data: &[u8]) -> XptResult<XPTDataset> {
+    let mut values = serde_json::Deserializer::from_slice(data).into_iter::<Value>();
+    let first = values
+        .next()
+        .ok_or_else(|| invalid("The file holds no Dataset-JSON metadata"))?
+        .map_err(json_error)?;
+    let version = first
+        .get("datasetJSONVersion")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    if !version.starts_with("1.1") {
+        return Err(invalid(format!(
+            "Dataset-JSON version {:?} is not supported; only version 1.1 is",
+            version
+        )));
+    }
+    let mut metadata: Metadata = serde_json::from_value(first).map_err(json_error)?;
+    let rows = match metadata.rows.take() {
+        Some(rows) => rows,
+        None => values
+            .map(|row| row.and_then(serde_json::from_value::<Vec<Value>>))
+            .collect::<Result<_, _>>()
+            .map_err(json_error)?,
+    };
+
+    let cells: Vec<Cell> = metadata.columns.iter().map(Cell::of).collect();
+    let mut failures = vec![0usize; cells.len()];
+    let rows: Vec<XPTRow> = rows
+        .iter()
+        .map(|row| XPTRow {
+            values: cells
+                .iter()
+                .enumerate()
+                .map(|(index, cell)| {
+                    let value = row.get(index).unwrap_or(&Value::Null);
+                    cell.read(value).unwrap_or_else(|| {
+                        failures[index] += 1;
+                        XPTValue::Missing(MissingValue::Standard)
+                    })
+                })
+                .collect(),
+        })
+        .collect();
+
+    let mut warnings = Vec::new();
+    if metadata.records != rows.len() {
+        warnings.push(format!(
+            "The file declares {} records but holds {} rows",
+            metadata.records,
+            rows.len()
+        ));
+    }
+    let mut offset = 0;
+    let mut variables = Vec::with_capacity(cells.len());
+    for (index, (column, &cell)) in metadata.columns.iter().zip(&cells).enumerate() {
+        if failures[index] > 0 {
+            let data_type = serde_json::to_value(column.data_type).unwrap_or_default();
+            warnings.push(format!(
+                "{} values of {} are not valid {} values and were read as missing",
+                failures[index],
+                column.name,
+                data_type.as_str().unwrap_or_default()
+            ));
+        }
+        let (var_type, length, justification) = match cell {
+            Cell::Text => {
+                let longest = rows
+                    .iter()
+                    .filter_map(|row| match &row.values[index] {
+                        XPTValue::Character(text) => Some(text.len()),
+                        _ => None,
+                    })
+                    .max()
+                    .unwrap_or(0);
+                let length = column.length.unwrap_or(0).max(longest).max(1);
+                (VariableType::Character, length, Justification::Left)
+            }
+            _ => (VariableType::Numeric, NUMERIC_LENGTH, Justification::Right),
+        };
+        let format = match &column.display_format {
+            Some(description) => {
+                let mut format = VariableFormat::default();
+                format.apply_description(description);
+                format
+            }
+            None => cell.default_format(),
+        };
+        variables.push(XPTVariable {
+            name: column.name.clone(),
+            label: column.label.clone(),
+            var_type,
+            length,
+            format,
+            informat: VariableFormat::default(),
+            justification,
+            number: u16::try_from(index + 1).unwrap_or(u16::MAX),
+            offset,
+        });
+        offset += length;
+    }
+
+    let created = parse_timestamp(&metadata.creation_date_time);
+    let modified = metadata
+        .db_last_modified_date_time
+        .as_deref()
+        .and_then(parse_timestamp);
+    Ok(XPTDataset {
+        title: metadata.name,
+        label: metadata.label,
+        dataset_type: String::new(),
+        sas_version: String::new(),
+        os_name: String::new(),
+        created_date: created,
+        modified_date: modified,
+        library: XPTLibraryHeader {
+            created,
+            modified,
+            ..XPTLibraryHeader::default()
+        },
+        variables,
+        rows,
+        encoding: TextEncoding::Utf8,
+        warnings,
+    })
+}
+
+/// Writes the variables of `dataset` and the `records` rows yielded by `rows` as Dataset-JSON
+///
+/// Only the metadata of `dataset` is used. The row count is written ahead of the rows, as the
+/// format asks, so `records` must match the number of rows yielded; a mismatch is reported as an
+/// error once the rows have been written. Errors yielded by `rows` stop the export and are
+/// passed through, and so does a date or time that has no ISO 8601 form. Nothing is buffered,
+/// so a file should be wrapped in a `BufWriter`.
+pub fn write_dataset_json<W, E>(
+    mut out: W,
+    dataset: &XPTDataset,
+    records: usize,
+    rows: impl IntoIterator<Item = Result<XPTRow, E>>,
+    options: &DatasetJsonOptions,
+) -> Result<W, E>
+where
+    W: Write,
+    E: From<io::Error> + From<XptError>,
+{
+    let cells: Vec<Option<Temporal>> = dataset
+        .variables
+        .iter()
+        .map(|variable| match variable.var_type {
+            VariableType::Numeric => Temporal::of(&variable.format)
+                .filter(|&kind| kind != Temporal::Time || !is_duration(&variable.format)),
+            VariableType::Character => None,
+        })
+        .collect();
+    let metadata = Metadata {
+        creation_date_time: now(),
+        version: VERSION.to_string(),
+        file_oid: None,
+        db_last_modified_date_time: dataset.modified_date.map(|date| date.to_string()),
+        originator: options.originator.clone(),
+        source_system: Some(SourceSystem {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }),
+        study_oid: options.study_oid.clone(),
+        metadata_version_oid: options.metadata_version_oid.clone(),
+        item_group_oid: format!("IG.{}", dataset.title),
+        records,
+        name: dataset.title.clone(),
+        label: dataset.label.clone(),
+        columns: dataset
+            .variables
+            .iter()
+            .zip(&cells)
+            .map(|(variable, &temporal)| column(dataset, variable, temporal))
+            .collect(),
+        rows: None,
+    };
+
+    let header = serde_json::to_vec(&metadata).map_err(io::Error::from)?;
+    if options.ndjson {
+        out.write_all(&header)?;
+        out.write_all(b"\n")?;
+    } else {
+        // Reopen the object to append the rows after the other attributes
+        out.write_all(&header[..header.len() - 1])?;
+        out.write_all(b",\"rows\":[")?;
+    }
+    let mut written = 0;
+    let mut cells_json = Vec::with_capacity(cells.len());
+    for row in rows {
+        let row = row?;
+        cells_json.clear();
+        for (index, (variable, &temporal)) in dataset.variables.iter().zip(&cells).enumerate() {
+            let value = row
+                .values
+                .get(index)
+                .unwrap_or(&XPTValue::Missing(MissingValue::Standard));
+            cells_json.push(cell_value(value, variable, temporal, written + 1)?);
+        }
+        if !options.ndjson && written > 0 {
+            out.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut out, &cells_json).map_err(io::Error::from)?;
+        if options.ndjson {
+            out.write_all(b"\n")?;
+        }
+        written += 1;
+    }
+    if !options.ndjson {
+        out.write_all(b"]}")?;
+    }
+    out.flush()?;
+
+    if written != records {
+        return Err(XptError::new(
+            ErrorCode::InvalidValue,
+            Section::Observations,
+            format!(
+                "{} declares {} records but {} rows were written",
+                dataset.title, records, written
+            ),
+        )
+        .into());
+    }
+    Ok(out)
+}
+
+/// Column describing a variable, with the type its values are written as
+fn column(dataset: &XPTDataset, variable: &XPTVariable, temporal: Option<Temporal>) -> Column {
+    let data_type = match (variable.var_type, temporal) {
+        (VariableType::Character, _) => DataType::String,
+        (VariableType::Numeric, Some(Temporal::Date)) => DataType::Date,
+        (VariableType::Numeric, Some(Temporal::DateTime)) => DataType::Datetime,
+        (VariableType::Numeric, Some(Temporal::Time)) => DataType::Time,
+        (VariableType::Numeric, None) => DataType::Double,
+    };
+    let display_format = variable.format.to_string();
+    Column {
+        item_oid: format!("IT.{}.{}", dataset.title, variable.name),
+        name: variable.name.clone(),
+        label: variable.label.clone(),
+        data_type,
+        target_data_type: temporal.map(|_| TargetDataType::Integer),
+        length: Some(variable.length),
+        display_format: (!display_format.is_empty()).then_some(display_format),
+        key_sequence: None,
+    }
+}
+
+/// Whether a time format shows a duration, which may run past a day, rather than a time of day
+fn is_duration(format: &VariableFormat) -> bool {
+    matches!(
+        format.name.to_ascii_uppercase().as_str(),
+        "TIME" | "HHMM" | "HOUR" | "MMSS"
+    )
+}
+
+/// A cell as written: text, a number, ISO 8601 text for dates and times, or `null` when missing
+///
+/// A date or time its ISO 8601 form cannot show, such as a time of day past midnight, is an
+/// error naming the 1-based `row`.
+fn cell_value(
+    value: &XPTValue,
+    variable: &XPTVariable,
+    temporal: Option<Temporal>,
+    row: usize,
+) -> XptResult<Value> {
+    let number = match value {
+        XPTValue::Character(text) => return Ok(Value::String(text.clone())),
+        XPTValue::Missing(_) => return Ok(Value::Null),
+        XPTValue::Numeric(number) => *number,
+    };
+    let Some(kind) = temporal else {
+        return Ok(serde_json::Number::from_f64(number).map_or(Value::Null, Value::Number));
+    };
+    let (name, kind) = match kind {
+        Temporal::Date => ("E8601DA", "date"),
+        Temporal::DateTime => ("E8601DT", "datetime"),
+        Temporal::Time => ("E8601TM", "time"),
+    };
+    let format = VariableFormat {
+        name: name.to_string(),
+        width: 0,
+        decimals: variable.format.decimals,
+    };
+    let text = format_numeric(number, &format).ok_or_else(|| {
+        XptError::new(
+            ErrorCode::InvalidValue,
+            Section::Observations,
+            format!(
+                "{} in row {} holds {}, which is not a {} Dataset-JSON can hold",
+                variable.name, row, number, kind
+            ),
+        )
+    })?;
+    Ok(Value::String(text))
+}
+
+/// Current time in UTC as an ISO 8601 datetime
+fn now() -> String {
+    let unix_seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let sas_seconds = unix_seconds as f64 + (SAS_EPOCH_OFFSET_DAYS * SECONDS_PER_DAY) as f64;
+    let format = VariableFormat {
+        name: "E8601DT".to_string(),
+        width: 19,
+        decimals: 0,
+    };
+    format_numeric(sas_seconds, &format).unwrap_or_default()
+}
+
+/// Reads the date and time of day of an ISO 8601 timestamp, ignoring any fraction of a second
+/// and UTC offset
+fn parse_timestamp(text: &str) -> Option<SasDateTime> {
+    let text = text.get(..19)?;
+    parse_iso_datetime(text)?;
+    Some(SasDateTime {
+        year: text[0..4].parse().ok()?,
+        month: text[5..7].parse().ok()?,
+        day: text[8..10].parse().ok()?,
+        hour: text[11..13].parse().ok()?,
+        minute: text[14..16].parse().ok()?,
+        second: text[17..19].parse().ok()?,
+    })
+}
+
+fn invalid(message: impl Into<String>) -> XptError {
+    XptError::new(ErrorCode::InvalidDatasetJson, Section::File, message)
+}
+
+fn json_error(error: serde_json::Error) -> XptError {
+    invalid(format!("Not a valid Dataset-JSON file: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::visits;
+    use crate::xpt_parser::XPTParser;
+
+    const TRUNCATED_NUMERICS: &[u8] = include_bytes!("../tests/fixtures/truncated_numerics.xpt");
+
+    fn export(dataset: &XPTDataset, options: &DatasetJsonOptions) -> Vec<u8> {
+        let rows = dataset.rows.iter().cloned().map(Ok::<_, XptError>);
+        write_dataset_json(Vec::new(), dataset, dataset.rows.len(), rows, options).unwrap()
+    }
+
+    #[test]
+    fn writes_columns_from_the_namestr() {
+        let json: Value = serde_json::from_slice(&export(&visits(), &Default::default())).unwrap();
+        assert_eq!(json["datasetJSONVersion"], "1.1.0");
+        assert_eq!(json["itemGroupOID"], "IG.SV");
+        assert_eq!(json["records"], 2);
+        assert_eq!(json["dbLastModifiedDateTime"], "2024-01-02T09:45:10");
+        assert_eq!(
+            json["columns"][1],
+            serde_json::json!({
+                "itemOID": "IT.SV.SVSTDT",
+                "name": "SVSTDT",
+                "label": "Start date",
+                "dataType": "date",
+                "targetDataType": "integer",
+                "length": 8,
+                "displayFormat": "DATE9."
+            })
+        );
+        assert_eq!(json["columns"][3]["dataType"], "double");
+        assert_eq!(
+            json["rows"],
+            serde_json::json!([
+                ["01-001", "2022-01-01", "2022-01-01T12:34:56", 1.5],
+                ["01-002", null, null, 2.5]
+            ])
+        );
+    }
+
+    #[test]
+    fn round_trips_json_and_ndjson() {
+        let dataset = visits();
+        for ndjson in [false, true] {
+            let options = DatasetJsonOptions {
+                ndjson,
+                ..DatasetJsonOptions::default()
+            };
+            let read = read_dataset_json(&export(&dataset, &options)).unwrap();
+            assert_eq!(read.title, "SV");
+            assert_eq!(read.label, "Subject Visits");
+            assert_eq!(read.modified_date, dataset.modified_date);
+            assert!(read.warnings.is_empty(), "{:?}", read.warnings);
+            assert_eq!(read.variables[1].format.to_string(), "DATE9.");
+            assert_eq!(read.variables[1].var_type, VariableType::Numeric);
+            assert_eq!(read.variables[3].offset, 24);
+            assert_eq!(read.rows[0].values, dataset.rows[0].values);
+            assert_eq!(
+                read.rows[1].values[2],
+                XPTValue::Missing(MissingValue::Standard)
+            );
+        }
+    }
+
+    #[test]
+    fn writes_durations_as_doubles_and_times_of_day_as_times() {
+        let mut dataset = visits();
+        dataset.variables[3].format = VariableFormat {
+            name: "TIME".to_string(),
+            width: 8,
+            decimals: 0,
+        };
+        dataset.rows[0].values[3] = XPTValue::Numeric(97_200.0);
+        let file = export(&dataset, &DatasetJsonOptions::default());
+        let json: Value = serde_json::from_slice(&file).unwrap();
+        assert_eq!(json["columns"][3]["dataType"], "double");
+        assert_eq!(json["columns"][3]["displayFormat"], "TIME8.");
+        assert!(json["columns"][3].get("targetDataType").is_none());
+        assert_eq!(json["rows"][0][3], serde_json::json!(97_200.0));
+        let read = read_dataset_json(&file).unwrap();
+        assert!(read.warnings.is_empty(), "{:?}", read.warnings);
+        assert_eq!(read.variables[3].format.to_string(), "TIME8.");
+        assert_eq!(read.rows[0].values[3], XPTValue::Numeric(97_200.0));
+
+        dataset.variables[3].format.name = "TOD".to_string();
+        dataset.rows[0].values[3] = XPTValue::Numeric(45_296.0);
+        let json: Value =
+            serde_json::from_slice(&export(&dataset, &DatasetJsonOptions::default())).unwrap();
+        assert_eq!(json["columns"][3]["dataType"], "time");
+        assert_eq!(json["columns"][3]["targetDataType"], "integer");
+        assert_eq!(json["rows"][0][3], "12:34:56");
+
+        dataset.rows[0].values[3] = XPTValue::Numeric(97_200.0);
+        let rows = dataset.rows.iter().cloned().map(Ok::<_, XptError>);
+        let error =
+            write_dataset_json(Vec::new(), &dataset, 2, rows, &Default::default()).unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidValue);
+        assert_eq!(
+            error.message,
+            "VISITNUM in row 1 holds 97200, which is not a time Dataset-JSON can hold"
+        );
+    }
+
+    #[test]
+    fn reads_strings_decimals_and_partial_dates() {
+        let file = br#"{
+            "datasetJSONCreationDateTime": "2023-06-28T15:38:43+01:00",
+            "datasetJSONVersion": "1.1.0",
+            "itemGroupOID": "IG.AE",
+            "records": 3,
+            "name": "AE",
+            "label": "Adverse Events",
+            "columns": [
+                {"itemOID": "IT.AE.AESTDTC", "name": "AESTDTC", "label": "Start", "dataType": "date"},
+                {"itemOID": "IT.AE.AESTDT", "name": "AESTDT", "label": "Start", "dataType": "date", "targetDataType": "integer"},
+                {"itemOID": "IT.AE.DOSE", "name": "DOSE", "label": "Dose", "dataType": "decimal", "displayFormat": "8.2"}
+            ],
+            "rows": [
+                ["2023-01-15", "2023-01-15", "12.50"],
+                ["2023-02", "2023-02", null]
+            ]
+        }"#;
+        let dataset = read_dataset_json(file).unwrap();
+        assert_eq!(dataset.created_date, SasDateTime::parse("28JUN23:15:38:43"));
+        assert_eq!(dataset.variables[0].var_type, VariableType::Character);
+        assert_eq!(dataset.variables[0].length, 10);
+        assert_eq!(dataset.variables[1].format.to_string(), "E8601DA10.");
+        assert_eq!(dataset.variables[2].format.to_string(), "8.2");
+        assert_eq!(
+            dataset.rows[0].values,
+            [
+                XPTValue::Character("2023-01-15".to_string()),
+                XPTValue::Numeric(23_025.0),
+                XPTValue::Numeric(12.5),
+            ]
+        );
+        assert_eq!(
+            dataset.rows[1].values[1],
+            XPTValue::Missing(MissingValue::Standard)
+        );
+        assert_eq!(dataset.warnings.len(), 2, "{:?}", dataset.warnings);
+    }
+
+    #[test]
+    fn rejects_other_versions_and_mismatched_counts() {
+        let error = read_dataset_json(br#"{"datasetJSONVersion": "1.0.0"}"#).unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidDatasetJson);
+        let error = read_dataset_json(b"not json").unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidDatasetJson);
+
+        let dataset = &XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap()[0];
+        let rows = dataset.rows.iter().cloned().map(Ok::<_, XptError>);
+        let error =
+            write_dataset_json(Vec::new(), dataset, 1, rows, &Default::default()).unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidValue);
+    }
+}
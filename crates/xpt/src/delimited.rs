@@ -0,0 +1,365 @@
+//! Export of datasets as delimited text (CSV, TSV and similar)
+//!
+//! [`write_delimited`] writes rows as they come from any iterator, such as [`Rows`] or the
+//! `rows` of a parsed dataset, so exporting a large file never holds more than one row as text.
+//!
+//! [`Rows`]: crate::Rows
+
+use crate::sas_format::format_numeric;
+use crate::xpt_error::{ErrorCode, Section, XptError};
+use crate::xpt_parser::{MissingValue, XPTDataset, XPTRow, XPTValue, XPTVariable};
+use serde::Deserialize;
+use std::io::{self, Write};
+use std::ops::Range;
+
+/// Character separating the fields of a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Delimiter {
+    Comma,
+    Tab,
+    Semicolon,
+    Pipe,
+}
+
+impl Delimiter {
+    fn as_char(self) -> char {
+        match self {
+            Delimiter::Comma => ',',
+            Delimiter::Tab => '\t',
+            Delimiter::Semicolon => ';',
+            Delimiter::Pipe => '|',
+        }
+    }
+}
+
+/// How fields that could be mistaken for the file structure are protected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Quoting {
+    /// Quote fields holding the delimiter, a quote or a line break, as in RFC 4180
+    Minimal,
+    /// Quote every field
+    All,
+    /// Quote every character value and header, and numbers only when they need it
+    NonNumeric,
+    /// Never quote; write tabs, line breaks, backslashes and the delimiter as `\t`, `\n`, `\r`,
+    /// `\\` and a backslash before the delimiter, as TSV readers expect
+    Escape,
+}
+
+/// What the first record holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HeaderRow {
+    /// Variable names
+    Names,
+    /// Variable labels, or the name of variables without one
+    Labels,
+    /// No header record
+    Omit,
+}
+
+/// How missing numeric values are written
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MissingValues {
+    /// Ordinary missing values as empty fields and special ones as their code (`.A`, `._`)
+    Empty,
+    /// Every missing value as its SAS code: `.`, `.A` to `.Z` or `._`
+    Codes,
+    /// Every missing value, special ones included, as the given text, such as `NA`
+    Text(String),
+}
+
+/// Options for [`write_delimited`]; the default writes RFC 4180 CSV of every row and column
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DelimitedOptions {
+    pub delimiter: Delimiter,
+    pub quoting: Quoting,
+    pub header: HeaderRow,
+    pub missing: MissingValues,
+    /// Write numbers with the SAS format of their variable, as the viewer shows them, instead
+    /// of at full precision
+    pub formatted: bool,
+    /// Variables to write, by name and in this order; all of them when `None`
+    pub columns: Option<Vec<String>>,
+    /// Rows to write, by 0-based index; all of them when `None`
+    pub rows: Option<Range<usize>>,
+}
+
+impl Default for DelimitedOptions {
+    fn default() -> Self {
+        DelimitedOptions {
+            delimiter: Delimiter::Comma,
+            quoting: Quoting::Minimal,
+            header: HeaderRow::Names,
+            missing: MissingValues::Empty,
+            formatted: false,
+            columns: None,
+            rows: None,
+        }
+    }
+}
+
+impl DelimitedOptions {
+    /// Tab-separated values with escaped tabs and line breaks instead of quotes
+    pub fn tsv() -> Self {
+        DelimitedOptions {
+            delimiter: Delimiter::Tab,
+            quoting: Quoting::Escape,
+            ..Self::default()
+        }
+    }
+}
+
+/// Writes the variables of `dataset` and the rows yielded by `rows` as delimited text
+///
+/// Only the metadata of `dataset` is used; `rows` yields every row of the dataset, and the
+/// subset chosen in `options` is taken from it with [`Iterator::skip`], which iterators with a
+/// cheap `nth` such as [`Rows`](crate::Rows) turn into a seek. Errors yielded by `rows` stop
+/// the export and are passed through. Nothing is buffered, so a file should be wrapped in a
+/// `BufWriter`.
+pub fn write_delimited<W, E>(
+    out: W,
+    dataset: &XPTDataset,
+    rows: impl IntoIterator<Item = Result<XPTRow, E>>,
+    options: &DelimitedOptions,
+) -> Result<W, E>
+where
+    W: Write,
+    E: From<io::Error> + From<XptError>,
+{
+    let columns = select_columns(dataset, options.columns.as_deref())?;
+    let variables: Vec<&XPTVariable> = columns
+        .iter()
+        .map(|&index| &dataset.variables[index])
+        .collect();
+    let mut writer = FieldWriter {
+        out,
+        delimiter: options.delimiter.as_char(),
+        quoting: options.quoting,
+    };
+
+    let header = match options.header {
+        HeaderRow::Names => Some(variables.iter().map(|v| v.name.as_str()).collect()),
+        HeaderRow::Labels => Some(
+            variables
+                .iter()
+                .map(|v| {
+                    if v.label.is_empty() {
+                        v.name.as_str()
+                    } else {
+                        v.label.as_str()
+                    }
+                })
+                .collect::<Vec<_>>(),
+        ),
+        HeaderRow::Omit => None,
+    };
+    if let Some(header) = header {
+        let fields: Vec<(&str, bool)> = header.into_iter().map(|name| (name, false)).collect();
+        writer.write_record(&fields)?;
+    }
+
+    let (skip, take) = match &options.rows {
+        Some(range) => (range.start, range.end.saturating_sub(range.start)),
+        None => (0, usize::MAX),
+    };
+    let mut fields = Vec::with_capacity(columns.len());
+    for row in rows.into_iter().skip(skip).take(take) {
+        let row = row?;
+        fields.clear();
+        for (&index, variable) in columns.iter().zip(&variables) {
+            let value = row
+                .values
+                .get(index)
+                .unwrap_or(&XPTValue::Missing(MissingValue::Standard));
+            fields.push(render(value, variable, options));
+        }
+        let borrowed: Vec<(&str, bool)> = fields
+            .iter()
+            .map(|(text, numeric)| (text.as_str(), *numeric))
+            .collect();
+        writer.write_record(&borrowed)?;
+    }
+
+    writer.out.flush()?;
+    Ok(writer.out)
+}
+
+/// Indices of the variables to write, in output order
+fn select_columns(dataset: &XPTDataset, names: Option<&[String]>) -> Result<Vec<usize>, XptError> {
+    let Some(names) = names else {
+        return Ok((0..dataset.variables.len()).collect());
+    };
+    names
+        .iter()
+        .map(|name| {
+            dataset
+                .variables
+                .iter()
+                .position(|variable| variable.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    XptError::new(
+                        ErrorCode::UnknownVariable,
+                        Section::Namestr,
+                        format!("No variable {} in {}", name, dataset.title),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Text of a cell and whether it is a number, which decides quoting under
+/// [`Quoting::NonNumeric`]
+fn render(value: &XPTValue, variable: &XPTVariable, options: &DelimitedOptions) -> (String, bool) {
+    match value {
+        XPTValue::Character(text) => (text.clone(), false),
+        XPTValue::Numeric(number) if options.formatted => (
+            format_numeric(*number, &variable.format).unwrap_or_else(|| number.to_string()),
+            true,
+        ),
+        XPTValue::Numeric(number) => (number.to_string(), true),
+        XPTValue::Missing(missing) => {
+            let text = match (&options.missing, missing) {
+                (MissingValues::Empty, MissingValue::Standard) => String::new(),
+                (MissingValues::Empty | MissingValues::Codes, _) => missing.code(),
+                (MissingValues::Text(text), _) => text.clone(),
+            };
+            (text, true)
+        }
+    }
+}
+
+struct FieldWriter<W> {
+    out: W,
+    delimiter: char,
+    quoting: Quoting,
+}
+
+impl<W: Write> FieldWriter<W> {
+    /// Writes one line from `(text, is_number)` fields
+    fn write_record(&mut self, fields: &[(&str, bool)]) -> io::Result<()> {
+        let mut line = String::new();
+        for (index, &(field, numeric)) in fields.iter().enumerate() {
+            if index > 0 {
+                line.push(self.delimiter);
+            }
+            let needs_quotes = field.contains([self.delimiter, '"', '\n', '\r']);
+            let quoted = match self.quoting {
+                Quoting::Minimal => needs_quotes,
+                Quoting::All => true,
+                Quoting::NonNumeric => needs_quotes || !numeric,
+                Quoting::Escape => false,
+            };
+            if quoted {
+                line.push('"');
+                line.push_str(&field.replace('"', "\"\""));
+                line.push('"');
+            } else if self.quoting == Quoting::Escape {
+                for c in field.chars() {
+                    match c {
+                        '\t' => line.push_str("\\t"),
+                        '\n' => line.push_str("\\n"),
+                        '\r' => line.push_str("\\r"),
+                        '\\' => line.push_str("\\\\"),
+                        c if c == self.delimiter => {
+                            line.push('\\');
+                            line.push(c);
+                        }
+                        c => line.push(c),
+                    }
+                }
+            } else {
+                line.push_str(field);
+            }
+        }
+        line.push('\n');
+        self.out.write_all(line.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xpt_parser::XPTParser;
+
+    const TRUNCATED_NUMERICS: &[u8] = include_bytes!("../tests/fixtures/truncated_numerics.xpt");
+
+    fn export(options: &DelimitedOptions) -> String {
+        let dataset = &XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap()[0];
+        let rows = dataset.rows.iter().cloned().map(Ok::<_, XptError>);
+        String::from_utf8(write_delimited(Vec::new(), dataset, rows, options).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn writes_csv_of_every_row_and_column() {
+        assert_eq!(
+            export(&DelimitedOptions::default()),
+            "N3,N4,N6,CHR,N8\n1,12345,123456789,ABCD,3.25\n-2.5,0.5,-0.125,EFGH,100\n.A,,7,IJKL,\n"
+        );
+    }
+
+    #[test]
+    fn applies_subsets_headers_missing_values_and_quoting() {
+        let options = DelimitedOptions {
+            delimiter: Delimiter::Semicolon,
+            quoting: Quoting::NonNumeric,
+            header: HeaderRow::Labels,
+            missing: MissingValues::Text("NA".to_string()),
+            columns: Some(vec!["chr".to_string(), "N4".to_string()]),
+            rows: Some(1..5),
+            ..DelimitedOptions::default()
+        };
+        assert_eq!(
+            export(&options),
+            "\"Character after numerics\";\"4-byte numeric\"\n\"EFGH\";0.5\n\"IJKL\";NA\n"
+        );
+
+        let unknown = DelimitedOptions {
+            columns: Some(vec!["AGE".to_string()]),
+            ..DelimitedOptions::default()
+        };
+        let dataset = &XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap()[0];
+        let error = write_delimited(
+            Vec::new(),
+            dataset,
+            Vec::<Result<XPTRow, XptError>>::new(),
+            &unknown,
+        )
+        .unwrap_err();
+        assert_eq!(error.code, ErrorCode::UnknownVariable);
+    }
+
+    #[test]
+    fn quotes_and_escapes_fields() {
+        let record = |delimiter: Delimiter, quoting: Quoting, fields: &[&str]| {
+            let mut writer = FieldWriter {
+                out: Vec::new(),
+                delimiter: delimiter.as_char(),
+                quoting,
+            };
+            let fields: Vec<(&str, bool)> = fields.iter().map(|&field| (field, false)).collect();
+            writer.write_record(&fields).unwrap();
+            String::from_utf8(writer.out).unwrap()
+        };
+        assert_eq!(
+            record(
+                Delimiter::Comma,
+                Quoting::Minimal,
+                &["plain", "a,b", "say \"hi\"", "two\nlines"]
+            ),
+            "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\"\n"
+        );
+        assert_eq!(
+            record(
+                Delimiter::Tab,
+                Quoting::Escape,
+                &["a\tb", "c\\d", "e\nf", "g,h"]
+            ),
+            "a\\tb\tc\\\\d\te\\nf\tg,h\n"
+        );
+    }
+}
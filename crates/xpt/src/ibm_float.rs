@@ -0,0 +1,323 @@
+//! Bit-level conversion between IBM System/360 hexadecimal floating point and IEEE 754 doubles
+//!
+//! An IBM double is laid out as:
+//! - Byte 0: sign bit (bit 7) + 7-bit excess-64 exponent of 16 (bits 0-6)
+//! - Bytes 1-7: 56-bit fraction, read as `0.f` in base 16
+//!
+//! Every IBM value lies between 16^-65 and 16^63, well inside the IEEE double range, so decoding
+//! never overflows or underflows. It can however carry up to 56 significant bits, which are
+//! rounded once to the 53 bits of an IEEE double using round-half-to-even. Encoding in the other
+//! direction is exact inside the IBM range and reports overflow and underflow outside of it.
+
+/// Largest finite IBM double: 0x7F followed by an all-ones fraction
+const IBM_MAX: [u8; 8] = [0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+/// Number of fraction bits in an IBM double
+const IBM_FRACTION_BITS: u32 = 56;
+/// Number of significant bits in an IEEE double, including the implicit leading bit
+const IEEE_SIGNIFICANT_BITS: u32 = 53;
+/// Exponent bias of an IEEE double
+const IEEE_EXPONENT_BIAS: i32 = 1023;
+
+/// Problem encountered while converting a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Diagnostic {
+    /// The IBM value had a zero leading hexadecimal digit. SAS never writes such values, so they
+    /// usually point at corrupted data; they are still converted exactly.
+    Unnormalized,
+    /// The value is too large for an IBM double and was clamped to the largest finite one
+    Overflow,
+    /// The value is too small for an IBM double and was flushed to zero
+    Underflow,
+    /// NaN has no IBM representation and was encoded as the standard missing value
+    NotANumber,
+}
+
+impl Diagnostic {
+    /// Short human-readable description, used in parser warnings
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Diagnostic::Unnormalized => "unnormalized IBM float",
+            Diagnostic::Overflow => "magnitude above the IBM float range",
+            Diagnostic::Underflow => "magnitude below the IBM float range",
+            Diagnostic::NotANumber => "NaN with no IBM float representation",
+        }
+    }
+}
+
+/// Result of a conversion, with the diagnostic raised while producing it, if any
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Converted<T> {
+    pub value: T,
+    pub diagnostic: Option<Diagnostic>,
+}
+
+impl<T> Converted<T> {
+    fn exact(value: T) -> Self {
+        Converted {
+            value,
+            diagnostic: None,
+        }
+    }
+}
+
+/// Converts an IBM double to the nearest IEEE double, rounding ties to even
+pub fn ibm_to_ieee(bytes: [u8; 8]) -> Converted<f64> {
+    let sign = u64::from(bytes[0] & 0x80) << 56;
+    let exponent16 = i32::from(bytes[0] & 0x7F) - 64;
+    let fraction = bytes[1..]
+        .iter()
+        .fold(0u64, |acc, &byte| (acc << 8) | u64::from(byte));
+
+    if fraction == 0 {
+        return Converted::exact(f64::from_bits(sign));
+    }
+
+    let diagnostic = if fraction >> (IBM_FRACTION_BITS - 4) == 0 {
+        Some(Diagnostic::Unnormalized)
+    } else {
+        None
+    };
+
+    // value = fraction * 2^exponent2, with the fraction widened or narrowed to exactly 53 bits
+    let mut exponent2 = 4 * exponent16 - IBM_FRACTION_BITS as i32;
+    let significant_bits = u64::BITS - fraction.leading_zeros();
+    let mut mantissa = if significant_bits > IEEE_SIGNIFICANT_BITS {
+        let shift = significant_bits - IEEE_SIGNIFICANT_BITS;
+        let dropped = fraction & ((1u64 << shift) - 1);
+        let half = 1u64 << (shift - 1);
+        let mut kept = fraction >> shift;
+        if dropped > half || (dropped == half && kept & 1 == 1) {
+            kept += 1;
+        }
+        exponent2 += shift as i32;
+        kept
+    } else {
+        let shift = IEEE_SIGNIFICANT_BITS - significant_bits;
+        exponent2 -= shift as i32;
+        fraction << shift
+    };
+
+    // Rounding up an all-ones mantissa carries into a 54th bit
+    if mantissa >> IEEE_SIGNIFICANT_BITS != 0 {
+        mantissa >>= 1;
+        exponent2 += 1;
+    }
+
+    // The IBM exponent range always maps to a normal IEEE exponent
+    let biased = (exponent2 + (IEEE_SIGNIFICANT_BITS as i32 - 1) + IEEE_EXPONENT_BIAS) as u64;
+    let bits = sign | (biased << 52) | (mantissa & ((1u64 << 52) - 1));
+
+    Converted {
+        value: f64::from_bits(bits),
+        diagnostic,
+    }
+}
+
+/// Converts an IEEE double to an IBM double
+///
+/// Values inside the IBM range convert exactly, since an IEEE mantissa always fits in the IBM
+/// fraction. Zero is written as all zero bytes regardless of its sign.
+pub fn ieee_to_ibm(value: f64) -> Converted<[u8; 8]> {
+    if value.is_nan() {
+        let mut missing = [0u8; 8];
+        missing[0] = b'.';
+        return Converted {
+            value: missing,
+            diagnostic: Some(Diagnostic::NotANumber),
+        };
+    }
+
+    let sign = if value.is_sign_negative() { 0x80 } else { 0x00 };
+    if value == 0.0 {
+        return Converted::exact([0u8; 8]);
+    }
+    if value.is_infinite() {
+        return clamp_to_max(sign);
+    }
+
+    let bits = value.to_bits();
+    let biased = ((bits >> 52) & 0x7FF) as i32;
+    let (mantissa, exponent2) = if biased == 0 {
+        // Subnormal: no implicit leading bit
+        (bits & ((1u64 << 52) - 1), 1 - IEEE_EXPONENT_BIAS - 52)
+    } else {
+        (
+            (bits & ((1u64 << 52) - 1)) | (1u64 << 52),
+            biased - IEEE_EXPONENT_BIAS - 52,
+        )
+    };
+
+    // Move the leading bit to the top of the 56-bit fraction: value = (m / 2^56) * 2^t
+    let shift = mantissa.leading_zeros() - (u64::BITS - IBM_FRACTION_BITS);
+    let normalized = mantissa << shift;
+    let t = exponent2 - shift as i32 + IBM_FRACTION_BITS as i32;
+
+    // Round the binary exponent up to a multiple of 4; the fraction gives up to 3 low bits,
+    // which are always zero because an IEEE mantissa has at most 53 significant bits
+    let exponent16 = t.div_euclid(4) + i32::from(t.rem_euclid(4) != 0);
+    let fraction = normalized >> (4 * exponent16 - t);
+
+    if exponent16 > 63 {
+        return clamp_to_max(sign);
+    }
+    if exponent16 < -64 {
+        return Converted {
+            value: [0u8; 8],
+            diagnostic: Some(Diagnostic::Underflow),
+        };
+    }
+
+    let mut bytes = [0u8; 8];
+    bytes[0] = sign | (exponent16 + 64) as u8;
+    bytes[1..].copy_from_slice(&fraction.to_be_bytes()[1..]);
+    Converted::exact(bytes)
+}
+
+fn clamp_to_max(sign: u8) -> Converted<[u8; 8]> {
+    let mut bytes = IBM_MAX;
+    bytes[0] |= sign;
+    Converted {
+        value: bytes,
+        diagnostic: Some(Diagnostic::Overflow),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Reference IBM bit patterns and the IEEE doubles they must decode to
+    const REFERENCE: &[([u8; 8], f64)] = &[
+        ([0x41, 0x10, 0, 0, 0, 0, 0, 0], 1.0),
+        ([0xC1, 0x10, 0, 0, 0, 0, 0, 0], -1.0),
+        ([0x40, 0x80, 0, 0, 0, 0, 0, 0], 0.5),
+        ([0x42, 0x64, 0, 0, 0, 0, 0, 0], 100.0),
+        ([0xC2, 0x76, 0xA0, 0, 0, 0, 0, 0], -118.625),
+        ([0x40, 0x19, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A], 0.1),
+        ([0x45, 0x56, 0x76, 0x40, 0, 0, 0, 0], 354_148.0),
+        (
+            [0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            7.237_005_577_332_262e75,
+        ),
+        ([0x00, 0x10, 0, 0, 0, 0, 0, 0], 5.397_605_346_934_028e-79),
+        // 56 significant bits, all ones: rounds up into the next power of two
+        ([0x41, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], 16.0),
+        // 8 + 2^-50 is halfway between 8 and the next double up: ties go to the even mantissa
+        ([0x41, 0x80, 0, 0, 0, 0, 0, 0x04], 8.0),
+        // 8 + 3 * 2^-50 is halfway between an odd and an even mantissa: rounds up to the even one
+        ([0x41, 0x80, 0, 0, 0, 0, 0, 0x0C], 8.0 + 16.0 * f64::EPSILON),
+    ];
+
+    #[test]
+    fn decodes_reference_bit_patterns() {
+        for &(bytes, expected) in REFERENCE {
+            let converted = ibm_to_ieee(bytes);
+            assert_eq!(
+                converted.value.to_bits(),
+                expected.to_bits(),
+                "{:02X?}",
+                bytes
+            );
+            assert_eq!(converted.diagnostic, None);
+        }
+    }
+
+    #[test]
+    fn encodes_reference_bit_patterns() {
+        for &(bytes, value) in REFERENCE.iter().filter(|(bytes, _)| bytes[7] & 0x07 == 0) {
+            assert_eq!(ieee_to_ibm(value).value, bytes, "{}", value);
+        }
+    }
+
+    #[test]
+    fn flags_unnormalized_fractions() {
+        let converted = ibm_to_ieee([0x41, 0x01, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(converted.value, 0.0625);
+        assert_eq!(converted.diagnostic, Some(Diagnostic::Unnormalized));
+    }
+
+    #[test]
+    fn reports_values_outside_the_ibm_range() {
+        let overflow = ieee_to_ibm(-1e300);
+        assert_eq!(
+            overflow.value,
+            [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+        assert_eq!(overflow.diagnostic, Some(Diagnostic::Overflow));
+
+        let infinity = ieee_to_ibm(f64::INFINITY);
+        assert_eq!(infinity.value, IBM_MAX);
+        assert_eq!(infinity.diagnostic, Some(Diagnostic::Overflow));
+
+        let underflow = ieee_to_ibm(1e-300);
+        assert_eq!(underflow.value, [0u8; 8]);
+        assert_eq!(underflow.diagnostic, Some(Diagnostic::Underflow));
+
+        let nan = ieee_to_ibm(f64::NAN);
+        assert_eq!(nan.value, [b'.', 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(nan.diagnostic, Some(Diagnostic::NotANumber));
+    }
+
+    /// IEEE doubles whose magnitude lies inside the IBM range
+    fn ibm_range_double() -> impl Strategy<Value = f64> {
+        (any::<bool>(), 1u64..(1u64 << 52), -255i32..=250).prop_map(|(negative, bits, exponent)| {
+            let magnitude = f64::from_bits(((exponent + 1023) as u64) << 52 | bits);
+            if negative {
+                -magnitude
+            } else {
+                magnitude
+            }
+        })
+    }
+
+    /// Normalized IBM doubles with any sign, exponent and fraction
+    fn normalized_ibm() -> impl Strategy<Value = [u8; 8]> {
+        (any::<u8>(), 1u64 << 52..1u64 << 56).prop_map(|(head, fraction)| {
+            let mut bytes = [0u8; 8];
+            bytes[0] = head;
+            bytes[1..].copy_from_slice(&fraction.to_be_bytes()[1..]);
+            bytes
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn ieee_round_trips_through_ibm(value in ibm_range_double()) {
+            let encoded = ieee_to_ibm(value);
+            prop_assert_eq!(encoded.diagnostic, None);
+            let decoded = ibm_to_ieee(encoded.value);
+            prop_assert_eq!(decoded.value.to_bits(), value.to_bits());
+            prop_assert_eq!(decoded.diagnostic, None);
+        }
+
+        #[test]
+        fn decoding_rounds_to_nearest(bytes in normalized_ibm()) {
+            let decoded = ibm_to_ieee(bytes).value;
+
+            // Re-encoding is exact, so comparing fractions measures the rounding error in
+            // units of the last IBM fraction bit
+            let reencoded = ieee_to_ibm(decoded).value;
+            let exponent_shift = 4 * (i32::from(reencoded[0] & 0x7F) - i32::from(bytes[0] & 0x7F));
+            let fraction = |b: &[u8; 8]| b[1..].iter().fold(0i128, |acc, &x| (acc << 8) | i128::from(x));
+            let original = fraction(&bytes);
+            let rounded = fraction(&reencoded) << exponent_shift;
+            let error = (rounded - original).abs();
+
+            // At most half a unit in the last place of a 53-bit mantissa
+            let significant_bits = 128 - original.leading_zeros();
+            let ulp = 1i128 << significant_bits.saturating_sub(IEEE_SIGNIFICANT_BITS);
+            prop_assert!(2 * error <= ulp, "error {} exceeds half ulp {}", error, ulp);
+            prop_assert_eq!(reencoded[0] & 0x80, bytes[0] & 0x80);
+        }
+
+        #[test]
+        fn decoding_preserves_order(lhs in normalized_ibm(), rhs in normalized_ibm()) {
+            let positive = |mut b: [u8; 8]| { b[0] &= 0x7F; b };
+            let (lhs, rhs) = (positive(lhs), positive(rhs));
+            if lhs < rhs {
+                prop_assert!(ibm_to_ieee(lhs).value <= ibm_to_ieee(rhs).value);
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Reader and writer for SAS XPORT transport files, Version 5 and Version 8/9
+//!
+//! [`XPTParser`] decodes a whole file held in memory, while [`XptReader`] reads the metadata of
+//! each member from any `Read + Seek` source as it is reached and then streams rows one at a
+//! time:
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use xpt::XptReader;
+//!
+//! let mut reader = XptReader::new(File::open("dm.xpt")?)?;
+//! let names: Vec<String> = reader.datasets()?.map(|dataset| dataset.title.clone()).collect();
+//! println!("members: {}", names.join(", "));
+//! for row in reader.rows(0)?.expect("the file has a member") {
+//!     println!("{:?}", row?.values);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! [`XPTWriter`] writes datasets back out as Version 5 or Version 8 transport files, and
+//! [`write_delimited`] exports them as CSV or TSV. [`read_dataset_json`] and
+//! [`write_dataset_json`] read and write CDISC Dataset-JSON v1.1 files, and [`read_sas7bdat`]
+//! reads native SAS datasets into the same model, or [`read_sas7bdat_lazy`] their metadata
+//! alone. With the `arrow` feature, `to_record_batch`
+//! converts a dataset into an Apache Arrow record batch and `write_arrow_ipc` writes Arrow IPC
+//! (Feather) files; with the `parquet` feature, `write_parquet` writes Apache Parquet files;
+//! with the `xlsx` feature, `XlsxWriter` builds Excel workbooks with a sheet per dataset.
+//!
+//! Numeric values are decoded at full double precision; [`format_numeric`] renders them with
+//! the SAS display format recorded for their variable.
+
+#[cfg(feature = "arrow")]
+mod arrow_export;
+mod dataset_json;
+mod delimited;
+mod ibm_float;
+#[cfg(feature = "parquet")]
+mod parquet_export;
+mod sas7bdat;
+mod sas_format;
+#[cfg(test)]
+mod test_fixtures;
+mod text_encoding;
+#[cfg(feature = "xlsx")]
+mod xlsx_export;
+mod xpt_error;
+mod xpt_parser;
+mod xpt_reader;
+mod xpt_writer;
+
+#[cfg(feature = "arrow")]
+pub use arrow_export::{arrow_schema, to_record_batch, write_arrow_ipc, ArrowOptions};
+pub use dataset_json::{read_dataset_json, write_dataset_json, DatasetJsonOptions};
+pub use delimited::{
+    write_delimited, DelimitedOptions, Delimiter, HeaderRow, MissingValues, Quoting,
+};
+#[cfg(feature = "parquet")]
+pub use parquet_export::{write_parquet, ParquetOptions};
+pub use sas7bdat::{read_sas7bdat, read_sas7bdat_lazy, LazySas7bdat};
+pub use sas_format::format_numeric;
+pub use text_encoding::TextEncoding;
+#[cfg(feature = "xlsx")]
+pub use xlsx_export::{XlsxOptions, XlsxWriter};
+pub use xpt_error::{ErrorCode, Section, XptError, XptResult};
+pub use xpt_parser::{
+    Justification, LazyDataset, LazyMembers, MissingValue, ParseOptions, SasDateTime,
+    VariableFormat, VariableType, XPTDataset, XPTLibraryHeader, XPTParser, XPTRow, XPTValue,
+    XPTVariable, XportVersion,
+};
+pub use xpt_reader::{Rows, XptReader};
+pub use xpt_writer::{MemberWriter, XPTWriter};
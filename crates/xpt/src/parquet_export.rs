@@ -0,0 +1,223 @@
+//! Export of datasets as Apache Parquet files, behind the `parquet` feature
+//!
+//! Numeric variables become `DOUBLE` columns and character variables `UTF8` strings. With
+//! [`ParquetOptions::logical_dates`], variables with a SAS date format become `DATE` columns and
+//! those with a datetime format `TIMESTAMP(MICROS)` columns, and a date or datetime beyond the
+//! years SAS supports is an error. Missing values of every kind become nulls, losing which
+//! special missing value a cell held. Columns are built as Arrow record batches, as
+//! [`to_record_batch`](crate::to_record_batch) does.
+//!
+//! The dataset name and label, and the label, format and informat of every variable, are written
+//! to the key-value metadata of the file under `sas.dataset.name`, `sas.dataset.label` and
+//! `sas.variable.<NAME>.label` and so on, with empty values left out. They are also kept as
+//! metadata of the embedded Arrow schema, for readers that load the file through Arrow.
+
+use crate::arrow_export::{variable_metadata, BatchBuilder};
+use crate::xpt_error::{ErrorCode, Section, XptError};
+use crate::xpt_parser::{XPTDataset, XPTRow};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use serde::Deserialize;
+use std::io::{self, Write};
+
+/// Options for [`write_parquet`]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ParquetOptions {
+    /// Write variables with a SAS date or datetime format as Parquet dates and timestamps
+    /// instead of SAS day and second counts
+    pub logical_dates: bool,
+    /// Largest number of rows in a row group, which is also the number of rows held in memory
+    pub row_group_size: usize,
+}
+
+impl Default for ParquetOptions {
+    fn default() -> Self {
+        ParquetOptions {
+            logical_dates: true,
+            row_group_size: 65_536,
+        }
+    }
+}
+
+/// Writes the variables of `dataset` and the rows yielded by `rows` as a Parquet file
+///
+/// Only the metadata of `dataset` is used; rows are buffered one row group at a time. Errors
+/// yielded by `rows` stop the export and are passed through, and `out` is returned once the file
+/// footer has been written.
+pub fn write_parquet<W, E>(
+    out: W,
+    dataset: &XPTDataset,
+    rows: impl IntoIterator<Item = Result<XPTRow, E>>,
+    options: &ParquetOptions,
+) -> Result<W, E>
+where
+    W: Write + Send,
+    E: From<io::Error> + From<XptError>,
+{
+    let mut batches = BatchBuilder::new(dataset, options.logical_dates);
+    let row_group_size = options.row_group_size.max(1);
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_size(row_group_size)
+        .set_key_value_metadata(Some(file_metadata(dataset)))
+        .build();
+    let mut writer = ArrowWriter::try_new(out, batches.schema.clone(), Some(properties))
+        .map_err(parquet_error)?;
+
+    for row in rows {
+        batches.append(&row?)?;
+        if batches.len() == row_group_size {
+            writer.write(&batches.finish()?).map_err(parquet_error)?;
+        }
+    }
+    if batches.len() > 0 {
+        writer.write(&batches.finish()?).map_err(parquet_error)?;
+    }
+    Ok(writer.into_inner().map_err(parquet_error)?)
+}
+
+fn parquet_error(error: parquet::errors::ParquetError) -> XptError {
+    XptError::new(
+        ErrorCode::Io,
+        Section::File,
+        format!("Unable to write the Parquet output: {}", error),
+    )
+}
+
+/// Key-value metadata of the file: the dataset and the attributes of every variable
+fn file_metadata(dataset: &XPTDataset) -> Vec<KeyValue> {
+    let mut metadata = vec![KeyValue::new(
+        "sas.dataset.name".to_string(),
+        dataset.title.clone(),
+    )];
+    if !dataset.label.is_empty() {
+        metadata.push(KeyValue::new(
+            "sas.dataset.label".to_string(),
+            dataset.label.clone(),
+        ));
+    }
+    for variable in &dataset.variables {
+        let mut attributes: Vec<(String, String)> =
+            variable_metadata(variable).into_iter().collect();
+        attributes.sort();
+        for (key, value) in attributes {
+            metadata.push(KeyValue::new(
+                format!("sas.variable.{}.{}", variable.name, key),
+                value,
+            ));
+        }
+    }
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::visits;
+    use crate::xpt_error::XptError;
+    use crate::xpt_parser::XPTParser;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Date32Type, Float64Type, TimestampMicrosecondType};
+    use arrow_array::{Array, RecordBatch};
+    use arrow_schema::DataType;
+    use bytes::Bytes;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::collections::HashMap;
+
+    const TRUNCATED_NUMERICS: &[u8] = include_bytes!("../tests/fixtures/truncated_numerics.xpt");
+
+    fn export(dataset: &XPTDataset, options: &ParquetOptions) -> Bytes {
+        let rows = dataset.rows.iter().cloned().map(Ok::<_, XptError>);
+        Bytes::from(write_parquet(Vec::new(), dataset, rows, options).unwrap())
+    }
+
+    #[test]
+    fn writes_dates_timestamps_and_nulls() {
+        let file = export(&visits(), &ParquetOptions::default());
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+
+        let subjects = batch.column(0).as_string::<i32>();
+        assert_eq!(subjects.value(1), "01-002");
+        let dates = batch.column(1).as_primitive::<Date32Type>();
+        assert_eq!(dates.value(0), 18_993);
+        assert!(dates.is_null(1));
+        let times = batch.column(2).as_primitive::<TimestampMicrosecondType>();
+        assert_eq!(times.value(0), 1_641_040_496_000_000);
+        assert!(times.is_null(1));
+        let visits = batch.column(3).as_primitive::<Float64Type>();
+        assert_eq!(visits.value(1), 2.5);
+    }
+
+    #[test]
+    fn keeps_sas_numbers_without_logical_dates() {
+        let options = ParquetOptions {
+            logical_dates: false,
+            row_group_size: 1,
+        };
+        let file = export(&visits(), &options);
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+        assert_eq!(builder.metadata().num_row_groups(), 2);
+        assert_eq!(builder.schema().field(1).data_type(), &DataType::Float64);
+        let batch = builder.build().unwrap().next().unwrap().unwrap();
+        assert_eq!(
+            batch.column(1).as_primitive::<Float64Type>().value(0),
+            22_646.0
+        );
+    }
+
+    #[test]
+    fn stores_labels_and_formats_as_metadata() {
+        let file = export(&visits(), &ParquetOptions::default());
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+        let metadata: HashMap<String, Option<String>> = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.key.clone(), entry.value.clone()))
+            .collect();
+        let value = |key: &str| metadata.get(key).cloned().flatten();
+        assert_eq!(value("sas.dataset.name").as_deref(), Some("SV"));
+        assert_eq!(
+            value("sas.dataset.label").as_deref(),
+            Some("Subject Visits")
+        );
+        assert_eq!(
+            value("sas.variable.SVSTDT.label").as_deref(),
+            Some("Start date")
+        );
+        assert_eq!(
+            value("sas.variable.SVSTDT.format").as_deref(),
+            Some("DATE9.")
+        );
+        assert_eq!(value("sas.variable.USUBJID.format"), None);
+
+        let field = builder.schema().field(1).clone();
+        assert_eq!(
+            field.metadata().get("format").map(String::as_str),
+            Some("DATE9.")
+        );
+    }
+
+    #[test]
+    fn exports_a_parsed_file() {
+        let dataset = &XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap()[0];
+        let file = export(dataset, &ParquetOptions::default());
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+        assert_eq!(
+            builder.metadata().file_metadata().num_rows(),
+            dataset.rows.len() as i64
+        );
+        assert_eq!(builder.schema().fields().len(), dataset.variables.len());
+    }
+}
@@ -0,0 +1,1384 @@
+//! Reader for SAS7BDAT datasets, the native file format of SAS
+//!
+//! A SAS7BDAT file is a header followed by pages of a fixed size. Metadata pages hold
+//! subheaders describing the dataset: the row size, the column names, attributes, formats and
+//! labels, and the blocks of text these point into. Rows are stored on data pages, after the
+//! subheaders of a mixed page or, in a compressed file, one to a subheader. Numbers are written
+//! in the byte order of the machine that created the file, and offsets in its word size, so
+//! little- and big-endian files with 32- or 64-bit layouts are all read here, as are rows
+//! compressed with run-length encoding (`SASYZCRL`) or Ross Data Compression (`SASYZCR2`).
+//!
+//! [`read_sas7bdat`] decodes every row up front, while [`read_sas7bdat_lazy`] reads only the
+//! metadata and where each row is stored, so rows can be decoded one at a time from a file too
+//! large to hold decoded in memory.
+//!
+//! The format is undocumented; this reader follows the description published by Matthew
+//! Shotwell and the layouts read by ReadStat and pandas. Informats are not read, and rows SAS
+//! marked as deleted are read as live rows; the dataset warnings say when a page holds any.
+
+use crate::sas_format::sas_datetime;
+use crate::text_encoding::TextEncoding;
+use crate::xpt_error::{ErrorCode, Section, XptError, XptResult};
+use crate::xpt_parser::{
+    text_trimmed, Justification, MissingValue, ParseOptions, SasDateTime, VariableFormat,
+    VariableType, XPTDataset, XPTLibraryHeader, XPTRow, XPTValue, XPTVariable,
+    ENCODING_SAMPLE_ROWS,
+};
+use std::ops::Range;
+
+/// First bytes of every SAS7BDAT file
+const MAGIC: [u8; 32] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC2, 0xEA, 0x81, 0x60,
+    0xB3, 0x14, 0x11, 0xCF, 0xBD, 0x92, 0x08, 0x00, 0x09, 0xC7, 0x31, 0x8C, 0x18, 0x1F, 0x10, 0x11,
+];
+/// Bytes of the header holding every field read from it
+const MIN_HEADER_LENGTH: usize = 288;
+
+/// Page types, after masking off the flag bits of the mixed and metadata variants
+const PAGE_META: u16 = 0x0000;
+const PAGE_DATA: u16 = 0x0100;
+const PAGE_MIX: u16 = 0x0200;
+const PAGE_AMD: u16 = 0x0400;
+const PAGE_TYPE_MASK: u16 = 0x0F00;
+/// Pages of this type hold nothing the reader needs
+const PAGE_COMP: u16 = 0x9000;
+/// Flag of a data or mixed page holding rows that SAS marked as deleted
+const PAGE_HAS_DELETED: u16 = 0x0080;
+
+/// Subheader signatures, as their first four bytes read in the byte order of the file
+const ROW_SIZE: u32 = 0xF7F7_F7F7;
+const COLUMN_SIZE: u32 = 0xF6F6_F6F6;
+const COLUMN_TEXT: u32 = 0xFFFF_FFFD;
+const COLUMN_NAME: u32 = 0xFFFF_FFFF;
+const COLUMN_ATTRIBUTES: u32 = 0xFFFF_FFFC;
+const FORMAT_AND_LABEL: u32 = 0xFFFF_FBFE;
+
+/// Compression flag of a subheader pointer that points past the data actually stored
+const TRUNCATED_SUBHEADER: u8 = 1;
+/// Compression flag of a subheader holding a compressed row
+const COMPRESSED_ROW: u8 = 4;
+/// Type flag of a subheader holding a row
+const ROW_SUBHEADER: u8 = 1;
+
+/// Markers written in the first block of column text by SAS for compressed datasets
+const RLE_MARKER: &[u8] = b"SASYZCRL";
+const RDC_MARKER: &[u8] = b"SASYZCR2";
+
+/// Reads a SAS7BDAT dataset into the same model as a transport file member
+///
+/// Names, labels and character values are decoded with the encoding given in `options`, then
+/// the one recorded in the file header, and otherwise one detected from the text as for
+/// transport files. With `options.recover`, rows that cannot be decompressed and pages missing
+/// from the end of the file are skipped, and reported in the dataset warnings, instead of
+/// failing the read.
+pub fn read_sas7bdat(data: &[u8], options: ParseOptions) -> XptResult<XPTDataset> {
+    let (mut lazy, declared_rows, header_encoding) = open(data, options)?;
+    let mut rows = Vec::with_capacity(lazy.row_count);
+    for index in 0..lazy.row_count {
+        match lazy.read_row(data, index) {
+            Ok(row) => rows.push(row),
+            Err(reason) if options.recover => {
+                let warning = format!("Row {} skipped: {}", index + 1, reason);
+                lazy.dataset.warnings.push(warning);
+            }
+            Err(reason) => {
+                return Err(XptError::new(
+                    ErrorCode::InvalidSas7bdat,
+                    Section::Observations,
+                    format!("Row {} cannot be read: {}", index + 1, reason),
+                ))
+            }
+        }
+    }
+    let mut dataset = lazy.dataset;
+    if rows.len() < declared_rows {
+        dataset.warnings.push(format!(
+            "The file declares {} rows but holds {}",
+            declared_rows,
+            rows.len()
+        ));
+    }
+    dataset.rows = rows;
+    // Text was read as Latin-1 so that it can be re-decoded once the encoding is known
+    let encoding = options
+        .encoding
+        .or(header_encoding)
+        .unwrap_or_else(|| TextEncoding::detect(dataset.texts()));
+    dataset.apply_encoding(encoding);
+    Ok(dataset)
+}
+
+/// Reads the metadata of a SAS7BDAT dataset and where its rows are stored, without decoding
+/// any row
+///
+/// Rows are then decoded one at a time with [`LazySas7bdat::row`], so memory use does not grow
+/// with the file. Unless an encoding is given or recorded in the file header, it is detected
+/// from the metadata and the first rows. `options.recover` also lets pages missing from the end
+/// of the file be skipped here; rows that cannot be decompressed are only found when read.
+pub fn read_sas7bdat_lazy(data: &[u8], options: ParseOptions) -> XptResult<LazySas7bdat> {
+    let (mut lazy, declared_rows, header_encoding) = open(data, options)?;
+    if lazy.row_count < declared_rows {
+        lazy.dataset.warnings.push(format!(
+            "The file declares {} rows but holds {}",
+            declared_rows, lazy.row_count
+        ));
+    }
+    let encoding = options.encoding.or(header_encoding).unwrap_or_else(|| {
+        let mut sample = lazy.dataset.clone();
+        sample.rows = (0..lazy.row_count.min(ENCODING_SAMPLE_ROWS))
+            .filter_map(|index| lazy.read_row(data, index).ok())
+            .collect();
+        TextEncoding::detect(sample.texts())
+    });
+    lazy.dataset.apply_encoding(encoding);
+    Ok(lazy)
+}
+
+/// A SAS7BDAT dataset whose rows are decoded from the file bytes on demand, see
+/// [`read_sas7bdat_lazy`]
+#[derive(Debug, Clone)]
+pub struct LazySas7bdat {
+    /// Dataset metadata; `rows` stays empty
+    pub dataset: XPTDataset,
+    layout: Layout,
+    compression: Option<&'static [u8]>,
+    row_length: usize,
+    row_count: usize,
+    /// Chunks holding at least one row, each with the index of its first row
+    chunks: Vec<(usize, Chunk)>,
+}
+
+impl LazySas7bdat {
+    /// Number of rows stored in the file
+    pub fn row_count(&self) -> usize {
+        self.row_count
+    }
+
+    /// Decodes row `index` from `data`, which must be the bytes the dataset was read from
+    ///
+    /// Returns `None` past the last row and for a row that cannot be decompressed.
+    pub fn row(&self, data: &[u8], index: usize) -> Option<XPTRow> {
+        if index >= self.row_count {
+            return None;
+        }
+        let mut row = self.read_row(data, index).ok()?;
+        row.apply_encoding(self.dataset.encoding);
+        Some(row)
+    }
+
+    /// Decodes one row, leaving its text read as Latin-1
+    fn read_row(&self, data: &[u8], index: usize) -> Result<XPTRow, String> {
+        let position = self.chunks.partition_point(|(first, _)| *first <= index);
+        let (first, chunk) = &self.chunks[position - 1];
+        let expanded;
+        let row = match *chunk {
+            Chunk::Row(ref stored) if stored.len() >= self.row_length => {
+                &data[stored.start..stored.start + self.row_length]
+            }
+            Chunk::Row(ref stored) => {
+                let stored = &data[stored.clone()];
+                expanded = match self.compression {
+                    Some(RLE_MARKER) => rle_decompress(stored, self.row_length),
+                    Some(_) => rdc_decompress(stored, self.row_length),
+                    None => Err(format!(
+                        "{} bytes are stored for a {}-byte row in an uncompressed file",
+                        stored.len(),
+                        self.row_length
+                    )),
+                }?;
+                &expanded
+            }
+            Chunk::Rows { start, .. } => {
+                let start = start + (index - first) * self.row_length;
+                &data[start..start + self.row_length]
+            }
+        };
+        Ok(decode_row(row, &self.dataset.variables, self.layout))
+    }
+}
+
+/// Reads the header and every page, leaving text read as Latin-1
+///
+/// Returns the dataset without decoding any row, the row count the file declares and the
+/// encoding recorded in the header.
+fn open(
+    data: &[u8],
+    options: ParseOptions,
+) -> XptResult<(LazySas7bdat, usize, Option<TextEncoding>)> {
+    let header = Header::read(data)?;
+    let mut warnings = Vec::new();
+    let mut metadata = Metadata::new(header.layout);
+
+    for number in 0..header.page_count {
+        let start = number
+            .checked_mul(header.page_length)
+            .and_then(|offset| offset.checked_add(header.header_length))
+            .filter(|&start| start < data.len());
+        let Some(start) = start else {
+            let message = format!(
+                "The header declares {} pages but the file holds {}",
+                header.page_count, number
+            );
+            if !options.recover {
+                return Err(invalid(message));
+            }
+            warnings.push(message);
+            break;
+        };
+        let end = (start + header.page_length).min(data.len());
+        metadata
+            .read_page(&data[start..end], start)
+            .map_err(|error| invalid(format!("Page {}: {}", number + 1, error.message)))?;
+    }
+    if metadata.deleted_pages > 0 {
+        warnings.push(format!(
+            "Rows marked as deleted are read as live rows (pages flagged: {})",
+            metadata.deleted_pages
+        ));
+    }
+
+    let variables = metadata.columns()?;
+    let mut stored = 0;
+    let mut chunks = Vec::new();
+    for chunk in &metadata.chunks {
+        let count = metadata.stored_rows(chunk);
+        if count > 0 {
+            chunks.push((stored, chunk.clone()));
+            stored += count;
+        }
+    }
+
+    let dataset = XPTDataset {
+        title: header.name,
+        label: metadata
+            .label
+            .map(|label| metadata.text(label))
+            .unwrap_or_default(),
+        dataset_type: header.file_type,
+        sas_version: header.release.clone(),
+        os_name: header.os_name.clone(),
+        created_date: header.created,
+        modified_date: header.modified,
+        library: XPTLibraryHeader {
+            sas_symbol: "SAS".to_string(),
+            sas_version: header.release,
+            os_name: header.os_name,
+            created: header.created,
+            modified: header.modified,
+        },
+        variables,
+        rows: Vec::new(),
+        encoding: TextEncoding::Latin1,
+        warnings,
+    };
+    let lazy = LazySas7bdat {
+        dataset,
+        layout: header.layout,
+        compression: metadata.compression(),
+        row_length: metadata.row_length,
+        // The declared count is only trusted as far as the pages can hold it
+        row_count: metadata.row_count.min(stored),
+        chunks,
+    };
+    Ok((lazy, metadata.row_count, header.encoding))
+}
+
+/// Byte order and word size of a file
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    big_endian: bool,
+    /// Offsets and counts take 8 bytes rather than 4
+    wide: bool,
+}
+
+impl Layout {
+    fn word(&self) -> usize {
+        if self.wide {
+            8
+        } else {
+            4
+        }
+    }
+
+    /// Offset of the page type within a page
+    fn page_header(&self) -> usize {
+        if self.wide {
+            32
+        } else {
+            16
+        }
+    }
+
+    fn pointer_length(&self) -> usize {
+        3 * self.word()
+    }
+
+    fn u16(&self, data: &[u8], at: usize) -> XptResult<u16> {
+        let bytes = field(data, at)?;
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, data: &[u8], at: usize) -> XptResult<u32> {
+        let bytes = field(data, at)?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// Reads an offset or count of the word size of the file
+    fn number(&self, data: &[u8], at: usize) -> XptResult<usize> {
+        let value = if self.wide {
+            let bytes = field(data, at)?;
+            if self.big_endian {
+                u64::from_be_bytes(bytes)
+            } else {
+                u64::from_le_bytes(bytes)
+            }
+        } else {
+            u64::from(self.u32(data, at)?)
+        };
+        usize::try_from(value).map_err(|_| invalid(format!("Offset {} is too large", value)))
+    }
+
+    fn f64(&self, data: &[u8], at: usize) -> XptResult<f64> {
+        let bytes = field(data, at)?;
+        Ok(if self.big_endian {
+            f64::from_be_bytes(bytes)
+        } else {
+            f64::from_le_bytes(bytes)
+        })
+    }
+
+    /// Reads the signature of a subheader
+    ///
+    /// Signatures take a word; in 64-bit files the four bytes that identify the subheader are
+    /// padded with zeros or, for the signatures that are negative numbers, with `0xFF` bytes,
+    /// which come first in big-endian files.
+    fn signature(&self, subheader: &[u8]) -> XptResult<u32> {
+        let first = self.u32(subheader, 0)?;
+        if self.wide && (first == 0 || first == u32::MAX) {
+            return self.u32(subheader, 4);
+        }
+        Ok(first)
+    }
+}
+
+/// Fields of the file header
+struct Header {
+    layout: Layout,
+    header_length: usize,
+    page_length: usize,
+    page_count: usize,
+    name: String,
+    file_type: String,
+    created: Option<SasDateTime>,
+    modified: Option<SasDateTime>,
+    release: String,
+    os_name: String,
+    encoding: Option<TextEncoding>,
+}
+
+impl Header {
+    fn read(data: &[u8]) -> XptResult<Self> {
+        if data.len() < MIN_HEADER_LENGTH || data[..MAGIC.len()] != MAGIC {
+            return Err(invalid("The file is not a SAS7BDAT dataset"));
+        }
+        let layout = Layout {
+            big_endian: data[37] == 0x00,
+            wide: data[32] == b'3',
+        };
+        // Both alignments are either zero or four bytes; the second only follows the page count
+        let align = if data[35] == b'3' { 4 } else { 0 };
+        let total_align = align + if layout.wide { 4 } else { 0 };
+
+        let header_length = layout.u32(data, 196 + align)? as usize;
+        let page_length = layout.u32(data, 200 + align)? as usize;
+        let page_count = layout.number(data, 204 + align)?;
+        if header_length < MIN_HEADER_LENGTH || page_length <= layout.page_header() + 8 {
+            return Err(invalid(format!(
+                "The header declares {}-byte headers and {}-byte pages",
+                header_length, page_length
+            )));
+        }
+
+        let text = |at: usize, length: usize| {
+            data.get(at..at + length)
+                .map(text_trimmed)
+                .unwrap_or_default()
+        };
+        let os_name = match text(272 + total_align, 16) {
+            name if name.is_empty() => text(256 + total_align, 16),
+            name => name,
+        };
+        Ok(Header {
+            layout,
+            header_length,
+            page_length,
+            page_count,
+            name: text(92, 64),
+            file_type: text(156, 8),
+            created: sas_datetime(layout.f64(data, 164 + align)?),
+            modified: sas_datetime(layout.f64(data, 172 + align)?),
+            release: text(216 + total_align, 8),
+            os_name,
+            encoding: match data[70] {
+                20 => Some(TextEncoding::Utf8),
+                29 => Some(TextEncoding::Latin1),
+                62 => Some(TextEncoding::Windows1252),
+                _ => None,
+            },
+        })
+    }
+}
+
+/// Location of a piece of text within the column text subheaders
+#[derive(Debug, Clone, Copy)]
+struct TextRef {
+    /// Which column text subheader, in the order they appear
+    block: usize,
+    offset: usize,
+    length: usize,
+}
+
+impl TextRef {
+    fn read(layout: Layout, data: &[u8], at: usize) -> XptResult<Self> {
+        Ok(TextRef {
+            block: layout.u16(data, at)? as usize,
+            offset: layout.u16(data, at + 2)? as usize,
+            length: layout.u16(data, at + 4)? as usize,
+        })
+    }
+}
+
+/// Where a column is stored within a row
+struct Attributes {
+    offset: usize,
+    length: usize,
+    var_type: VariableType,
+}
+
+struct ColumnFormat {
+    name: TextRef,
+    width: u16,
+    decimals: u16,
+    label: TextRef,
+}
+
+/// Rows found while reading the pages, in file order, located by their bytes in the file
+#[derive(Debug, Clone)]
+enum Chunk {
+    /// A row stored in a subheader, compressed if shorter than the row length
+    Row(Range<usize>),
+    /// Consecutive uncompressed rows from `start` to at most the `end` of their page; a mixed
+    /// page holds as many as the row size subheader says, a data page as many as its own
+    /// header says
+    Rows {
+        start: usize,
+        end: usize,
+        count: Option<usize>,
+    },
+}
+
+/// Everything read from the subheaders of every page
+struct Metadata<'a> {
+    layout: Layout,
+    row_length: usize,
+    row_count: usize,
+    mix_page_row_count: usize,
+    label: Option<TextRef>,
+    texts: Vec<&'a [u8]>,
+    names: Vec<TextRef>,
+    attributes: Vec<Attributes>,
+    formats: Vec<ColumnFormat>,
+    chunks: Vec<Chunk>,
+    /// Data and mixed pages flagged as holding deleted rows
+    deleted_pages: usize,
+}
+
+impl<'a> Metadata<'a> {
+    fn new(layout: Layout) -> Self {
+        Metadata {
+            layout,
+            row_length: 0,
+            row_count: 0,
+            mix_page_row_count: 0,
+            label: None,
+            texts: Vec::new(),
+            names: Vec::new(),
+            attributes: Vec::new(),
+            formats: Vec::new(),
+            chunks: Vec::new(),
+            deleted_pages: 0,
+        }
+    }
+
+    /// Reads one page, which starts at byte `offset` of the file
+    fn read_page(&mut self, page: &'a [u8], offset: usize) -> XptResult<()> {
+        let layout = self.layout;
+        let header = layout.page_header();
+        let page_type = layout.u16(page, header)?;
+        if page_type == PAGE_COMP {
+            return Ok(());
+        }
+        let kind = page_type & PAGE_TYPE_MASK;
+        let block_count = layout.u16(page, header + 2)? as usize;
+        let subheader_count = layout.u16(page, header + 4)? as usize;
+        let pointers = header + 8;
+
+        if matches!(kind, PAGE_META | PAGE_MIX | PAGE_AMD) {
+            for index in 0..subheader_count {
+                let at = pointers + index * layout.pointer_length();
+                self.read_pointer(page, offset, at)?;
+            }
+        }
+        if matches!(kind, PAGE_DATA | PAGE_MIX) && page_type & PAGE_HAS_DELETED != 0 {
+            self.deleted_pages += 1;
+        }
+        let end = offset + page.len();
+        match kind {
+            PAGE_DATA => self.chunks.push(Chunk::Rows {
+                start: offset + pointers,
+                end,
+                count: Some(block_count),
+            }),
+            PAGE_MIX => {
+                // Rows start on an 8-byte boundary after the subheader pointers
+                let start = pointers + subheader_count * layout.pointer_length();
+                self.chunks.push(Chunk::Rows {
+                    start: offset + start + start % 8,
+                    end,
+                    count: None,
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn read_pointer(&mut self, page: &'a [u8], page_offset: usize, at: usize) -> XptResult<()> {
+        let layout = self.layout;
+        let word = layout.word();
+        let offset = layout.number(page, at)?;
+        let length = layout.number(page, at + word)?;
+        let [compression, kind] = field(page, at + 2 * word)?;
+        if length == 0 || compression == TRUNCATED_SUBHEADER {
+            return Ok(());
+        }
+        let subheader = offset
+            .checked_add(length)
+            .and_then(|end| page.get(offset..end))
+            .ok_or_else(|| {
+                invalid(format!(
+                    "A {}-byte subheader at byte {} runs past the end of the page",
+                    length, offset
+                ))
+            })?;
+        if compression == COMPRESSED_ROW {
+            self.chunks.push(Chunk::Row(
+                page_offset + offset..page_offset + offset + length,
+            ));
+            return Ok(());
+        }
+
+        match layout.signature(subheader)? {
+            ROW_SIZE => {
+                self.row_length = layout.number(subheader, 5 * word)?;
+                self.row_count = layout.number(subheader, 6 * word)?;
+                self.mix_page_row_count = layout.number(subheader, 15 * word)?;
+                self.label = length
+                    .checked_sub(130)
+                    .and_then(|at| TextRef::read(layout, subheader, at).ok())
+                    .filter(|label| label.length > 0);
+            }
+            COLUMN_SIZE => {}
+            COLUMN_TEXT => self.texts.push(&subheader[word.min(length)..]),
+            COLUMN_NAME => {
+                let count = length.saturating_sub(2 * word + 12) / 8;
+                for index in 0..count {
+                    let entry = word + 8 + index * 8;
+                    self.names.push(TextRef::read(layout, subheader, entry)?);
+                }
+            }
+            COLUMN_ATTRIBUTES => {
+                let count = length.saturating_sub(2 * word + 12) / (word + 8);
+                for index in 0..count {
+                    let entry = word + 8 + index * (word + 8);
+                    let [var_type] = field(subheader, entry + word + 6)?;
+                    self.attributes.push(Attributes {
+                        offset: layout.number(subheader, entry)?,
+                        length: layout.u32(subheader, entry + word)? as usize,
+                        var_type: if var_type == 1 {
+                            VariableType::Numeric
+                        } else {
+                            VariableType::Character
+                        },
+                    });
+                }
+            }
+            FORMAT_AND_LABEL => {
+                let base = 3 * word;
+                self.formats.push(ColumnFormat {
+                    width: layout.u16(subheader, base)?,
+                    decimals: layout.u16(subheader, base + 2)?,
+                    name: TextRef::read(layout, subheader, base + 22)?,
+                    label: TextRef::read(layout, subheader, base + 28)?,
+                });
+            }
+            _ if kind == ROW_SUBHEADER => {
+                let start = page_offset + offset;
+                self.chunks.push(Chunk::Row(start..start + length));
+            }
+            // Subheader counts, column lists and subheaders not described anywhere
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Text a reference points to, empty when it points outside the column text
+    fn text(&self, text: TextRef) -> String {
+        self.texts
+            .get(text.block)
+            .and_then(|block| block.get(text.offset..text.offset + text.length))
+            .map(text_trimmed)
+            .unwrap_or_default()
+    }
+
+    fn compression(&self) -> Option<&'static [u8]> {
+        let first = self.texts.first()?;
+        [RLE_MARKER, RDC_MARKER]
+            .into_iter()
+            .find(|marker| first.windows(marker.len()).any(|window| window == *marker))
+    }
+
+    /// Combines the names, attributes, formats and labels read into variables
+    fn columns(&self) -> XptResult<Vec<XPTVariable>> {
+        if self.row_length == 0 {
+            return Err(invalid("The file has no row size subheader"));
+        }
+        if self.names.len() != self.attributes.len() {
+            return Err(invalid(format!(
+                "The file names {} columns but describes {}",
+                self.names.len(),
+                self.attributes.len()
+            )));
+        }
+
+        let mut columns = Vec::with_capacity(self.names.len());
+        for (index, (name, attributes)) in self.names.iter().zip(&self.attributes).enumerate() {
+            let name = self.text(*name);
+            let fits = attributes
+                .offset
+                .checked_add(attributes.length)
+                .is_some_and(|end| end <= self.row_length);
+            let valid_length = match attributes.var_type {
+                VariableType::Numeric => (1..=8).contains(&attributes.length),
+                VariableType::Character => attributes.length > 0,
+            };
+            if !fits || !valid_length {
+                return Err(invalid(format!(
+                    "Column {} takes bytes {} to {} of {}-byte rows",
+                    name,
+                    attributes.offset,
+                    attributes.offset + attributes.length,
+                    self.row_length
+                )));
+            }
+
+            let (format, label) = match self.formats.get(index) {
+                Some(format) => (
+                    VariableFormat {
+                        name: self.text(format.name),
+                        width: format.width,
+                        decimals: format.decimals,
+                    },
+                    self.text(format.label),
+                ),
+                None => (VariableFormat::default(), String::new()),
+            };
+            columns.push(XPTVariable {
+                name,
+                label,
+                var_type: attributes.var_type,
+                length: attributes.length,
+                format,
+                informat: VariableFormat::default(),
+                justification: match attributes.var_type {
+                    VariableType::Numeric => Justification::Right,
+                    VariableType::Character => Justification::Left,
+                },
+                number: u16::try_from(index + 1).unwrap_or(u16::MAX),
+                offset: attributes.offset,
+            });
+        }
+        Ok(columns)
+    }
+
+    /// Number of rows a chunk holds
+    fn stored_rows(&self, chunk: &Chunk) -> usize {
+        match *chunk {
+            Chunk::Row(_) => 1,
+            Chunk::Rows { start, end, count } => {
+                let count = count.unwrap_or(self.mix_page_row_count);
+                // Rows past the end of the page are not stored there
+                count.min(end.saturating_sub(start) / self.row_length)
+            }
+        }
+    }
+}
+
+/// Decodes the values of one uncompressed row
+fn decode_row(row: &[u8], variables: &[XPTVariable], layout: Layout) -> XPTRow {
+    let values = variables
+        .iter()
+        .map(|variable| {
+            let cell = &row[variable.offset..variable.offset + variable.length];
+            match variable.var_type {
+                VariableType::Character => XPTValue::Character(text_trimmed(cell)),
+                VariableType::Numeric => decode_number(cell, layout.big_endian),
+            }
+        })
+        .collect();
+    XPTRow { values }
+}
+
+/// Decodes a double, which is stored without its least significant bytes when shorter than 8
+///
+/// Missing values are NaNs whose second byte holds the complement of a code for the kind of
+/// missing value: 1 for `.`, 0 for `._` and 2 to 27 for `.A` to `.Z`. Some writers store the
+/// ASCII character instead, so both are accepted.
+fn decode_number(cell: &[u8], big_endian: bool) -> XPTValue {
+    let mut bytes = [0u8; 8];
+    let value = if big_endian {
+        bytes[..cell.len()].copy_from_slice(cell);
+        f64::from_be_bytes(bytes)
+    } else {
+        bytes[8 - cell.len()..].copy_from_slice(cell);
+        f64::from_le_bytes(bytes)
+    };
+    if !value.is_nan() {
+        return XPTValue::Numeric(value);
+    }
+    let code = !((value.to_bits() >> 40) as u8);
+    XPTValue::Missing(match code {
+        0 | b'_' => MissingValue::Underscore,
+        2..=27 => MissingValue::Letter(char::from(b'A' + code - 2)),
+        b'A'..=b'Z' => MissingValue::Letter(char::from(code)),
+        _ => MissingValue::Standard,
+    })
+}
+
+/// Expands a row compressed with the SAS run-length encoding (`SASYZCRL`)
+///
+/// Each command byte holds the command in its high nibble and part of a length in its low
+/// nibble; commands copy bytes from the input or repeat a single byte.
+fn rle_decompress(input: &[u8], row_length: usize) -> Result<Vec<u8>, String> {
+    let mut output = Vec::with_capacity(row_length);
+    let mut position = 0;
+    let next = |position: &mut usize| -> Result<usize, String> {
+        let byte = input
+            .get(*position)
+            .copied()
+            .ok_or("the compressed row is cut short")?;
+        *position += 1;
+        Ok(usize::from(byte))
+    };
+
+    while position < input.len() {
+        let control = next(&mut position)?;
+        let (command, length) = (control >> 4, control & 0x0F);
+        let (copy, insert) = match command {
+            0x0 => (next(&mut position)? + 64 + length * 256, None),
+            0x1 => (next(&mut position)? + 64 + length * 256 + 4096, None),
+            0x2 => (length + 96, None),
+            0x4 => {
+                let count = next(&mut position)? + 18 + length * 256;
+                (0, Some((count, next(&mut position)? as u8)))
+            }
+            0x5 => (0, Some((next(&mut position)? + 17 + length * 256, b'@'))),
+            0x6 => (0, Some((next(&mut position)? + 17 + length * 256, b' '))),
+            0x7 => (0, Some((next(&mut position)? + 17 + length * 256, 0))),
+            0x8 => (length + 1, None),
+            0x9 => (length + 17, None),
+            0xA => (length + 33, None),
+            0xB => (length + 49, None),
+            0xC => (0, Some((length + 3, next(&mut position)? as u8))),
+            0xD => (0, Some((length + 2, b'@'))),
+            0xE => (0, Some((length + 2, b' '))),
+            0xF => (0, Some((length + 2, 0))),
+            _ => {
+                return Err(format!(
+                    "unknown run-length command {:#04x} at byte {}",
+                    control,
+                    position - 1
+                ))
+            }
+        };
+        if copy > 0 {
+            let bytes = input
+                .get(position..position + copy)
+                .ok_or("the compressed row is cut short")?;
+            output.extend_from_slice(bytes);
+            position += copy;
+        }
+        if let Some((count, byte)) = insert {
+            output.resize(output.len() + count, byte);
+        }
+        if output.len() > row_length {
+            break;
+        }
+    }
+    expanded(output, row_length)
+}
+
+/// Expands a row compressed with Ross Data Compression (`SASYZCR2`)
+///
+/// A 16-bit control word, most significant bit first, tells for each of the next 16 items
+/// whether it is a literal byte or a command; commands repeat a byte or copy bytes already
+/// written.
+fn rdc_decompress(input: &[u8], row_length: usize) -> Result<Vec<u8>, String> {
+    const CUT_SHORT: &str = "the compressed row is cut short";
+    let mut output: Vec<u8> = Vec::with_capacity(row_length);
+    let mut position = 0;
+    let mut control: u16 = 0;
+    let mut mask: u16 = 0;
+    let byte = |position: usize| {
+        input
+            .get(position)
+            .copied()
+            .map(usize::from)
+            .ok_or(CUT_SHORT)
+    };
+
+    while position < input.len() {
+        mask >>= 1;
+        if mask == 0 {
+            control = ((byte(position)? as u16) << 8) | byte(position + 1)? as u16;
+            position += 2;
+            mask = 0x8000;
+        }
+        if control & mask == 0 {
+            output.push(byte(position)? as u8);
+            position += 1;
+            continue;
+        }
+
+        let command = byte(position)? >> 4;
+        let count = byte(position)? & 0x0F;
+        position += 1;
+        match command {
+            // Short and long runs of one byte
+            0 | 1 => {
+                let count = if command == 0 {
+                    count + 3
+                } else {
+                    let count = count + (byte(position)? << 4) + 19;
+                    position += 1;
+                    count
+                };
+                let repeated = byte(position)? as u8;
+                position += 1;
+                output.resize(output.len() + count, repeated);
+            }
+            // Long and short copies of earlier output
+            _ => {
+                let offset = count + 3 + (byte(position)? << 4);
+                position += 1;
+                let count = if command == 2 {
+                    let count = byte(position)? + 16;
+                    position += 1;
+                    count
+                } else {
+                    command
+                };
+                let start = output.len().checked_sub(offset).ok_or_else(|| {
+                    format!(
+                        "a copy reaches {} bytes before the start of the row",
+                        offset
+                    )
+                })?;
+                for index in start..start + count {
+                    output.push(output[index]);
+                }
+            }
+        }
+        if output.len() > row_length {
+            break;
+        }
+    }
+    expanded(output, row_length)
+}
+
+fn expanded(output: Vec<u8>, row_length: usize) -> Result<Vec<u8>, String> {
+    if output.len() != row_length {
+        return Err(format!(
+            "it expands to {} bytes instead of {}",
+            output.len(),
+            row_length
+        ));
+    }
+    Ok(output)
+}
+
+/// Reads `N` bytes at `at`
+fn field<const N: usize>(data: &[u8], at: usize) -> XptResult<[u8; N]> {
+    at.checked_add(N)
+        .and_then(|end| data.get(at..end))
+        .map(|bytes| bytes.try_into().expect("the slice has N bytes"))
+        .ok_or_else(|| invalid(format!("The data ends before byte {}", at + N)))
+}
+
+fn invalid(message: impl Into<String>) -> XptError {
+    XptError::new(ErrorCode::InvalidSas7bdat, Section::File, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRUNCATED_NUMERICS: &[u8] = include_bytes!("../tests/fixtures/truncated_numerics.xpt");
+    const HEADER_LENGTH: usize = 1024;
+    const PAGE_LENGTH: usize = 4096;
+    const ROW_LENGTH: usize = 23;
+    /// Name, label, format, format width, numeric, offset and length of every column
+    const COLUMNS: [(&str, &str, &str, u16, bool, usize, usize); 3] = [
+        ("USUBJID", "Subject", "", 0, false, 11, 12),
+        ("AGE", "Age", "", 0, true, 8, 3),
+        ("VISITDT", "Visit date", "DATE", 9, true, 0, 8),
+    ];
+    const MISSING: u64 = 0xFFFF_FE00_0000_0000;
+    const MISSING_A: u64 = 0xFFFF_FD00_0000_0000;
+
+    type Compress = fn(&[u8]) -> Vec<u8>;
+
+    fn layout(big_endian: bool, wide: bool) -> Layout {
+        Layout { big_endian, wide }
+    }
+
+    fn put(layout: Layout, buffer: &mut [u8], at: usize, value: u64, size: usize) {
+        let mut bytes = value.to_le_bytes()[..size].to_vec();
+        if layout.big_endian {
+            bytes.reverse();
+        }
+        buffer[at..at + size].copy_from_slice(&bytes);
+    }
+
+    fn put_text(layout: Layout, buffer: &mut [u8], at: usize, (offset, length): (usize, usize)) {
+        put(layout, buffer, at, 0, 2);
+        put(layout, buffer, at + 2, offset as u64, 2);
+        put(layout, buffer, at + 4, length as u64, 2);
+    }
+
+    fn subheader(layout: Layout, signature: u32, length: usize) -> Vec<u8> {
+        let mut subheader = vec![0; length];
+        let signature = match signature {
+            0xFF00_0000.. => 0xFFFF_FFFF_0000_0000 | u64::from(signature),
+            _ => u64::from(signature),
+        };
+        put(layout, &mut subheader, 0, signature, layout.word());
+        subheader
+    }
+
+    fn row(layout: Layout, subject: &str, age: u64, date: u64) -> Vec<u8> {
+        let mut row = vec![b' '; ROW_LENGTH];
+        row[11..11 + subject.len()].copy_from_slice(subject.as_bytes());
+        for (value, offset, length) in [(age, 8, 3), (date, 0, 8)] {
+            let cell = if layout.big_endian {
+                value.to_be_bytes()[..length].to_vec()
+            } else {
+                value.to_le_bytes()[8 - length..].to_vec()
+            };
+            row[offset..offset + length].copy_from_slice(&cell);
+        }
+        row
+    }
+
+    /// Compresses with literal copies and runs of blanks, zeros or any other byte
+    fn rle(row: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut literal: Vec<u8> = Vec::new();
+        let flush = |output: &mut Vec<u8>, literal: &mut Vec<u8>| {
+            if !literal.is_empty() {
+                output.push(0x80 | (literal.len() - 1) as u8);
+                output.append(literal);
+            }
+        };
+        let mut index = 0;
+        while index < row.len() {
+            let byte = row[index];
+            let run = row[index..]
+                .iter()
+                .take(17)
+                .take_while(|&&other| other == byte)
+                .count();
+            if run >= 2 && (byte == b' ' || byte == 0) {
+                flush(&mut output, &mut literal);
+                output.push(if byte == b' ' { 0xE0 } else { 0xF0 } | (run - 2) as u8);
+                index += run;
+            } else if run >= 3 {
+                flush(&mut output, &mut literal);
+                output.extend([0xC0 | (run - 3) as u8, byte]);
+                index += run;
+            } else {
+                literal.push(byte);
+                if literal.len() == 16 {
+                    flush(&mut output, &mut literal);
+                }
+                index += 1;
+            }
+        }
+        flush(&mut output, &mut literal);
+        output
+    }
+
+    /// Compresses with literal bytes and short runs
+    fn rdc(row: &[u8]) -> Vec<u8> {
+        let mut items: Vec<Vec<u8>> = Vec::new();
+        let mut index = 0;
+        while index < row.len() {
+            let byte = row[index];
+            let run = row[index..]
+                .iter()
+                .take(18)
+                .take_while(|&&other| other == byte)
+                .count();
+            if run >= 3 {
+                items.push(vec![(run - 3) as u8, byte]);
+                index += run;
+            } else {
+                items.push(vec![byte]);
+                index += 1;
+            }
+        }
+        let mut output = Vec::new();
+        for group in items.chunks(16) {
+            let control = group
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| item.len() > 1)
+                .fold(0u16, |control, (bit, _)| control | 0x8000 >> bit);
+            output.extend(control.to_be_bytes());
+            output.extend(group.concat());
+        }
+        output
+    }
+
+    /// Builds a dataset of three rows: on a mixed page and a data page when `compress` is
+    /// `None`, and otherwise in subheaders compressed with it and marked by `marker`
+    fn build(layout: Layout, marker: &[u8], compress: Option<Compress>) -> Vec<u8> {
+        let word = layout.word();
+        let mut text = vec![0; 8];
+        text.extend_from_slice(marker);
+        let mut add = |value: &str| {
+            let offset = text.len();
+            text.extend_from_slice(value.as_bytes());
+            text.resize(text.len().next_multiple_of(4), 0);
+            (offset, value.len())
+        };
+        let dataset_label = add("Visites médicales");
+        let texts: Vec<_> = COLUMNS
+            .iter()
+            .map(|column| (add(column.0), add(column.1), add(column.2)))
+            .collect();
+        let text_length = text.len() as u64;
+        put(layout, &mut text, 0, text_length, 2);
+
+        let length = if layout.wide { 808 } else { 480 };
+        let mut row_size = subheader(layout, ROW_SIZE, length);
+        put(layout, &mut row_size, 5 * word, ROW_LENGTH as u64, word);
+        put(layout, &mut row_size, 6 * word, 3, word);
+        put(layout, &mut row_size, 15 * word, 2, word);
+        put_text(layout, &mut row_size, length - 130, dataset_label);
+
+        let mut column_size = subheader(layout, COLUMN_SIZE, 3 * word);
+        put(layout, &mut column_size, word, 3, word);
+        let mut column_text = subheader(layout, COLUMN_TEXT, word);
+        column_text.extend_from_slice(&text);
+        let mut names = subheader(layout, COLUMN_NAME, 2 * word + 12 + 8 * COLUMNS.len());
+        let attributes_length = 2 * word + 12 + (word + 8) * COLUMNS.len();
+        let mut attributes = subheader(layout, COLUMN_ATTRIBUTES, attributes_length);
+        let mut subheaders = Vec::new();
+        for (index, column) in COLUMNS.iter().enumerate() {
+            let (name, label, format) = texts[index];
+            put_text(layout, &mut names, word + 8 + 8 * index, name);
+            let entry = word + 8 + index * (word + 8);
+            put(layout, &mut attributes, entry, column.5 as u64, word);
+            put(layout, &mut attributes, entry + word, column.6 as u64, 4);
+            attributes[entry + word + 6] = if column.4 { 1 } else { 2 };
+
+            let mut format_and_label = subheader(layout, FORMAT_AND_LABEL, 3 * word + 34);
+            put(layout, &mut format_and_label, 3 * word, column.3.into(), 2);
+            if !column.2.is_empty() {
+                put_text(layout, &mut format_and_label, 3 * word + 22, format);
+            }
+            put_text(layout, &mut format_and_label, 3 * word + 28, label);
+            subheaders.push((format_and_label, 0, 0));
+        }
+        subheaders.splice(
+            0..0,
+            [row_size, column_size, column_text, names, attributes].map(|bytes| (bytes, 0, 0)),
+        );
+
+        let rows = [
+            row(layout, "01-001", 42f64.to_bits(), 22_646f64.to_bits()),
+            row(layout, "01-002", MISSING_A, MISSING),
+            row(layout, "01-003", 7f64.to_bits(), (-1f64).to_bits()),
+        ];
+        let pages = match compress {
+            Some(compress) => {
+                for row in &rows {
+                    let compressed = compress(row);
+                    assert!(compressed.len() < ROW_LENGTH, "{:?}", compressed);
+                    subheaders.push((compressed, COMPRESSED_ROW, ROW_SUBHEADER));
+                }
+                vec![page(layout, PAGE_META, &subheaders, &[])]
+            }
+            None => vec![
+                page(layout, PAGE_MIX, &subheaders, &rows[..2]),
+                page(layout, PAGE_DATA, &[], &rows[2..]),
+            ],
+        };
+        let mut file = header(layout, pages.len());
+        file.extend(pages.concat());
+        file
+    }
+
+    fn page(
+        layout: Layout,
+        page_type: u16,
+        subheaders: &[(Vec<u8>, u8, u8)],
+        rows: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let word = layout.word();
+        let header = layout.page_header();
+        let mut page = vec![0; PAGE_LENGTH];
+        let blocks = (subheaders.len() + rows.len()) as u64;
+        put(layout, &mut page, header, page_type.into(), 2);
+        put(layout, &mut page, header + 2, blocks, 2);
+        put(layout, &mut page, header + 4, subheaders.len() as u64, 2);
+
+        let mut end = PAGE_LENGTH;
+        for (index, (bytes, compression, kind)) in subheaders.iter().enumerate() {
+            end -= bytes.len();
+            page[end..end + bytes.len()].copy_from_slice(bytes);
+            let pointer = header + 8 + index * layout.pointer_length();
+            put(layout, &mut page, pointer, end as u64, word);
+            put(layout, &mut page, pointer + word, bytes.len() as u64, word);
+            page[pointer + 2 * word] = *compression;
+            page[pointer + 2 * word + 1] = *kind;
+        }
+        let start = header + 8 + subheaders.len() * layout.pointer_length();
+        let start = start + start % 8;
+        for (index, row) in rows.iter().enumerate() {
+            let at = start + index * ROW_LENGTH;
+            page[at..at + ROW_LENGTH].copy_from_slice(row);
+        }
+        page
+    }
+
+    fn header(layout: Layout, page_count: usize) -> Vec<u8> {
+        let mut header = vec![0; HEADER_LENGTH];
+        header[..32].copy_from_slice(&MAGIC);
+        let flag = if layout.wide { b'3' } else { b'2' };
+        header[32] = flag;
+        header[35] = flag;
+        header[37] = u8::from(!layout.big_endian);
+        header[39] = b'1';
+        header[70] = 20;
+        header[84..92].copy_from_slice(b"SAS FILE");
+        header[92..156].fill(b' ');
+        header[92..98].copy_from_slice(b"VISITS");
+        header[156..164].copy_from_slice(b"DATA    ");
+
+        let align = if layout.wide { 4 } else { 0 };
+        let created = 1_956_659_696f64.to_bits();
+        put(layout, &mut header, 164 + align, created, 8);
+        put(layout, &mut header, 172 + align, created, 8);
+        put(layout, &mut header, 196 + align, HEADER_LENGTH as u64, 4);
+        put(layout, &mut header, 200 + align, PAGE_LENGTH as u64, 4);
+        put(
+            layout,
+            &mut header,
+            204 + align,
+            page_count as u64,
+            layout.word(),
+        );
+        let align = 2 * align;
+        header[216 + align..224 + align].copy_from_slice(b"9.0401M6");
+        header[272 + align..280 + align].copy_from_slice(b"X64_10PR");
+        header
+    }
+
+    fn assert_visits(dataset: &XPTDataset) {
+        assert_eq!(dataset.title, "VISITS");
+        assert_eq!(dataset.label, "Visites médicales");
+        assert_eq!(dataset.encoding, TextEncoding::Utf8);
+        assert_eq!(dataset.dataset_type, "DATA");
+        assert_eq!(dataset.sas_version, "9.0401M6");
+        assert_eq!(dataset.os_name, "X64_10PR");
+        assert_eq!(dataset.created_date, SasDateTime::parse("01JAN22:12:34:56"));
+
+        let names: Vec<&str> = dataset.variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["USUBJID", "AGE", "VISITDT"]);
+        let visit = &dataset.variables[2];
+        assert_eq!(visit.label, "Visit date");
+        assert_eq!(visit.format.to_string(), "DATE9.");
+        assert_eq!(dataset.variables[0].var_type, VariableType::Character);
+        assert_eq!(dataset.variables[1].length, 3);
+
+        let values: Vec<Vec<XPTValue>> = dataset.rows.iter().map(|r| r.values.clone()).collect();
+        assert_eq!(
+            values,
+            [
+                [
+                    XPTValue::Character("01-001".to_string()),
+                    XPTValue::Numeric(42.0),
+                    XPTValue::Numeric(22_646.0),
+                ],
+                [
+                    XPTValue::Character("01-002".to_string()),
+                    XPTValue::Missing(MissingValue::Letter('A')),
+                    XPTValue::Missing(MissingValue::Standard),
+                ],
+                [
+                    XPTValue::Character("01-003".to_string()),
+                    XPTValue::Numeric(7.0),
+                    XPTValue::Numeric(-1.0),
+                ],
+            ]
+        );
+        assert!(dataset.warnings.is_empty(), "{:?}", dataset.warnings);
+    }
+
+    #[test]
+    fn reads_every_byte_order_and_word_size() {
+        for (big_endian, wide) in [(false, false), (false, true), (true, false), (true, true)] {
+            let file = build(layout(big_endian, wide), b"        ", None);
+            assert_visits(&read_sas7bdat(&file, ParseOptions::default()).unwrap());
+        }
+    }
+
+    #[test]
+    fn reads_compressed_rows() {
+        for (big_endian, wide) in [(false, true), (true, false)] {
+            let layout = layout(big_endian, wide);
+            let file = build(layout, RLE_MARKER, Some(rle));
+            assert_visits(&read_sas7bdat(&file, ParseOptions::default()).unwrap());
+            let file = build(layout, RDC_MARKER, Some(rdc));
+            assert_visits(&read_sas7bdat(&file, ParseOptions::default()).unwrap());
+        }
+    }
+
+    #[test]
+    fn skips_rows_that_cannot_be_expanded_when_recovering() {
+        let cut_short: Compress = |row| rle(row)[..4].to_vec();
+        let file = build(layout(false, true), RLE_MARKER, Some(cut_short));
+        let error = read_sas7bdat(&file, ParseOptions::default()).unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidSas7bdat);
+        assert_eq!(error.section, Section::Observations);
+
+        let options = ParseOptions {
+            recover: true,
+            ..ParseOptions::default()
+        };
+        let dataset = read_sas7bdat(&file, options).unwrap();
+        assert!(dataset.rows.is_empty());
+        assert_eq!(dataset.warnings.len(), 4, "{:?}", dataset.warnings);
+        assert!(dataset.warnings[0].starts_with("Row 1 skipped"));
+    }
+
+    #[test]
+    fn reads_the_rows_stored_when_more_are_declared() {
+        let mut file = build(layout(false, true), b"        ", None);
+        let row_size = file
+            .windows(4)
+            .position(|bytes| bytes == ROW_SIZE.to_le_bytes())
+            .unwrap();
+        file[row_size + 48..row_size + 56].copy_from_slice(&u64::MAX.to_le_bytes());
+        let dataset = read_sas7bdat(&file, ParseOptions::default()).unwrap();
+        assert_eq!(dataset.rows.len(), 3);
+        assert_eq!(
+            dataset.warnings,
+            [format!("The file declares {} rows but holds 3", u64::MAX)]
+        );
+    }
+
+    #[test]
+    fn decodes_rows_on_demand() {
+        for (marker, compress) in [
+            (&b"        "[..], None),
+            (RDC_MARKER, Some(rdc as Compress)),
+        ] {
+            let file = build(layout(true, true), marker, compress);
+            let lazy = read_sas7bdat_lazy(&file, ParseOptions::default()).unwrap();
+            assert!(lazy.dataset.rows.is_empty());
+            assert_eq!(lazy.row_count(), 3);
+            let mut dataset = lazy.dataset.clone();
+            dataset.rows = (0..3)
+                .map(|index| lazy.row(&file, index).unwrap())
+                .collect();
+            assert_visits(&dataset);
+            assert!(lazy.row(&file, 3).is_none());
+        }
+    }
+
+    #[test]
+    fn warns_about_pages_holding_deleted_rows() {
+        let layout = layout(false, false);
+        let mut file = build(layout, b"        ", None);
+        // The data page type, 0x0100, flagged as holding deleted rows
+        file[HEADER_LENGTH + PAGE_LENGTH + layout.page_header()] = 0x80;
+        let dataset = read_sas7bdat(&file, ParseOptions::default()).unwrap();
+        assert_eq!(dataset.rows.len(), 3);
+        assert_eq!(
+            dataset.warnings,
+            ["Rows marked as deleted are read as live rows (pages flagged: 1)"]
+        );
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_sas_datasets() {
+        let error = read_sas7bdat(TRUNCATED_NUMERICS, ParseOptions::default()).unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidSas7bdat);
+
+        let mut file = build(layout(false, false), b"        ", None);
+        file.truncate(HEADER_LENGTH + PAGE_LENGTH);
+        let error = read_sas7bdat(&file, ParseOptions::default()).unwrap_err();
+        assert!(error.message.contains("declares 2 pages"), "{}", error);
+    }
+
+    #[test]
+    fn expands_run_length_commands() {
+        let mut input = vec![0x00, 0x01];
+        input.extend([b'x'; 65]);
+        input.extend([0x82, b'a', b'b', b'c', 0xC1, b'y', 0xE0, 0xF1, 0xD0]);
+        input.extend([0x40, 0x00, b'z', 0x60, 0x00, 0x70, 0x00]);
+        let mut expected = vec![b'x'; 65];
+        expected.extend(b"abcyyyy  \0\0\0@@");
+        expected.extend([b'z'; 18]);
+        expected.extend([b' '; 17]);
+        expected.extend([0; 17]);
+        assert_eq!(rle_decompress(&input, expected.len()), Ok(expected));
+        assert!(rle_decompress(&[0x85, b'a'], 6).is_err());
+    }
+
+    #[test]
+    fn expands_ross_data_compression_commands() {
+        // Three literals, a short copy, a short run, a long run and a long copy
+        let input = [
+            0x1E, 0x00, b'a', b'b', b'c', 0x60, 0x00, 0x02, b'X', 0x11, 0x00, b'Z', 0x23, 0x01,
+            0x00,
+        ];
+        let mut expected = b"abcabcabcXXXXX".to_vec();
+        expected.extend([b'Z'; 20]);
+        let copied = expected[expected.len() - 22..expected.len() - 6].to_vec();
+        expected.extend(copied);
+        assert_eq!(rdc_decompress(&input, expected.len()), Ok(expected));
+        assert!(rdc_decompress(&[0x80, 0x00, 0x30, 0x00], 3).is_err());
+    }
+}
@@ -0,0 +1,657 @@
+//! Display formats for numeric values, driven by the SAS format named in the namestr
+//!
+//! SAS dates count days and datetimes count seconds from 1 January 1960; times count seconds
+//! since midnight. Only the formats common in clinical and analysis datasets are implemented:
+//! DATE, YYMMDD, E8601DA, DATETIME, E8601DT, TIME, E8601TM, w.d, BEST, COMMA, PERCENT and Z.
+//! Values with any other format are left for the caller to render as plain numbers.
+
+use crate::xpt_parser::{SasDateTime, VariableFormat};
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+/// Days between 1960-01-01, the SAS epoch, and 1970-01-01
+pub(crate) const SAS_EPOCH_OFFSET_DAYS: i64 = 3653;
+/// Largest day count SAS accepts as a date (31 December 20000)
+const MAX_SAS_DAYS: f64 = 6_589_335.0;
+pub(crate) const SECONDS_PER_DAY: i64 = 86_400;
+/// Digits of an IEEE double that are meaningful in decimal output
+const SIGNIFICANT_DIGITS: usize = 15;
+/// Decimal places honoured in time and datetime formats
+const MAX_TIME_DECIMALS: usize = 6;
+
+/// Renders `value` using `format`, or returns `None` when the format is not supported or the
+/// value cannot be shown with it
+pub fn format_numeric(value: f64, format: &VariableFormat) -> Option<String> {
+    if !value.is_finite() {
+        return None;
+    }
+    let width = (format.width > 0).then_some(format.width as usize);
+    let decimals = format.decimals as usize;
+
+    let text = match format.name.to_ascii_uppercase().as_str() {
+        "DATE" => date(value, width.unwrap_or(7))?,
+        "YYMMDD" => yymmdd(value, width.unwrap_or(8))?,
+        "E8601DA" | "IS8601DA" => {
+            let date = Date::from_sas_days(value)?;
+            format!("{:04}-{:02}-{:02}", date.year, date.month, date.day)
+        }
+        "DATETIME" => datetime(value, width.unwrap_or(16), decimals)?,
+        "E8601DT" | "IS8601DT" => iso_datetime(value, decimals)?,
+        "TIME" => time(value, width.unwrap_or(8), decimals),
+        "E8601TM" | "IS8601TM" => iso_time(value, decimals)?,
+        // A bare format with neither name nor width means no format was assigned
+        "" if width.is_none() => return None,
+        "" | "F" => fixed(value, width.unwrap_or(12), decimals),
+        "BEST" => best(value, width.unwrap_or(12)),
+        "COMMA" => comma(value, width.unwrap_or(6), decimals),
+        "PERCENT" => percent(value, width.unwrap_or(6), decimals),
+        "Z" => zero_padded(value, width.unwrap_or(1), decimals),
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// Kind of point in time a numeric format shows its value as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Temporal {
+    /// Days since 1960-01-01
+    Date,
+    /// Seconds since 1960-01-01T00:00:00
+    DateTime,
+    /// Seconds since midnight
+    Time,
+}
+
+impl Temporal {
+    /// Reads what `format` shows a value as, or `None` for formats that show plain numbers
+    ///
+    /// This recognises more formats than [`format_numeric`] renders, including the
+    /// separator variants of MMDDYY, DDMMYY, YYMMDD, YYMM and MMYY such as `YYMMDDN`.
+    pub(crate) fn of(format: &VariableFormat) -> Option<Self> {
+        let name = format.name.to_ascii_uppercase();
+        let kind = match name.as_str() {
+            "DATE" | "DAY" | "DDMMYY" | "DOWNAME" | "E8601DA" | "IS8601DA" | "B8601DA"
+            | "JULDAY" | "JULIAN" | "MMDDYY" | "MMYY" | "MONNAME" | "MONTH" | "MONYY" | "NENGO"
+            | "QTR" | "WEEKDATE" | "WEEKDATX" | "WEEKDAY" | "WORDDATE" | "WORDDATX" | "YEAR"
+            | "YYMM" | "YYMMDD" | "YYMON" | "YYQ" => Temporal::Date,
+            "DATETIME" | "DATEAMPM" | "DTDATE" | "E8601DT" | "IS8601DT" | "B8601DT" | "E8601DN"
+            | "B8601DN" | "MDYAMPM" => Temporal::DateTime,
+            "TIME" | "TIMEAMPM" | "TOD" | "HHMM" | "HOUR" | "MMSS" | "E8601TM" | "IS8601TM"
+            | "B8601TM" => Temporal::Time,
+            _ => {
+                // MMDDYYw. and the like followed by a separator letter: B, C, D, N, P, S or X
+                let separated = ["MMDDYY", "DDMMYY", "YYMMDD", "YYMM", "MMYY"]
+                    .iter()
+                    .filter_map(|stem| name.strip_prefix(stem))
+                    .any(|separator| matches!(separator, "B" | "C" | "D" | "N" | "P" | "S" | "X"));
+                if !separated {
+                    return None;
+                }
+                Temporal::Date
+            }
+        };
+        Some(kind)
+    }
+}
+
+/// Converts a SAS date to days since 1970-01-01, ignoring any fraction of a day
+#[cfg_attr(not(feature = "arrow"), allow(dead_code))]
+pub(crate) fn unix_days(value: f64) -> Option<i64> {
+    let days = value.floor();
+    if !days.is_finite() || days.abs() > MAX_SAS_DAYS {
+        return None;
+    }
+    Some(days as i64 - SAS_EPOCH_OFFSET_DAYS)
+}
+
+/// Converts a SAS datetime to microseconds since 1970-01-01T00:00:00, rounding to the nearest
+/// microsecond
+#[cfg_attr(not(feature = "arrow"), allow(dead_code))]
+pub(crate) fn unix_micros(value: f64) -> Option<i64> {
+    if !value.is_finite() || (value / SECONDS_PER_DAY as f64).abs() > MAX_SAS_DAYS {
+        return None;
+    }
+    let micros = (value * 1e6).round() as i64;
+    Some(micros - SAS_EPOCH_OFFSET_DAYS * SECONDS_PER_DAY * 1_000_000)
+}
+
+/// Parses an ISO 8601 date such as `2022-03-02` into a SAS date
+pub(crate) fn parse_iso_date(text: &str) -> Option<f64> {
+    let days = Date::parse_iso(text.trim())?.unix_days() + SAS_EPOCH_OFFSET_DAYS;
+    Some(days as f64)
+}
+
+/// Parses an ISO 8601 datetime such as `2022-03-02T12:34:56.5` into a SAS datetime
+///
+/// A trailing `Z` is accepted; other UTC offsets are not, as SAS datetimes carry no time zone.
+pub(crate) fn parse_iso_datetime(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let text = text.strip_suffix('Z').unwrap_or(text);
+    let (date, time) = text.split_once('T')?;
+    let days = parse_iso_date(date)?;
+    Some(days * SECONDS_PER_DAY as f64 + parse_iso_time(time)?)
+}
+
+/// Parses an ISO 8601 time such as `12:34` or `12:34:56.25` into a SAS time
+pub(crate) fn parse_iso_time(text: &str) -> Option<f64> {
+    let mut parts = text.trim().split(':');
+    let hours: u32 = parts.next().filter(|part| part.len() == 2)?.parse().ok()?;
+    let minutes: u32 = parts.next().filter(|part| part.len() == 2)?.parse().ok()?;
+    let seconds: f64 = match parts.next() {
+        Some(part) if part.len() >= 2 && part.as_bytes()[..2].iter().all(u8::is_ascii_digit) => {
+            part.parse().ok()?
+        }
+        Some(_) => return None,
+        None => 0.0,
+    };
+    if parts.next().is_some() || hours > 24 || minutes > 59 || !(0.0..60.0).contains(&seconds) {
+        return None;
+    }
+    Some(f64::from(hours * 3600 + minutes * 60) + seconds)
+}
+
+/// Converts a SAS datetime to a header timestamp, dropping any fraction of a second
+pub(crate) fn sas_datetime(value: f64) -> Option<SasDateTime> {
+    if !value.is_finite() {
+        return None;
+    }
+    let seconds_per_day = SECONDS_PER_DAY as f64;
+    let date = Date::from_sas_days((value / seconds_per_day).floor())?;
+    let seconds = value.rem_euclid(seconds_per_day) as u32;
+    Some(SasDateTime {
+        year: u16::try_from(date.year).ok()?,
+        month: date.month as u8,
+        day: date.day as u8,
+        hour: (seconds / 3600) as u8,
+        minute: (seconds / 60 % 60) as u8,
+        second: (seconds % 60) as u8,
+    })
+}
+
+/// Calendar date in the proleptic Gregorian calendar
+#[derive(Debug, PartialEq, Eq)]
+struct Date {
+    year: i64,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    /// Converts a SAS date (days since 1960-01-01), ignoring any fraction of a day
+    fn from_sas_days(value: f64) -> Option<Self> {
+        let days = value.floor();
+        if days.abs() > MAX_SAS_DAYS {
+            return None;
+        }
+        Some(Self::from_unix_days(days as i64 - SAS_EPOCH_OFFSET_DAYS))
+    }
+
+    /// Parses an ISO 8601 calendar date such as `2022-03-02`
+    fn parse_iso(text: &str) -> Option<Self> {
+        let mut parts = text.splitn(3, '-');
+        let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+        let date = Date {
+            year: year.parse().ok()?,
+            month: month.parse().ok()?,
+            day: day.parse().ok()?,
+        };
+        // Reject days past the end of the month by converting back
+        (Self::from_unix_days(date.unix_days()) == date).then_some(date)
+    }
+
+    /// Days since 1970-01-01 (Howard Hinnant's `days_from_civil`)
+    fn unix_days(&self) -> i64 {
+        let year = self.year - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let month = i64::from(self.month);
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5
+            + i64::from(self.day)
+            - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// Converts days since 1970-01-01 to a civil date (Howard Hinnant's `civil_from_days`)
+    fn from_unix_days(days: i64) -> Self {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        } as u32;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Date { year, month, day }
+    }
+
+    fn month_name(&self) -> &'static str {
+        MONTHS[self.month as usize - 1]
+    }
+
+    fn short_year(&self) -> i64 {
+        self.year.rem_euclid(100)
+    }
+}
+
+/// A count of seconds split into whole seconds and a fraction kept as `decimals` digits
+struct Seconds {
+    whole: i64,
+    fraction: i64,
+    decimals: usize,
+}
+
+impl Seconds {
+    /// Truncates `value` to `decimals` places, as SAS does for time and datetime formats
+    fn new(value: f64, decimals: usize) -> Option<Self> {
+        let decimals = decimals.min(MAX_TIME_DECIMALS);
+        let scale = 10_i64.pow(decimals as u32);
+        let ticks = (value * scale as f64).floor();
+        if ticks.abs() >= i64::MAX as f64 {
+            return None;
+        }
+        let ticks = ticks as i64;
+        Some(Seconds {
+            whole: ticks.div_euclid(scale),
+            fraction: ticks.rem_euclid(scale),
+            decimals,
+        })
+    }
+
+    /// Fraction of a second as `.fff`, or nothing without decimals
+    fn fraction_suffix(&self) -> String {
+        if self.decimals == 0 {
+            String::new()
+        } else {
+            format!(".{:0width$}", self.fraction, width = self.decimals)
+        }
+    }
+}
+
+/// DATEw.: `01JAN60` by default, `01JAN1960` from width 9 and `01-JAN-1960` from width 11
+fn date(value: f64, width: usize) -> Option<String> {
+    let date = Date::from_sas_days(value)?;
+    let text = match width {
+        0..=5 => format!("{:02}{}", date.day, date.month_name()),
+        6..=8 => format!(
+            "{:02}{}{:02}",
+            date.day,
+            date.month_name(),
+            date.short_year()
+        ),
+        9..=10 => format!("{:02}{}{:04}", date.day, date.month_name(), date.year),
+        _ => format!("{:02}-{}-{:04}", date.day, date.month_name(), date.year),
+    };
+    Some(text)
+}
+
+/// YYMMDDw.: `600101` below width 8, `60-01-01` below width 10 and `1960-01-01` from there
+fn yymmdd(value: f64, width: usize) -> Option<String> {
+    let date = Date::from_sas_days(value)?;
+    let text = match width {
+        0..=7 => format!("{:02}{:02}{:02}", date.short_year(), date.month, date.day),
+        8..=9 => format!("{:02}-{:02}-{:02}", date.short_year(), date.month, date.day),
+        _ => format!("{:04}-{:02}-{:02}", date.year, date.month, date.day),
+    };
+    Some(text)
+}
+
+/// DATETIMEw.d: `01JAN60:00:00:00` by default and `01JAN1960:00:00:00` from width 18, dropping
+/// the seconds and then the time as the width shrinks
+fn datetime(value: f64, width: usize, decimals: usize) -> Option<String> {
+    let seconds = Seconds::new(value, decimals)?;
+    let date = Date::from_sas_days(seconds.whole.div_euclid(SECONDS_PER_DAY) as f64)?;
+    let time_of_day = seconds.whole.rem_euclid(SECONDS_PER_DAY);
+    let (hours, minutes, secs) = (time_of_day / 3600, time_of_day / 60 % 60, time_of_day % 60);
+
+    let day = if width >= 18 {
+        format!("{:02}{}{:04}", date.day, date.month_name(), date.year)
+    } else {
+        format!(
+            "{:02}{}{:02}",
+            date.day,
+            date.month_name(),
+            date.short_year()
+        )
+    };
+    let text = match width {
+        0..=12 => day,
+        13..=15 => format!("{}:{:02}:{:02}", day, hours, minutes),
+        _ => format!(
+            "{}:{:02}:{:02}:{:02}{}",
+            day,
+            hours,
+            minutes,
+            secs,
+            seconds.fraction_suffix()
+        ),
+    };
+    Some(text)
+}
+
+/// E8601DT.: `1960-01-01T00:00:00`, with `decimals` digits of fractional seconds
+fn iso_datetime(value: f64, decimals: usize) -> Option<String> {
+    let seconds = Seconds::new(value, decimals)?;
+    let date = Date::from_sas_days(seconds.whole.div_euclid(SECONDS_PER_DAY) as f64)?;
+    let time_of_day = seconds.whole.rem_euclid(SECONDS_PER_DAY);
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+        date.year,
+        date.month,
+        date.day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60,
+        seconds.fraction_suffix()
+    ))
+}
+
+/// E8601TM.: `hh:mm:ss` within a day, with `decimals` digits of fractional seconds
+fn iso_time(value: f64, decimals: usize) -> Option<String> {
+    let seconds = Seconds::new(value, decimals)?;
+    if !(0..SECONDS_PER_DAY).contains(&seconds.whole) {
+        return None;
+    }
+    Some(format!(
+        "{:02}:{:02}:{:02}{}",
+        seconds.whole / 3600,
+        seconds.whole / 60 % 60,
+        seconds.whole % 60,
+        seconds.fraction_suffix()
+    ))
+}
+
+/// TIMEw.d: `h:mm:ss`, or `h:mm` below width 7; hours are not wrapped at 24
+fn time(value: f64, width: usize, decimals: usize) -> String {
+    let sign = if value < 0.0 { "-" } else { "" };
+    let Some(seconds) = Seconds::new(value.abs(), decimals) else {
+        return best(value, width);
+    };
+    let (hours, minutes, secs) = (
+        seconds.whole / 3600,
+        seconds.whole / 60 % 60,
+        seconds.whole % 60,
+    );
+    if width < 7 {
+        format!("{}{}:{:02}", sign, hours, minutes)
+    } else {
+        format!(
+            "{}{}:{:02}:{:02}{}",
+            sign,
+            hours,
+            minutes,
+            secs,
+            seconds.fraction_suffix()
+        )
+    }
+}
+
+/// w.d: fixed-point with `decimals` places, falling back to BESTw. when it does not fit
+fn fixed(value: f64, width: usize, decimals: usize) -> String {
+    let text = rounded(value, decimals);
+    if text.len() <= width {
+        text
+    } else {
+        best(value, width)
+    }
+}
+
+/// BESTw.: the most precise decimal or scientific notation that fits in `width` characters
+fn best(value: f64, width: usize) -> String {
+    let width = width.clamp(1, 32);
+    if value == 0.0 {
+        return "0".to_string();
+    }
+
+    let integer_digits = rounded(value.trunc(), 0).len();
+    if value.abs() >= 1e-4 && integer_digits <= width {
+        let significant = if value.abs() >= 1.0 {
+            integer_digits - usize::from(value < 0.0)
+        } else {
+            0
+        };
+        let mut decimals = width
+            .saturating_sub(integer_digits + 1)
+            .min(SIGNIFICANT_DIGITS.saturating_sub(significant));
+        loop {
+            let text = trim_fraction(rounded(value, decimals));
+            if text.len() <= width {
+                return text;
+            }
+            if decimals == 0 {
+                break;
+            }
+            decimals -= 1;
+        }
+    }
+
+    for digits in (0..SIGNIFICANT_DIGITS).rev() {
+        let text = format!("{:.*E}", digits, value);
+        let (mantissa, exponent) = text.split_once('E').unwrap_or((&text, "0"));
+        let text = format!("{}E{}", trim_fraction(mantissa.to_string()), exponent);
+        if text.len() <= width {
+            return text;
+        }
+    }
+    "*".repeat(width)
+}
+
+/// COMMAw.d: thousands separated by commas, with `decimals` places, shown as w.d when the commas
+/// do not fit
+fn comma(value: f64, width: usize, decimals: usize) -> String {
+    let text = rounded(value, decimals);
+    let (sign, unsigned) = match text.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", text.as_str()),
+    };
+    let (integer, fraction) = match unsigned.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (unsigned, None),
+    };
+
+    let mut grouped = String::with_capacity(integer.len() + integer.len() / 3);
+    for (index, digit) in integer.chars().enumerate() {
+        if index > 0 && (integer.len() - index) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    let text = match fraction {
+        Some(fraction) => format!("{}{}.{}", sign, grouped, fraction),
+        None => format!("{}{}", sign, grouped),
+    };
+    if text.len() <= width {
+        text
+    } else {
+        fixed(value, width, decimals)
+    }
+}
+
+/// PERCENTw.d: the value times 100 followed by `%`, with negatives in parentheses; asterisks
+/// when that does not fit in `width`, as the bare value would read as a different number
+fn percent(value: f64, width: usize, decimals: usize) -> String {
+    let text = rounded(value * 100.0, decimals);
+    let text = match text.strip_prefix('-') {
+        Some(magnitude) => format!("({}%)", magnitude),
+        None => format!("{}%", text),
+    };
+    if text.len() <= width {
+        text
+    } else {
+        "*".repeat(width)
+    }
+}
+
+/// Zw.d: fixed-point with `decimals` places, padded with leading zeros to `width`
+fn zero_padded(value: f64, width: usize, decimals: usize) -> String {
+    let text = rounded(value, decimals);
+    match text.strip_prefix('-') {
+        Some(magnitude) => format!("-{:0>width$}", magnitude, width = width.saturating_sub(1)),
+        None => format!("{:0>width$}", text, width = width),
+    }
+}
+
+/// Rounds half away from zero to `decimals` places, as SAS does, and drops the sign of a value
+/// that rounds to zero
+fn rounded(value: f64, decimals: usize) -> String {
+    let scale = 10_f64.powi(decimals.min(SIGNIFICANT_DIGITS) as i32);
+    let scaled = value * scale;
+    // Beyond 2^53 every double is an integer, so there is nothing left to round
+    let value = if scaled.abs() < 2_f64.powi(53) {
+        scaled.round() / scale
+    } else {
+        value
+    };
+    let text = format!("{:.*}", decimals, value);
+    match text.strip_prefix('-') {
+        Some(magnitude) if magnitude.bytes().all(|b| b == b'0' || b == b'.') => {
+            magnitude.to_string()
+        }
+        _ => text,
+    }
+}
+
+/// Drops trailing zeros after the decimal point, and the point itself if nothing remains
+fn trim_fraction(text: String) -> String {
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(name: &str, width: u16, decimals: u16) -> VariableFormat {
+        VariableFormat {
+            name: name.to_string(),
+            width,
+            decimals,
+        }
+    }
+
+    /// Values, formats and the text SAS prints for them, with leading blanks removed
+    const REFERENCE: &[(f64, &str, u16, u16, &str)] = &[
+        (0.0, "DATE", 9, 0, "01JAN1960"),
+        (22_646.0, "DATE", 9, 0, "01JAN2022"),
+        (22_646.0, "DATE", 7, 0, "01JAN22"),
+        (22_646.0, "DATE", 11, 0, "01-JAN-2022"),
+        (-1.0, "DATE", 9, 0, "31DEC1959"),
+        (-138_061.0, "DATE", 9, 0, "01JAN1582"),
+        (22_706.0, "YYMMDD", 10, 0, "2022-03-02"),
+        (22_706.0, "YYMMDD", 8, 0, "22-03-02"),
+        (22_706.0, "YYMMDD", 6, 0, "220302"),
+        (22_706.9, "E8601DA", 10, 0, "2022-03-02"),
+        (1_956_614_400.0, "DATETIME", 20, 0, "01JAN2022:00:00:00"),
+        (1_956_659_696.0, "DATETIME", 16, 0, "01JAN22:12:34:56"),
+        (1_956_659_696.25, "DATETIME", 22, 2, "01JAN2022:12:34:56.25"),
+        (1_956_659_696.0, "E8601DT", 19, 0, "2022-01-01T12:34:56"),
+        (1_956_659_696.5, "E8601DT", 21, 1, "2022-01-01T12:34:56.5"),
+        (45_296.0, "TIME", 8, 0, "12:34:56"),
+        (32_703.0, "TIME", 8, 0, "9:05:03"),
+        (32_703.0, "TIME", 5, 0, "9:05"),
+        (97_200.0, "TIME", 8, 0, "27:00:00"),
+        (-3_600.0, "TIME", 8, 0, "-1:00:00"),
+        (12.3456, "", 8, 2, "12.35"),
+        (-0.004, "", 8, 2, "0.00"),
+        (2.5, "F", 5, 0, "3"),
+        (1234.5, "", 3, 0, "1E3"),
+        (1.0 / 3.0, "BEST", 12, 0, "0.3333333333"),
+        (123_456_789.0, "BEST", 12, 0, "123456789"),
+        (1.5e15, "BEST", 12, 0, "1.5E15"),
+        (-1.234_567_89e-10, "BEST", 12, 0, "-1.23457E-10"),
+        (1_234_567.891, "COMMA", 12, 2, "1,234,567.89"),
+        (-1000.0, "COMMA", 6, 0, "-1,000"),
+        (1_234_567.0, "COMMA", 8, 0, "1234567"),
+        (1_234_567.891, "COMMA", 6, 2, "1.23E6"),
+        (0.125, "PERCENT", 8, 1, "12.5%"),
+        (12.5, "PERCENT", 4, 0, "****"),
+        (-0.05, "PERCENT", 6, 0, "(5%)"),
+        (42.0, "Z", 5, 0, "00042"),
+        (-3.5, "Z", 6, 1, "-003.5"),
+    ];
+
+    #[test]
+    fn formats_reference_values() {
+        for &(value, name, width, decimals, expected) in REFERENCE {
+            assert_eq!(
+                format_numeric(value, &format(name, width, decimals)).as_deref(),
+                Some(expected),
+                "{} with {}{}.{}",
+                value,
+                name,
+                width,
+                decimals
+            );
+        }
+    }
+
+    #[test]
+    fn leaves_unsupported_values_unformatted() {
+        assert_eq!(format_numeric(1.0, &format("", 0, 0)), None);
+        assert_eq!(format_numeric(1.0, &format("MMDDYY", 10, 0)), None);
+        assert_eq!(format_numeric(f64::NAN, &format("BEST", 12, 0)), None);
+        assert_eq!(format_numeric(1e12, &format("DATE", 9, 0)), None);
+    }
+
+    #[test]
+    fn recognises_date_datetime_and_time_formats() {
+        assert_eq!(Temporal::of(&format("date", 9, 0)), Some(Temporal::Date));
+        assert_eq!(Temporal::of(&format("YYMMDDN", 8, 0)), Some(Temporal::Date));
+        assert_eq!(
+            Temporal::of(&format("MMDDYYS", 10, 0)),
+            Some(Temporal::Date)
+        );
+        assert_eq!(
+            Temporal::of(&format("E8601DT", 19, 0)),
+            Some(Temporal::DateTime)
+        );
+        assert_eq!(Temporal::of(&format("TIME", 8, 0)), Some(Temporal::Time));
+        assert_eq!(Temporal::of(&format("BEST", 12, 0)), None);
+        assert_eq!(Temporal::of(&format("", 8, 2)), None);
+    }
+
+    #[test]
+    fn converts_to_the_unix_epoch() {
+        assert_eq!(unix_days(0.0), Some(-3653));
+        assert_eq!(unix_days(22_646.9), Some(18_993));
+        assert_eq!(unix_days(f64::NAN), None);
+        assert_eq!(unix_micros(1_956_614_400.0), Some(1_640_995_200_000_000));
+        assert_eq!(unix_micros(0.25), Some(-315_619_200_000_000 + 250_000));
+    }
+
+    #[test]
+    fn parses_iso_dates_and_times() {
+        assert_eq!(parse_iso_date("2022-01-01"), Some(22_646.0));
+        assert_eq!(parse_iso_date("1959-12-31"), Some(-1.0));
+        assert_eq!(parse_iso_date("2022-02-30"), None);
+        assert_eq!(parse_iso_date("2022-03"), None);
+        assert_eq!(
+            parse_iso_datetime("2022-01-01T12:34:56.5"),
+            Some(1_956_659_696.5)
+        );
+        assert_eq!(
+            parse_iso_datetime("2022-01-01T00:00:00Z"),
+            Some(1_956_614_400.0)
+        );
+        assert_eq!(parse_iso_datetime("2022-01-01T00:00:00+02:00"), None);
+        assert_eq!(parse_iso_time("09:05"), Some(32_700.0));
+        assert_eq!(parse_iso_time("9:05:03"), None);
+        assert_eq!(
+            format_numeric(32_703.0, &format("E8601TM", 8, 0)).as_deref(),
+            Some("09:05:03")
+        );
+    }
+}
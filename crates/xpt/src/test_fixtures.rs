@@ -0,0 +1,85 @@
+//! Datasets shared by the tests of the writers and exporters
+
+use crate::text_encoding::TextEncoding;
+use crate::xpt_parser::{
+    Justification, MissingValue, SasDateTime, VariableFormat, VariableType, XPTDataset,
+    XPTLibraryHeader, XPTRow, XPTValue, XPTVariable,
+};
+
+pub(crate) fn variable(name: &str, var_type: VariableType, length: usize) -> XPTVariable {
+    XPTVariable {
+        name: name.to_string(),
+        label: String::new(),
+        var_type,
+        length,
+        format: VariableFormat::default(),
+        informat: VariableFormat::default(),
+        justification: Justification::Left,
+        number: 0,
+        offset: 0,
+    }
+}
+
+pub(crate) fn dataset(
+    title: &str,
+    variables: Vec<XPTVariable>,
+    rows: Vec<Vec<XPTValue>>,
+) -> XPTDataset {
+    XPTDataset {
+        title: title.to_string(),
+        label: String::new(),
+        dataset_type: "DATA".to_string(),
+        sas_version: "9.4".to_string(),
+        os_name: "X64_10PR".to_string(),
+        created_date: SasDateTime::parse("01JAN24:08:30:00"),
+        modified_date: SasDateTime::parse("02JAN24:09:45:10"),
+        library: XPTLibraryHeader::default(),
+        variables,
+        rows: rows.into_iter().map(|values| XPTRow { values }).collect(),
+        encoding: TextEncoding::Utf8,
+        warnings: Vec::new(),
+    }
+}
+
+/// Two subject visits, with a character, a date, a datetime and a plain numeric variable; the
+/// date and datetime of the second visit are missing
+pub(crate) fn visits() -> XPTDataset {
+    let numeric = |name: &str, label: &str, format: &str, width| XPTVariable {
+        label: label.to_string(),
+        format: VariableFormat {
+            name: format.to_string(),
+            width,
+            decimals: 0,
+        },
+        justification: Justification::Right,
+        ..variable(name, VariableType::Numeric, 8)
+    };
+    let mut subject = variable("USUBJID", VariableType::Character, 8);
+    subject.label = "Subject".to_string();
+
+    let mut visits = dataset(
+        "SV",
+        vec![
+            subject,
+            numeric("SVSTDT", "Start date", "DATE", 9),
+            numeric("SVSTDTM", "Start date and time", "E8601DT", 19),
+            numeric("VISITNUM", "Visit number", "", 8),
+        ],
+        vec![
+            vec![
+                XPTValue::Character("01-001".to_string()),
+                XPTValue::Numeric(22_646.0),
+                XPTValue::Numeric(1_956_659_696.0),
+                XPTValue::Numeric(1.5),
+            ],
+            vec![
+                XPTValue::Character("01-002".to_string()),
+                XPTValue::Missing(MissingValue::Standard),
+                XPTValue::Missing(MissingValue::Letter('A')),
+                XPTValue::Numeric(2.5),
+            ],
+        ],
+    );
+    visits.label = "Subject Visits".to_string();
+    visits
+}
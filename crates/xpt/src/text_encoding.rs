@@ -0,0 +1,186 @@
+//! Character encodings for the text stored in transport files
+//!
+//! XPORT files carry no encoding marker: names, labels and character values hold whatever bytes
+//! the writing session used. The parser reads every text field as Latin-1, which maps each byte
+//! to the char of the same value, so the original bytes can be recovered from any decoded string
+//! and re-decoded here once the file encoding is known.
+
+use serde::{Deserialize, Serialize};
+
+/// Encoding applied to the text fields of a transport file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextEncoding {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "latin-1")]
+    Latin1,
+    #[serde(rename = "windows-1252")]
+    Windows1252,
+}
+
+/// Characters for bytes 0x80-0x9F in Windows-1252; the five unassigned bytes keep their C1
+/// control character, as browsers do
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+impl TextEncoding {
+    /// Picks UTF-8 when every text field is valid UTF-8 (which includes plain ASCII), and
+    /// Windows-1252, the superset of Latin-1 written by Windows SAS sessions, otherwise
+    ///
+    /// A field may end partway through a character, as SAS cuts labels and values at their
+    /// length in bytes, so an incomplete sequence at the end of a field still counts as UTF-8.
+    pub fn detect<'a>(texts: impl IntoIterator<Item = &'a str>) -> Self {
+        let all_utf8 = texts
+            .into_iter()
+            .all(|text| utf8_prefix(&latin1_bytes(text)).is_some());
+        if all_utf8 {
+            TextEncoding::Utf8
+        } else {
+            TextEncoding::Windows1252
+        }
+    }
+
+    /// Re-decodes a string the parser read as Latin-1 using this encoding
+    pub fn decode(&self, latin1: &str) -> String {
+        if latin1.is_ascii() {
+            return latin1.to_string();
+        }
+        match self {
+            TextEncoding::Latin1 => latin1.to_string(),
+            TextEncoding::Utf8 => {
+                let bytes = latin1_bytes(latin1);
+                match utf8_prefix(&bytes) {
+                    Some(text) => text.to_string(),
+                    None => String::from_utf8_lossy(&bytes).into_owned(),
+                }
+            }
+            TextEncoding::Windows1252 => latin1
+                .chars()
+                .map(|c| match c as u32 {
+                    code @ 0x80..=0x9F => WINDOWS_1252_HIGH[code as usize - 0x80],
+                    _ => c,
+                })
+                .collect(),
+        }
+    }
+
+    /// Encodes text for writing, or returns the first character this encoding cannot represent
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, char> {
+        match self {
+            TextEncoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            TextEncoding::Latin1 => text
+                .chars()
+                .map(|c| u8::try_from(c).map_err(|_| c))
+                .collect(),
+            TextEncoding::Windows1252 => text
+                .chars()
+                .map(|c| {
+                    if let Some(index) = WINDOWS_1252_HIGH.iter().position(|&high| high == c) {
+                        return Ok(0x80 + index as u8);
+                    }
+                    match c as u32 {
+                        0x80..=0x9F => Err(c),
+                        _ => u8::try_from(c).map_err(|_| c),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Decodes raw bytes as Latin-1, one char per byte
+pub fn latin1_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| char::from(byte)).collect()
+}
+
+/// Decodes `bytes` as UTF-8, dropping an incomplete character at the end, or returns `None`
+/// when they hold an invalid sequence
+fn utf8_prefix(bytes: &[u8]) -> Option<&str> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(text),
+        // No error length means the input ended inside a character
+        Err(error) if error.error_len().is_none() => {
+            std::str::from_utf8(&bytes[..error.valid_up_to()]).ok()
+        }
+        Err(_) => None,
+    }
+}
+
+/// Recovers the bytes of a string decoded by [`latin1_string`]
+fn latin1_bytes(text: &str) -> Vec<u8> {
+    text.chars().map(|c| c as u32 as u8).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_each_encoding() {
+        let utf8 = latin1_string("Müller – São Paulo".as_bytes());
+        assert_eq!(TextEncoding::Utf8.decode(&utf8), "Müller – São Paulo");
+
+        let latin1 = latin1_string(b"M\xFCller");
+        assert_eq!(TextEncoding::Latin1.decode(&latin1), "Müller");
+
+        let windows = latin1_string(b"\x93Caf\xE9\x94 \x80 5");
+        assert_eq!(
+            TextEncoding::Windows1252.decode(&windows),
+            "\u{201C}Café\u{201D} € 5"
+        );
+    }
+
+    #[test]
+    fn encodes_back_to_the_file_bytes() {
+        assert_eq!(
+            TextEncoding::Windows1252.encode("\u{201C}Café\u{201D} € 5"),
+            Ok(b"\x93Caf\xE9\x94 \x80 5".to_vec())
+        );
+        assert_eq!(
+            TextEncoding::Latin1.encode("Müller"),
+            Ok(b"M\xFCller".to_vec())
+        );
+        assert_eq!(TextEncoding::Latin1.encode("5 €"), Err('€'));
+        assert_eq!(TextEncoding::Windows1252.encode("\u{80}"), Err('\u{80}'));
+    }
+
+    #[test]
+    fn detects_utf8_and_falls_back_to_windows_1252() {
+        let ascii = latin1_string(b"AGE");
+        let utf8 = latin1_string("Âge".as_bytes());
+        let windows = latin1_string(b"\xC2ge moyen \x96 ann\xE9es");
+
+        assert_eq!(TextEncoding::detect([ascii.as_str()]), TextEncoding::Utf8);
+        assert_eq!(
+            TextEncoding::detect([ascii.as_str(), utf8.as_str()]),
+            TextEncoding::Utf8
+        );
+        assert_eq!(
+            TextEncoding::detect([utf8.as_str(), windows.as_str()]),
+            TextEncoding::Windows1252
+        );
+    }
+
+    #[test]
+    fn tolerates_a_character_cut_at_the_end_of_a_field() {
+        // "Durée" cut after the first byte of the two-byte é
+        let cut = latin1_string(&"Durée".as_bytes()[..4]);
+        let utf8 = latin1_string("Âge".as_bytes());
+        assert_eq!(
+            TextEncoding::detect([cut.as_str(), utf8.as_str()]),
+            TextEncoding::Utf8
+        );
+        assert_eq!(TextEncoding::Utf8.decode(&cut), "Dur");
+
+        // An incomplete character before the end is still not UTF-8
+        let inside = latin1_string(b"Dur\xC3e");
+        assert_eq!(
+            TextEncoding::detect([inside.as_str()]),
+            TextEncoding::Windows1252
+        );
+    }
+}
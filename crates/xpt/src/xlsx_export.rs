@@ -0,0 +1,472 @@
+//! Export of datasets as Excel workbooks, behind the `xlsx` feature
+//!
+//! Each dataset gets a sheet of its own whose first two rows, frozen above the data, hold the
+//! variable names and labels. With [`XlsxOptions::excel_dates`], variables with a SAS date,
+//! datetime or time format are written as Excel dates and times with a matching number format.
+//! Ordinary missing values are left blank and special ones are written as their code, such as
+//! `.A`. A last sheet, `Variables`, lists the name, label, type, length and format of the
+//! variables of every dataset.
+
+use crate::sas_format::{format_numeric, Temporal, SECONDS_PER_DAY};
+use crate::xpt_error::{ErrorCode, Section, XptError, XptResult};
+use crate::xpt_parser::{
+    MissingValue, VariableFormat, VariableType, XPTDataset, XPTRow, XPTValue, XPTVariable,
+};
+use rust_xlsxwriter::{ColNum, Format, RowNum, Workbook, Worksheet, XlsxError};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::io::{Seek, Write};
+
+/// Rows and columns of an Excel sheet
+const MAX_ROWS: usize = 1_048_576;
+const MAX_COLUMNS: usize = 16_384;
+/// Rows above the data: variable names, then labels
+const HEADER_ROWS: usize = 2;
+/// Longest sheet name Excel accepts
+const MAX_SHEET_NAME: usize = 31;
+/// Excel serial number of 1960-01-01, the SAS epoch
+const SAS_EPOCH_SERIAL: f64 = 21_916.0;
+/// First serial number Excel shows as the right date, 1900-03-01, past its phantom 29 Feb 1900
+const FIRST_RELIABLE_SERIAL: f64 = 61.0;
+/// Serial number of 9999-12-31, the last date Excel shows
+const LAST_SERIAL: f64 = 2_958_465.0;
+/// Widest column, in characters, that is sized to fit its contents
+const MAX_COLUMN_WIDTH: usize = 60;
+
+/// Options for [`XlsxWriter`]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct XlsxOptions {
+    /// Write variables with a SAS date, datetime or time format as Excel dates and times
+    /// instead of SAS day and second counts
+    pub excel_dates: bool,
+    /// Add the `Variables` sheet describing every variable
+    pub variables_sheet: bool,
+}
+
+impl Default for XlsxOptions {
+    fn default() -> Self {
+        XlsxOptions {
+            excel_dates: true,
+            variables_sheet: true,
+        }
+    }
+}
+
+/// Builds an Excel workbook one dataset at a time
+///
+/// The workbook is assembled in memory and written out by [`XlsxWriter::finish`]:
+///
+/// ```
+/// use xpt::{XPTParser, XlsxOptions, XlsxWriter};
+///
+/// # let bytes = std::fs::read("tests/fixtures/truncated_numerics.xpt")?;
+/// let mut writer = XlsxWriter::new(XlsxOptions::default());
+/// for dataset in XPTParser::parse(&bytes, None)? {
+///     writer.write_dataset(&dataset)?;
+/// }
+/// let workbook = writer.finish(std::io::Cursor::new(Vec::new()))?.into_inner();
+/// assert!(workbook.starts_with(b"PK"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// Sheets are named after their dataset, with the characters Excel does not allow in a sheet
+/// name replaced by `_` and a number added to names already taken.
+pub struct XlsxWriter {
+    workbook: Workbook,
+    options: XlsxOptions,
+    /// Lower-case names of the sheets added so far
+    sheet_names: HashSet<String>,
+    /// Dataset name and variable for every row of the `Variables` sheet
+    variables: Vec<(String, XPTVariable)>,
+    formats: Formats,
+}
+
+/// Cell formats shared by every sheet
+struct Formats {
+    name: Format,
+    label: Format,
+    date: Format,
+    datetime: Format,
+    time: Format,
+}
+
+impl XlsxWriter {
+    pub fn new(options: XlsxOptions) -> Self {
+        XlsxWriter {
+            workbook: Workbook::new(),
+            options,
+            sheet_names: HashSet::new(),
+            variables: Vec::new(),
+            formats: Formats {
+                name: Format::new().set_bold(),
+                label: Format::new().set_italic(),
+                date: Format::new().set_num_format("yyyy-mm-dd"),
+                datetime: Format::new().set_num_format("yyyy-mm-dd hh:mm:ss"),
+                time: Format::new().set_num_format("[h]:mm:ss"),
+            },
+        }
+    }
+
+    /// Adds a sheet holding the variables of `dataset` and the rows yielded by `rows`
+    ///
+    /// Only the metadata of `dataset` is used. Errors yielded by `rows` stop the export and are
+    /// passed through, as is a dataset with more rows or columns than a sheet holds.
+    pub fn add_dataset<E>(
+        &mut self,
+        dataset: &XPTDataset,
+        rows: impl IntoIterator<Item = Result<XPTRow, E>>,
+    ) -> Result<(), E>
+    where
+        E: From<XptError>,
+    {
+        if dataset.variables.len() > MAX_COLUMNS {
+            return Err(too_large(format!(
+                "{} has {} variables, more than the {} columns of an Excel sheet",
+                dataset.title,
+                dataset.variables.len(),
+                MAX_COLUMNS
+            ))
+            .into());
+        }
+        let name = self.sheet_name(&dataset.title);
+        let temporal: Vec<Option<Temporal>> = dataset
+            .variables
+            .iter()
+            .map(|variable| match variable.var_type {
+                VariableType::Numeric if self.options.excel_dates => Temporal::of(&variable.format),
+                _ => None,
+            })
+            .collect();
+        let formats = &self.formats;
+        let sheet = self.workbook.add_worksheet();
+        sheet.set_name(name).map_err(sheet_error)?;
+
+        let mut widths: Vec<usize> = Vec::with_capacity(dataset.variables.len());
+        for (column, variable) in dataset.variables.iter().enumerate() {
+            let column = column as ColNum;
+            sheet
+                .write_string_with_format(0, column, &variable.name, &formats.name)
+                .map_err(sheet_error)?;
+            if !variable.label.is_empty() {
+                sheet
+                    .write_string_with_format(1, column, &variable.label, &formats.label)
+                    .map_err(sheet_error)?;
+            }
+            widths.push(
+                variable
+                    .name
+                    .chars()
+                    .count()
+                    .max(variable.label.chars().count()),
+            );
+        }
+        sheet
+            .set_freeze_panes(HEADER_ROWS as RowNum, 0)
+            .map_err(sheet_error)?;
+
+        for (index, row) in rows.into_iter().enumerate() {
+            let row = row?;
+            let line = index + HEADER_ROWS;
+            if line >= MAX_ROWS {
+                return Err(too_large(format!(
+                    "{} has more than the {} rows an Excel sheet holds below its header",
+                    dataset.title,
+                    MAX_ROWS - HEADER_ROWS
+                ))
+                .into());
+            }
+            for (column, value) in row.values.iter().enumerate().take(widths.len()) {
+                let width = write_cell(
+                    sheet,
+                    formats,
+                    (line as RowNum, column as ColNum),
+                    value,
+                    temporal[column],
+                )
+                .map_err(sheet_error)?;
+                widths[column] = widths[column].max(width);
+            }
+        }
+        for (column, width) in widths.into_iter().enumerate() {
+            sheet
+                .set_column_width(column as ColNum, (width.min(MAX_COLUMN_WIDTH) + 2) as f64)
+                .map_err(sheet_error)?;
+        }
+
+        self.variables.extend(
+            dataset
+                .variables
+                .iter()
+                .map(|variable| (dataset.title.clone(), variable.clone())),
+        );
+        Ok(())
+    }
+
+    /// Adds a sheet holding the variables and every row of `dataset`
+    pub fn write_dataset(&mut self, dataset: &XPTDataset) -> XptResult<()> {
+        self.add_dataset(dataset, dataset.rows.iter().cloned().map(Ok))
+    }
+
+    /// Adds the `Variables` sheet, writes the workbook to `out` and gives it back
+    pub fn finish<W: Write + Seek + Send>(mut self, mut out: W) -> XptResult<W> {
+        if self.options.variables_sheet {
+            self.write_variables_sheet().map_err(sheet_error)?;
+        }
+        self.workbook.save_to_writer(&mut out).map_err(|error| {
+            XptError::new(
+                ErrorCode::Io,
+                Section::File,
+                format!("Unable to write the workbook: {}", error),
+            )
+        })?;
+        Ok(out)
+    }
+
+    fn write_variables_sheet(&mut self) -> Result<(), XlsxError> {
+        let name = self.sheet_name("Variables");
+        let bold = &self.formats.name;
+        let sheet = self.workbook.add_worksheet();
+        sheet.set_name(name)?;
+
+        let headings = ["Dataset", "Name", "Label", "Type", "Length", "Format"];
+        let mut widths: Vec<usize> = headings.iter().map(|heading| heading.len()).collect();
+        for (column, heading) in headings.iter().enumerate() {
+            sheet.write_string_with_format(0, column as ColNum, *heading, bold)?;
+        }
+        sheet.set_freeze_panes(1, 0)?;
+
+        for (index, (dataset, variable)) in self.variables.iter().enumerate() {
+            let row = (index + 1) as RowNum;
+            let kind = match variable.var_type {
+                VariableType::Numeric => "Numeric",
+                VariableType::Character => "Character",
+            };
+            let texts = [
+                dataset.clone(),
+                variable.name.clone(),
+                variable.label.clone(),
+                kind.to_string(),
+                String::new(),
+                variable.format.to_string(),
+            ];
+            for (column, text) in texts.iter().enumerate() {
+                if !text.is_empty() {
+                    sheet.write_string(row, column as ColNum, text)?;
+                }
+                widths[column] = widths[column].max(text.chars().count());
+            }
+            sheet.write_number(row, 4, variable.length as f64)?;
+        }
+        for (column, width) in widths.into_iter().enumerate() {
+            sheet.set_column_width(column as ColNum, (width.min(MAX_COLUMN_WIDTH) + 2) as f64)?;
+        }
+        Ok(())
+    }
+
+    /// Turns a dataset name into a sheet name Excel accepts and no other sheet has
+    fn sheet_name(&mut self, title: &str) -> String {
+        let cleaned: String = title
+            .chars()
+            .map(|c| match c {
+                '[' | ']' | ':' | '*' | '?' | '/' | '\\' => '_',
+                c => c,
+            })
+            .collect();
+        let cleaned = cleaned.trim_matches('\'');
+        let base = if cleaned.is_empty() { "Sheet" } else { cleaned };
+
+        let mut name: String = base.chars().take(MAX_SHEET_NAME).collect();
+        let mut number = 2;
+        while self.sheet_names.contains(&name.to_lowercase()) {
+            let suffix = format!(" ({})", number);
+            let kept = MAX_SHEET_NAME - suffix.len();
+            name = base.chars().take(kept).collect::<String>() + &suffix;
+            number += 1;
+        }
+        self.sheet_names.insert(name.to_lowercase());
+        name
+    }
+}
+
+/// Writes one cell and returns the number of characters it shows, for sizing its column
+fn write_cell(
+    sheet: &mut Worksheet,
+    formats: &Formats,
+    (row, column): (RowNum, ColNum),
+    value: &XPTValue,
+    temporal: Option<Temporal>,
+) -> Result<usize, XlsxError> {
+    match value {
+        XPTValue::Character(text) if text.is_empty() => Ok(0),
+        XPTValue::Character(text) => {
+            sheet.write_string(row, column, text)?;
+            Ok(text.chars().count())
+        }
+        XPTValue::Missing(MissingValue::Standard) => Ok(0),
+        XPTValue::Missing(missing) => {
+            let code = missing.code();
+            sheet.write_string(row, column, &code)?;
+            Ok(code.len())
+        }
+        XPTValue::Numeric(number) => {
+            if let Some((kind, serial)) =
+                temporal.and_then(|kind| Some((kind, excel_serial(*number, kind)?)))
+            {
+                let (format, width) = match kind {
+                    Temporal::Date => (&formats.date, 10),
+                    Temporal::DateTime => (&formats.datetime, 19),
+                    Temporal::Time => (&formats.time, 8),
+                };
+                sheet.write_number_with_format(row, column, serial, format)?;
+                return Ok(width);
+            }
+            // Dates Excel cannot show are written as ISO 8601 text instead
+            if let Some(text) = temporal.and_then(|kind| iso_text(*number, kind)) {
+                sheet.write_string(row, column, &text)?;
+                return Ok(text.len());
+            }
+            sheet.write_number(row, column, *number)?;
+            Ok(number.to_string().len())
+        }
+    }
+}
+
+/// Excel serial number of a SAS date, datetime or time, if Excel shows it as the same point in
+/// time
+fn excel_serial(value: f64, kind: Temporal) -> Option<f64> {
+    let serial = match kind {
+        Temporal::Date => value.floor() + SAS_EPOCH_SERIAL,
+        Temporal::DateTime => value / SECONDS_PER_DAY as f64 + SAS_EPOCH_SERIAL,
+        Temporal::Time => return (value >= 0.0).then_some(value / SECONDS_PER_DAY as f64),
+    };
+    (FIRST_RELIABLE_SERIAL..=LAST_SERIAL)
+        .contains(&serial)
+        .then_some(serial)
+}
+
+fn iso_text(value: f64, kind: Temporal) -> Option<String> {
+    let name = match kind {
+        Temporal::Date => "E8601DA",
+        Temporal::DateTime => "E8601DT",
+        Temporal::Time => return None,
+    };
+    let format = VariableFormat {
+        name: name.to_string(),
+        width: 0,
+        decimals: 0,
+    };
+    format_numeric(value, &format)
+}
+
+fn too_large(message: String) -> XptError {
+    XptError::new(ErrorCode::InvalidValue, Section::Observations, message)
+}
+
+fn sheet_error(error: XlsxError) -> XptError {
+    XptError::new(
+        ErrorCode::InvalidValue,
+        Section::Observations,
+        format!("Unable to fill the workbook: {}", error),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::visits;
+    use crate::xpt_parser::XPTParser;
+    use std::io::Cursor;
+
+    const TRUNCATED_NUMERICS: &[u8] = include_bytes!("../tests/fixtures/truncated_numerics.xpt");
+
+    /// Names of the parts of the zip archive holding the workbook, from its central directory
+    fn parts(workbook: &[u8]) -> Vec<String> {
+        (0..workbook.len().saturating_sub(46))
+            .filter(|&at| workbook[at..].starts_with(b"PK\x01\x02"))
+            .map(|at| {
+                let length = u16::from_le_bytes([workbook[at + 28], workbook[at + 29]]) as usize;
+                String::from_utf8_lossy(&workbook[at + 46..at + 46 + length]).into_owned()
+            })
+            .collect()
+    }
+
+    fn read_part(workbook: &[u8], name: &str) -> String {
+        let mut archive = zip::ZipArchive::new(Cursor::new(workbook)).unwrap();
+        std::io::read_to_string(archive.by_name(name).unwrap()).unwrap()
+    }
+
+    fn build(datasets: &[XPTDataset], options: XlsxOptions) -> Vec<u8> {
+        let mut writer = XlsxWriter::new(options);
+        for dataset in datasets {
+            writer.write_dataset(dataset).unwrap();
+        }
+        writer.finish(Cursor::new(Vec::new())).unwrap().into_inner()
+    }
+
+    #[test]
+    fn writes_one_sheet_per_dataset_and_a_variables_sheet() {
+        let titled = |title: &str| XPTDataset {
+            title: title.to_string(),
+            ..visits()
+        };
+        let workbook = build(
+            &[titled("SV"), titled("sv"), titled("A/B")],
+            XlsxOptions::default(),
+        );
+        let sheets = parts(&workbook)
+            .iter()
+            .filter(|part| part.starts_with("xl/worksheets/sheet"))
+            .count();
+        assert_eq!(sheets, 4);
+
+        let mut writer = XlsxWriter::new(XlsxOptions::default());
+        assert_eq!(writer.sheet_name("SV"), "SV");
+        assert_eq!(writer.sheet_name("sv"), "sv (2)");
+        assert_eq!(writer.sheet_name("A/B"), "A_B");
+        assert_eq!(writer.sheet_name(&"X".repeat(40)).len(), MAX_SHEET_NAME);
+    }
+
+    #[test]
+    fn parses_and_exports_a_transport_file() {
+        let datasets = XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap();
+        let workbook = build(&datasets, XlsxOptions::default());
+        assert!(workbook.starts_with(b"PK\x03\x04"));
+    }
+
+    #[test]
+    fn rejects_more_rows_than_a_sheet_holds() {
+        let dataset = visits();
+        // Rows without values keep the test quick, as no cell is written
+        let rows =
+            std::iter::repeat_n(XPTRow { values: Vec::new() }, MAX_ROWS).map(Ok::<_, XptError>);
+        let mut writer = XlsxWriter::new(XlsxOptions::default());
+        let error = writer.add_dataset(&dataset, rows).unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidValue);
+    }
+
+    #[test]
+    fn writes_dates_as_excel_serials() {
+        let mut sheet = Worksheet::new();
+        let writer = XlsxWriter::new(XlsxOptions::default());
+        let formats = &writer.formats;
+        let width = |sheet: &mut Worksheet, value: f64, kind| {
+            write_cell(sheet, formats, (2, 0), &XPTValue::Numeric(value), kind).unwrap()
+        };
+        assert_eq!(width(&mut sheet, 22_646.0, Some(Temporal::Date)), 10);
+        assert_eq!(
+            width(&mut sheet, 1_956_659_696.0, Some(Temporal::DateTime)),
+            19
+        );
+        // Before March 1900 Excel dates are off by a day, so the text is written instead
+        assert_eq!(width(&mut sheet, -25_000.0, Some(Temporal::Date)), 10);
+        assert_eq!(width(&mut sheet, 1.5, None), 3);
+
+        // 22646 days after 1960 is 2022-01-01, 44562 days after Excel's 1899-12-30
+        let workbook = build(&[visits()], XlsxOptions::default());
+        let cells = read_part(&workbook, "xl/worksheets/sheet1.xml");
+        assert!(cells.contains("<v>44562</v>"), "{}", cells);
+        let styles = read_part(&workbook, "xl/styles.xml");
+        assert!(styles.contains(r#"formatCode="yyyy-mm-dd""#), "{}", styles);
+    }
+}
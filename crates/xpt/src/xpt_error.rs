@@ -0,0 +1,137 @@
+use crate::xpt_parser::constants::RECORD_SIZE;
+use serde::Serialize;
+use std::fmt;
+
+/// Part of the transport file that was being read or written when an error occurred
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Section {
+    /// The file as a whole, before any record was read
+    File,
+    LibraryHeader,
+    MemberHeader,
+    Namestr,
+    Observations,
+}
+
+impl Section {
+    fn describe(&self) -> &'static str {
+        match self {
+            Section::File => "file",
+            Section::LibraryHeader => "library header",
+            Section::MemberHeader => "member header",
+            Section::Namestr => "namestr",
+            Section::Observations => "observations",
+        }
+    }
+}
+
+/// Machine-readable kind of a read or write error, stable for scripts that branch on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The file could not be read from disk, or written to its destination
+    Io,
+    /// The file to decode again, or the dataset to read rows from, is no longer held in memory
+    NotLoaded,
+    /// The file is shorter than a single 80-byte record
+    FileTooSmall,
+    /// No MEMBER (or MEMBV8) header record was found
+    MissingMemberHeader,
+    /// A member has no NAMESTR (or NAMSTV8) header record
+    MissingNamestrHeader,
+    /// A member has no OBS (or OBSV8) header record
+    MissingObsHeader,
+    /// The header records of a member are out of order
+    InvalidHeaderLayout,
+    /// The namestr block cannot hold a single variable descriptor
+    NamestrBlockTooSmall,
+    /// A variable descriptor has an unknown type or a zero length
+    InvalidNamestr,
+    /// No variable descriptor could be parsed
+    NoVariables,
+    /// The variables of a member add up to a zero-width observation
+    ZeroRowWidth,
+    /// The observation block does not split into whole rows
+    ObservationWidth,
+    /// A name, label, format or header field cannot be written in the transport format
+    InvalidMetadata,
+    /// A row does not match the variables of the member it is written to
+    InvalidValue,
+    /// A variable chosen for export is not in the dataset
+    UnknownVariable,
+    /// A Dataset-JSON file is not valid JSON or lacks a required attribute
+    InvalidDatasetJson,
+    /// A SAS7BDAT file is not a SAS dataset, or its header, metadata or compressed rows cannot
+    /// be read
+    InvalidSas7bdat,
+}
+
+/// Error raised while reading or writing a transport file, locating where in the file it happened
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XptError {
+    pub code: ErrorCode,
+    pub section: Section,
+    /// Byte offset of the record being read or written, if known
+    pub offset: Option<usize>,
+    /// 1-based number of the 80-byte record containing `offset`
+    pub record: Option<usize>,
+    /// 1-based index of the library member being read or written, if any
+    pub member: Option<usize>,
+    pub message: String,
+}
+
+pub type XptResult<T> = std::result::Result<T, XptError>;
+
+impl XptError {
+    pub fn new(code: ErrorCode, section: Section, message: impl Into<String>) -> Self {
+        XptError {
+            code,
+            section,
+            offset: None,
+            record: None,
+            member: None,
+            message: message.into(),
+        }
+    }
+
+    /// Records the byte offset where the error was found
+    pub fn at(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self.record = Some(offset / RECORD_SIZE + 1);
+        self
+    }
+
+    /// Records the 1-based index of the member being read or written
+    pub fn in_member(mut self, member: usize) -> Self {
+        self.member = Some(member);
+        self
+    }
+}
+
+impl fmt::Display for XptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}", self.message, self.section.describe())?;
+        if let Some(member) = self.member {
+            write!(f, ", member {}", member)?;
+        }
+        if let (Some(offset), Some(record)) = (self.offset, self.record) {
+            write!(f, ", byte {}, record {}", offset, record)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::error::Error for XptError {}
+
+impl From<std::io::Error> for XptError {
+    /// Failure to write an export, which has no record to point at
+    fn from(error: std::io::Error) -> Self {
+        XptError::new(
+            ErrorCode::Io,
+            Section::File,
+            format!("Unable to write the output: {}", error),
+        )
+    }
+}
@@ -0,0 +1,2072 @@
+use crate::ibm_float::{self, Diagnostic};
+use crate::text_encoding::{latin1_string, TextEncoding};
+use crate::xpt_error::{ErrorCode, Section, XptError, XptResult};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Constants for XPT format parsing
+pub(crate) mod constants {
+    /// Standard XPT record size in bytes
+    pub const RECORD_SIZE: usize = 80;
+    /// Length of a name string record in bytes
+    pub const NAME_STRING_RECORD_LENGTH: usize = 140;
+    /// Length of a name string record written on VAX/VMS hosts
+    pub const VMS_NAME_STRING_RECORD_LENGTH: usize = 136;
+    /// Header record opening a Version 5 library
+    pub const LIBRARY_HEADER: &[u8] = b"HEADER RECORD*******LIBRARY HEADER RECORD!!!!!!!";
+    /// Header record opening a Version 8/9 library
+    pub const LIBRARY_V8_HEADER: &[u8] = b"HEADER RECORD*******LIBV8   HEADER RECORD!!!!!!!";
+    /// Header record opening each member of the library
+    pub const MEMBER_HEADER: &[u8] = b"HEADER RECORD*******MEMBER  HEADER RECORD!!!!!!!";
+    /// Header record opening each member of a Version 8/9 library
+    pub const MEMBER_V8_HEADER: &[u8] = b"HEADER RECORD*******MEMBV8  HEADER RECORD!!!!!!!";
+    /// Start shared by the member header tags of both revisions
+    pub const MEMBER_PREFIX: &[u8] = b"HEADER RECORD*******MEMB";
+    /// Header record preceding the member descriptor records
+    pub const DESCRIPTOR_HEADER: &[u8] = b"HEADER RECORD*******DSCRPTR HEADER RECORD!!!!!!!";
+    /// Header record preceding the member descriptor records of a Version 8/9 member
+    pub const DESCRIPTOR_V8_HEADER: &[u8] = b"HEADER RECORD*******DSCPTV8 HEADER RECORD!!!!!!!";
+    /// Header record preceding the variable metadata of a member
+    pub const NAMESTR_HEADER: &[u8] = b"HEADER RECORD*******NAMESTR HEADER RECORD!!!!!!!";
+    /// Header record preceding the variable metadata of a Version 8/9 member
+    pub const NAMESTR_V8_HEADER: &[u8] = b"HEADER RECORD*******NAMSTV8 HEADER RECORD!!!!!!!";
+    /// Header record preceding long labels (Version 8)
+    pub const LABEL_V8_HEADER: &[u8] = b"HEADER RECORD*******LABELV8 HEADER RECORD!!!!!!!";
+    /// Header record preceding long labels, formats and informats (Version 9)
+    pub const LABEL_V9_HEADER: &[u8] = b"HEADER RECORD*******LABELV9 HEADER RECORD!!!!!!!";
+    /// Header record preceding the observations of a member
+    pub const OBS_HEADER: &[u8] = b"HEADER RECORD*******OBS     HEADER RECORD!!!!!!!";
+    /// Header record preceding the observations of a Version 8/9 member
+    pub const OBS_V8_HEADER: &[u8] = b"HEADER RECORD*******OBSV8   HEADER RECORD!!!!!!!";
+    /// Minimum length for numeric variables (SAS allows truncated floats of 3 to 8 bytes, and
+    /// 2 bytes on mainframes)
+    pub const MIN_NUMERIC_LENGTH: usize = 2;
+    /// Full length of an IBM 360 double-precision float
+    pub const FULL_NUMERIC_LENGTH: usize = 8;
+    /// Minimum length for character variables
+    pub const MIN_CHARACTER_LENGTH: usize = 1;
+}
+
+/// Represents a parsed XPT dataset
+#[derive(Debug, Clone)]
+pub struct XPTDataset {
+    pub title: String,
+    /// Dataset label from the member descriptor, empty when none was set
+    pub label: String,
+    /// Dataset type from the member descriptor (usually empty or `DATA`)
+    pub dataset_type: String,
+    /// Version of SAS that wrote the member
+    pub sas_version: String,
+    /// Operating system the member was written on
+    pub os_name: String,
+    pub created_date: Option<SasDateTime>,
+    pub modified_date: Option<SasDateTime>,
+    /// Header of the library the member belongs to
+    pub library: XPTLibraryHeader,
+    pub variables: Vec<XPTVariable>,
+    pub rows: Vec<XPTRow>,
+    /// Encoding used to decode names, labels and character values
+    pub encoding: TextEncoding,
+    /// Problems found while decoding values that did not prevent parsing
+    pub warnings: Vec<String>,
+}
+
+impl XPTDataset {
+    /// Every text field read from the file, still decoded as Latin-1 until the encoding is applied
+    pub(crate) fn texts(&self) -> impl Iterator<Item = &str> {
+        let metadata = [self.title.as_str(), self.label.as_str()]
+            .into_iter()
+            .chain(
+                self.variables
+                    .iter()
+                    .flat_map(|variable| [variable.name.as_str(), variable.label.as_str()]),
+            );
+        let values = self
+            .rows
+            .iter()
+            .flat_map(|row| row.values.iter())
+            .filter_map(|value| match value {
+                XPTValue::Character(text) => Some(text.as_str()),
+                _ => None,
+            });
+        metadata.chain(values)
+    }
+
+    /// Re-decodes the Latin-1 text fields with `encoding`
+    pub(crate) fn apply_encoding(&mut self, encoding: TextEncoding) {
+        self.encoding = encoding;
+        if encoding == TextEncoding::Latin1 {
+            return;
+        }
+        self.title = encoding.decode(&self.title);
+        self.label = encoding.decode(&self.label);
+        for variable in &mut self.variables {
+            variable.name = encoding.decode(&variable.name);
+            variable.label = encoding.decode(&variable.label);
+        }
+        for row in &mut self.rows {
+            row.apply_encoding(encoding);
+        }
+    }
+
+    /// Names a member whose header leaves the dataset name blank after the file it came from
+    ///
+    /// This is done once the encoding is applied, since the file name is already Unicode.
+    pub(crate) fn name_after_file(&mut self, suggested_filename: Option<&str>) {
+        if !self.title.is_empty() {
+            return;
+        }
+        self.title = suggested_filename
+            .and_then(|name| std::path::Path::new(name).file_stem())
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("XPT Dataset")
+            .to_string();
+    }
+}
+
+impl XPTRow {
+    /// Re-decodes the Latin-1 character values with `encoding`
+    pub(crate) fn apply_encoding(&mut self, encoding: TextEncoding) {
+        if encoding == TextEncoding::Latin1 {
+            return;
+        }
+        for value in &mut self.values {
+            if let XPTValue::Character(text) = value {
+                *text = encoding.decode(text);
+            }
+        }
+    }
+}
+
+/// What every member of a library is read with
+pub(crate) struct MemberContext<'a> {
+    pub(crate) version: XportVersion,
+    pub(crate) options: ParseOptions,
+    pub(crate) library: &'a XPTLibraryHeader,
+}
+
+/// Metadata of a member, along with what is needed to lay out its observations
+pub(crate) struct MemberHeader {
+    /// Metadata with empty `rows`
+    dataset: XPTDataset,
+    /// File offset of the first observation byte
+    pub(crate) obs_data_start: usize,
+    cell_offsets: Vec<usize>,
+    /// Row widths to try, in order of preference; the last pads rows to a multiple of 8 bytes
+    row_width_candidates: Vec<usize>,
+    /// Bytes of a row the variables take up, after which the padding of a padded row starts
+    storage_width: usize,
+}
+
+impl MemberHeader {
+    /// Number of bytes at the end of the observation block that [`MemberHeader::into_layout`]
+    /// looks at
+    pub(crate) fn tail_length(&self) -> usize {
+        let widest = self.row_width_candidates.iter().max().copied().unwrap_or(0);
+        2 * widest + constants::RECORD_SIZE
+    }
+
+    /// Works out the row width and row count of an observation block of `observation_length`
+    /// bytes, given its last bytes in `tail`: the whole block, or at least
+    /// [`MemberHeader::tail_length`] bytes of it
+    pub(crate) fn into_layout(
+        mut self,
+        tail: &[u8],
+        observation_length: usize,
+        options: ParseOptions,
+    ) -> XptResult<(XPTDataset, ObservationLayout)> {
+        let tail_start = observation_length - tail.len();
+        // Bytes `start..end` of the observation block, which lie within the tail
+        let bytes = |start: usize, end: usize| &tail[start - tail_start..end - tail_start];
+
+        let blank = |byte: &u8| *byte == 0x00 || *byte == 0x20;
+        let padded_index = self.row_width_candidates.len() - 1;
+        let storage_width = self.storage_width;
+        // Rows padded to 8 bytes hold blanks after their values; only the rows in the tail are
+        // checked
+        let padding_is_blank = |index: usize, width: usize, end: usize| {
+            index != padded_index
+                || width == storage_width
+                || (tail_start.div_ceil(width) * width..end)
+                    .step_by(width)
+                    .all(|row| bytes(row + storage_width, row + width).iter().all(blank))
+        };
+        // A width that divides the block exactly is preferred over one that leaves blank filler
+        // at its end, so that padded rows are not read at their unpadded width
+        let fit = |exact: bool| {
+            self.row_width_candidates
+                .iter()
+                .enumerate()
+                .find_map(|(index, &width)| {
+                    let end = observation_length - observation_length % width;
+                    let fits = if exact {
+                        end == observation_length
+                    } else {
+                        bytes(end, observation_length).iter().all(blank)
+                    };
+                    (fits && padding_is_blank(index, width, end)).then_some((index, width, end))
+                })
+        };
+        let resolved = fit(true).or_else(|| fit(false));
+        let observation_end = resolved.map_or(observation_length, |(_, _, end)| end);
+
+        // Members without observations are valid, so an empty block is not an error. When no
+        // candidate width fits, recovery keeps the first one and decodes the leftover bytes as
+        // a partial row.
+        let row_width = match resolved {
+            Some((_, width, _)) => width,
+            None if options.recover => self.row_width_candidates[0],
+            None => {
+                return Err(XptError::new(
+                    ErrorCode::ObservationWidth,
+                    Section::Observations,
+                    "Unable to determine observation width",
+                )
+                .at(self.obs_data_start));
+            }
+        };
+
+        if matches!(resolved, Some((index, _, _)) if index == padded_index)
+            && row_width > self.storage_width
+        {
+            self.dataset.warnings.push(format!(
+                "Rows are {} bytes long, {} more than the variables take up; the extra bytes are \
+                 ignored",
+                row_width,
+                row_width - self.storage_width
+            ));
+        }
+
+        // The last 80-byte record is padded with blanks, which reads as extra rows when the
+        // row width is shorter than a record
+        let mut observation_count = observation_end / row_width;
+        while observation_count > 0 {
+            let row_start = (observation_count - 1) * row_width;
+            let within_last_record = row_start + constants::RECORD_SIZE > observation_length;
+            if !within_last_record
+                || !bytes(row_start, row_start + row_width)
+                    .iter()
+                    .all(|&b| b == b' ')
+            {
+                break;
+            }
+            observation_count -= 1;
+        }
+
+        // Recovery keeps a final row cut short by truncation, leaving its absent cells missing
+        let partial_start = (observation_count * row_width).min(observation_end);
+        let partial_row = bytes(partial_start, observation_end);
+        let partial_row_length =
+            if options.recover && partial_row.iter().any(|&b| b != 0x00 && b != 0x20) {
+                self.dataset.warnings.push(format!(
+                    "Row {} is incomplete ({} of {} bytes); its remaining cells are left missing",
+                    observation_count + 1,
+                    partial_row.len(),
+                    row_width
+                ));
+                partial_row.len()
+            } else {
+                0
+            };
+
+        let layout = ObservationLayout {
+            start: self.obs_data_start,
+            row_width,
+            full_rows: observation_count,
+            partial_row_length,
+            cell_offsets: self.cell_offsets,
+        };
+        Ok((self.dataset, layout))
+    }
+}
+
+/// Members of a transport file read one at a time, see [`XPTParser::lazy_members`]
+///
+/// Each member is located from the end of the one before it: only its header records are read,
+/// and its observations scanned for the next MEMBER header, so the first member of a large file
+/// is available without walking the members after it.
+#[derive(Debug, Clone)]
+pub struct LazyMembers {
+    version: XportVersion,
+    library: XPTLibraryHeader,
+    options: ParseOptions,
+    suggested_filename: Option<String>,
+    /// File offset of the next MEMBER header, `None` once every member has been read or one
+    /// has failed to
+    next: Option<usize>,
+    /// Number of members read so far
+    read: usize,
+}
+
+impl LazyMembers {
+    /// Transport format revision of the file
+    pub fn version(&self) -> XportVersion {
+        self.version
+    }
+
+    /// Fields of the library header
+    pub fn library(&self) -> &XPTLibraryHeader {
+        &self.library
+    }
+
+    /// Whether members remain to be read
+    pub fn has_next(&self) -> bool {
+        self.next.is_some()
+    }
+
+    /// Reads the next member from `data`, which must be the bytes the members were found in
+    ///
+    /// Returns `None` once every member has been read. After an error no further member is
+    /// read, since where the next one starts is not known.
+    pub fn next_member(&mut self, data: &[u8]) -> Option<XptResult<LazyDataset>> {
+        let member = self.next_latin1(data)?;
+        Some(member.map(|mut lazy| {
+            // Text was read as Latin-1 so that it can be re-decoded once the encoding is known
+            let encoding = *self
+                .options
+                .encoding
+                .get_or_insert_with(|| TextEncoding::detect(lazy.sample(data).texts()));
+            lazy.dataset.apply_encoding(encoding);
+            lazy.dataset
+                .name_after_file(self.suggested_filename.as_deref());
+            lazy
+        }))
+    }
+
+    /// Reads the next member with its text still decoded as Latin-1 and a blank name left blank
+    fn next_latin1(&mut self, data: &[u8]) -> Option<XptResult<LazyDataset>> {
+        let start = self.next.take()?;
+        self.read += 1;
+        let context = MemberContext {
+            version: self.version,
+            options: self.options,
+            library: &self.library,
+        };
+
+        let header_end = header_records_end(data, start, self.version);
+        let header = match XPTParser::parse_member_header(&data[start..header_end], start, &context)
+        {
+            Ok(header) => header,
+            Err(error) => return Some(Err(error.in_member(self.read))),
+        };
+        let end = find_headers(data, header_end, data.len(), self.version.member_header()).next();
+        let observations = &data[header.obs_data_start..end.unwrap_or(data.len())];
+        let (dataset, layout) =
+            match header.into_layout(observations, observations.len(), self.options) {
+                Ok(layout) => layout,
+                Err(error) => return Some(Err(error.in_member(self.read))),
+            };
+        self.next = end;
+        Some(Ok(LazyDataset { dataset, layout }))
+    }
+}
+
+/// Version, library header and member byte ranges of a transport file
+struct Members {
+    version: XportVersion,
+    library: XPTLibraryHeader,
+    /// `start..end` of each member, from its MEMBER header to the next one or the end of file
+    ranges: Vec<(usize, usize)>,
+}
+
+/// Where the observations of a member are stored, so that any row can be decoded on its own
+#[derive(Debug, Clone)]
+pub(crate) struct ObservationLayout {
+    /// Byte offset of the first observation in the file
+    start: usize,
+    row_width: usize,
+    /// Number of complete rows
+    full_rows: usize,
+    /// Length of a trailing row cut short by truncation, kept in recovery mode, or 0
+    partial_row_length: usize,
+    /// Offset of each variable's value within a row
+    cell_offsets: Vec<usize>,
+}
+
+impl ObservationLayout {
+    fn row_count(&self) -> usize {
+        self.full_rows + usize::from(self.partial_row_length > 0)
+    }
+
+    /// Byte range of row `index` in the file
+    pub(crate) fn row_range(&self, index: usize) -> std::ops::Range<usize> {
+        let start = self.start + index * self.row_width;
+        let length = if index < self.full_rows {
+            self.row_width
+        } else {
+            self.partial_row_length
+        };
+        start..start + length
+    }
+}
+
+/// A member whose rows are decoded from the file bytes on demand, see [`XPTParser::parse_lazy`]
+#[derive(Debug, Clone)]
+pub struct LazyDataset {
+    /// Member metadata; `rows` stays empty
+    pub dataset: XPTDataset,
+    pub(crate) layout: ObservationLayout,
+}
+
+impl LazyDataset {
+    /// Number of rows in the member
+    pub fn row_count(&self) -> usize {
+        self.layout.row_count()
+    }
+
+    /// Decodes row `index` from `data`, which must be the bytes the member was parsed from
+    pub fn row(&self, data: &[u8], index: usize) -> Option<XPTRow> {
+        if index >= self.row_count() {
+            return None;
+        }
+        let mut row = self.decode(
+            &data[self.layout.row_range(index)],
+            &mut DiagnosticCounts::new(),
+        );
+        row.apply_encoding(self.dataset.encoding);
+        Some(row)
+    }
+
+    /// Metadata along with the first rows, still read as Latin-1, to detect the encoding from
+    fn sample(&self, data: &[u8]) -> XPTDataset {
+        let mut sample = self.dataset.clone();
+        let sample_rows = self.row_count().min(ENCODING_SAMPLE_ROWS);
+        sample.rows = (0..sample_rows)
+            .map(|index| {
+                let row_data = &data[self.layout.row_range(index)];
+                self.decode(row_data, &mut DiagnosticCounts::new())
+            })
+            .collect();
+        sample
+    }
+
+    /// Decodes the bytes of one row, leaving its text read as Latin-1
+    pub(crate) fn decode(
+        &self,
+        row_data: &[u8],
+        diagnostic_counts: &mut DiagnosticCounts,
+    ) -> XPTRow {
+        XPTRow {
+            values: XPTParser::parse_row(
+                row_data,
+                &self.dataset.variables,
+                &self.layout.cell_offsets,
+                diagnostic_counts,
+            ),
+        }
+    }
+}
+
+/// Represents a variable (column) in an XPT dataset
+#[derive(Debug, Clone)]
+pub struct XPTVariable {
+    pub name: String,
+    /// Variable label, empty when none was set
+    pub label: String,
+    pub var_type: VariableType,
+    pub length: usize,
+    /// Output format (nform, nfl, nfd)
+    pub format: VariableFormat,
+    /// Input format (niform, nifl, nifd)
+    pub informat: VariableFormat,
+    /// Justification of the formatted value (nfj)
+    pub justification: Justification,
+    /// Variable number within the dataset (nvar0)
+    pub number: u16,
+    /// Byte offset of the value within an observation (npos)
+    pub offset: usize,
+}
+
+/// Format or informat attached to a variable: a name, a width and a number of decimals
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VariableFormat {
+    /// Format name without width, e.g. `DATE`, `$CHAR`, or empty for plain `w.d`
+    pub name: String,
+    pub width: u16,
+    pub decimals: u16,
+}
+
+impl VariableFormat {
+    /// Whether no format was assigned
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty() && self.width == 0 && self.decimals == 0
+    }
+
+    /// Applies a full format description such as `DATE9.` or `$CHAR200.` from a LABELV9
+    /// record, keeping the namestr width and decimals when the text carries only a name
+    pub(crate) fn apply_description(&mut self, description: &str) {
+        let description = description.trim();
+        let name = description.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        self.name = name.to_string();
+
+        let specification = &description[name.len()..];
+        if let Some((width, decimals)) = specification.split_once('.') {
+            self.width = width.parse().unwrap_or(0);
+            self.decimals = decimals.parse().unwrap_or(0);
+        }
+    }
+}
+
+impl fmt::Display for VariableFormat {
+    /// Formats the specification the way SAS prints it, e.g. `DATE9.`, `8.2` or `$CHAR200.`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return Ok(());
+        }
+        write!(f, "{}", self.name)?;
+        if self.width > 0 {
+            write!(f, "{}", self.width)?;
+        }
+        write!(f, ".")?;
+        if self.decimals > 0 {
+            write!(f, "{}", self.decimals)?;
+        }
+        Ok(())
+    }
+}
+
+/// Justification of a formatted value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Justification {
+    Left,
+    Right,
+}
+
+/// Variable type (numeric or character)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VariableType {
+    Numeric,
+    Character,
+}
+
+/// One of the 28 SAS missing values for numeric variables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingValue {
+    /// The ordinary missing value, written as `.`
+    Standard,
+    /// The special missing value `._`
+    Underscore,
+    /// One of the special missing values `.A` through `.Z`
+    Letter(char),
+}
+
+impl MissingValue {
+    /// Decodes a numeric cell holding a missing value
+    ///
+    /// SAS stores missing values as the ASCII code of the missing value (`.`, `A`-`Z` or `_`)
+    /// in the first byte, followed by zero bytes.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let (&first, rest) = data.split_first()?;
+        if rest.iter().any(|&b| b != 0) {
+            return None;
+        }
+        match first {
+            b'.' => Some(MissingValue::Standard),
+            b'_' => Some(MissingValue::Underscore),
+            b'A'..=b'Z' => Some(MissingValue::Letter(first as char)),
+            _ => None,
+        }
+    }
+
+    /// Returns the SAS notation of the missing value, such as `.`, `.A` or `._`
+    pub fn code(&self) -> String {
+        match self {
+            MissingValue::Standard => ".".to_string(),
+            MissingValue::Underscore => "._".to_string(),
+            MissingValue::Letter(letter) => format!(".{}", letter),
+        }
+    }
+}
+
+/// A single decoded cell
+///
+/// Numeric cells keep the full double-precision value; formatting is left to the consumer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum XPTValue {
+    Character(String),
+    Numeric(f64),
+    Missing(MissingValue),
+}
+
+/// Represents a row of data
+#[derive(Debug, Clone)]
+pub struct XPTRow {
+    pub values: Vec<XPTValue>,
+}
+
+/// Fields of the library header records, shared by every member of a transport file
+#[derive(Debug, Clone, Default)]
+pub struct XPTLibraryHeader {
+    /// Symbol identifying the writer, `SAS` for files written by SAS
+    pub sas_symbol: String,
+    pub sas_version: String,
+    pub os_name: String,
+    pub created: Option<SasDateTime>,
+    pub modified: Option<SasDateTime>,
+}
+
+/// Timestamp stored in library and member headers as `ddMMMyy:hh:mm:ss`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SasDateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl SasDateTime {
+    const MONTHS: [&'static str; 12] = [
+        "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+    ];
+
+    /// Parses a header timestamp such as `16OCT26:10:11:12`
+    ///
+    /// Two-digit years from 60 to 99 are read as 19xx and the others as 20xx, since the
+    /// transport format postdates 1960.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (date, time) = text.trim().split_once(':')?;
+        if date.len() < 6 || !date.is_ascii() {
+            return None;
+        }
+        let (day, rest) = date.split_at(date.len() - 5);
+        let (month, year) = rest.split_at(3);
+
+        let month = Self::MONTHS
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(month))? as u8
+            + 1;
+        let day: u8 = day.trim().parse().ok()?;
+        let year: u16 = year.parse().ok()?;
+        let year = if year >= 60 { 1900 + year } else { 2000 + year };
+
+        let mut clock = time.split(':').map(|part| part.trim().parse::<u8>().ok());
+        let (hour, minute, second) = (clock.next()??, clock.next()??, clock.next()??);
+        if clock.next().is_some()
+            || !(1..=31).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 59
+        {
+            return None;
+        }
+
+        Some(SasDateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        })
+    }
+
+    /// Formats the timestamp the way headers store it, e.g. `16OCT26:10:11:12`
+    ///
+    /// Only the last two digits of the year are kept, so years outside 1960-2059 read back in
+    /// another century.
+    pub fn to_header_text(&self) -> String {
+        let month = Self::MONTHS
+            .get(usize::from(self.month).wrapping_sub(1))
+            .copied()
+            .unwrap_or("JAN");
+        format!(
+            "{:02}{}{:02}:{:02}:{:02}:{:02}",
+            self.day,
+            month,
+            self.year % 100,
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+}
+
+impl fmt::Display for SasDateTime {
+    /// Formats the timestamp as ISO 8601, e.g. `2026-10-16T10:11:12`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Transport format revision, which decides the header tags and the name string layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XportVersion {
+    /// Version 5: names of up to 8 characters and labels of up to 40
+    V5,
+    /// Version 8/9: names of up to 32 characters, with longer labels and format names in
+    /// LABELV8/LABELV9 records
+    V8,
+}
+
+impl XportVersion {
+    /// Detects the revision from the library header, falling back to the first member header
+    fn detect(data: &[u8]) -> Self {
+        let first_member = find_headers(data, 0, data.len(), constants::MEMBER_PREFIX).next();
+        Self::from_headers(data, first_member.map(|pos| &data[pos..]))
+    }
+
+    /// Picks the revision from the library header and the first member header record
+    pub(crate) fn from_headers(library_header: &[u8], first_member: Option<&[u8]>) -> Self {
+        if library_header.starts_with(constants::LIBRARY_V8_HEADER)
+            || first_member.is_some_and(|record| record.starts_with(constants::MEMBER_V8_HEADER))
+        {
+            XportVersion::V8
+        } else {
+            XportVersion::V5
+        }
+    }
+
+    pub(crate) fn library_header(self) -> &'static [u8] {
+        match self {
+            XportVersion::V5 => constants::LIBRARY_HEADER,
+            XportVersion::V8 => constants::LIBRARY_V8_HEADER,
+        }
+    }
+
+    pub(crate) fn member_header(self) -> &'static [u8] {
+        match self {
+            XportVersion::V5 => constants::MEMBER_HEADER,
+            XportVersion::V8 => constants::MEMBER_V8_HEADER,
+        }
+    }
+
+    pub(crate) fn descriptor_header(self) -> &'static [u8] {
+        match self {
+            XportVersion::V5 => constants::DESCRIPTOR_HEADER,
+            XportVersion::V8 => constants::DESCRIPTOR_V8_HEADER,
+        }
+    }
+
+    pub(crate) fn namestr_header(self) -> &'static [u8] {
+        match self {
+            XportVersion::V5 => constants::NAMESTR_HEADER,
+            XportVersion::V8 => constants::NAMESTR_V8_HEADER,
+        }
+    }
+
+    pub(crate) fn obs_header(self) -> &'static [u8] {
+        match self {
+            XportVersion::V5 => constants::OBS_HEADER,
+            XportVersion::V8 => constants::OBS_V8_HEADER,
+        }
+    }
+
+    /// Width of the dataset name field in the member descriptor record
+    pub(crate) fn dataset_name_length(self) -> usize {
+        match self {
+            XportVersion::V5 => 8,
+            XportVersion::V8 => 32,
+        }
+    }
+}
+
+/// Internal structure for parsing name string records
+struct NameStringRecord {
+    var_type: u16,
+    length: u16,
+    name: String,
+    label: String,
+    format: VariableFormat,
+    informat: VariableFormat,
+    justification: Justification,
+    position: u16,
+    /// Byte offset of the value within an observation (npos)
+    offset: usize,
+}
+
+/// Fields of the two member descriptor records that follow the MEMBER and DSCRPTR headers
+struct MemberDescriptor {
+    name: String,
+    sas_version: String,
+    os_name: String,
+    created: Option<SasDateTime>,
+    modified: Option<SasDateTime>,
+    label: String,
+    dataset_type: String,
+}
+
+/// Long name and label for one variable, read from a LABELV8/LABELV9 extension record
+struct LabelExtension {
+    var_number: u16,
+    name: String,
+    label: String,
+    format: Option<String>,
+    informat: Option<String>,
+}
+
+/// Rows per member decoded by [`XPTParser::parse_lazy`] and the streaming reader to detect the
+/// text encoding
+pub(crate) const ENCODING_SAMPLE_ROWS: usize = 1_000;
+
+/// Options controlling how strictly a transport file is read
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    /// Load as much of a damaged file as possible instead of failing: skip namestr records that
+    /// cannot be parsed, tolerate a missing OBS header, try alternative row widths and keep a
+    /// trailing partial row. Every repair is reported in the dataset warnings.
+    pub recover: bool,
+    /// Encoding of names, labels and character values, detected from the file when `None`
+    pub encoding: Option<TextEncoding>,
+}
+
+/// Parser for SAS XPORT Version 5 and Version 8/9 transport files
+pub struct XPTParser;
+
+impl XPTParser {
+    /// Parses every member of a SAS XPORT transport library
+    ///
+    /// The XPT format uses a fixed 80-byte record structure. After the library header, the file
+    /// holds one or more members, each made of:
+    /// 1. A MEMBER header followed by the member descriptor records
+    /// 2. A NAMESTR header and the variable metadata records (140 bytes each)
+    /// 3. Version 8/9 only: optional LABELV8/LABELV9 records for names and labels that do not
+    ///    fit in the namestr
+    /// 4. An OBS header and the observation data, running up to the next MEMBER header
+    ///
+    /// Version 8/9 files use the LIBV8, MEMBV8, NAMSTV8 and OBSV8 variants of these headers.
+    pub fn parse(data: &[u8], suggested_filename: Option<&str>) -> XptResult<Vec<XPTDataset>> {
+        Self::parse_with_options(data, suggested_filename, ParseOptions::default())
+    }
+
+    /// Parses every member of a SAS XPORT transport library using the given options
+    pub fn parse_with_options(
+        data: &[u8],
+        suggested_filename: Option<&str>,
+        options: ParseOptions,
+    ) -> XptResult<Vec<XPTDataset>> {
+        let Members {
+            version,
+            library,
+            ranges,
+        } = Self::locate_members(data)?;
+        let context = MemberContext {
+            version,
+            options,
+            library: &library,
+        };
+
+        let mut datasets = Vec::with_capacity(ranges.len());
+        for (index, &(member_start, member_end)) in ranges.iter().enumerate() {
+            let dataset = Self::parse_member(data, member_start, member_end, &context)
+                .map_err(|error| error.in_member(index + 1))?;
+            datasets.push(dataset);
+        }
+
+        // Text was read as Latin-1 so that it can be re-decoded once the encoding is known
+        let encoding = options
+            .encoding
+            .unwrap_or_else(|| TextEncoding::detect(datasets.iter().flat_map(XPTDataset::texts)));
+        for dataset in &mut datasets {
+            dataset.apply_encoding(encoding);
+            dataset.name_after_file(suggested_filename);
+        }
+
+        Ok(datasets)
+    }
+
+    /// Reads the metadata and observation layout of every member without decoding any row
+    ///
+    /// Rows are then decoded one at a time with [`LazyDataset::row`], so memory use does not
+    /// grow with the file. Per-value diagnostics are not collected, and unless an encoding is
+    /// given it is detected from the metadata and the first rows of each member.
+    pub fn parse_lazy(
+        data: &[u8],
+        suggested_filename: Option<&str>,
+        options: ParseOptions,
+    ) -> XptResult<Vec<LazyDataset>> {
+        // Members stay in Latin-1 until the encoding is detected from all of them
+        let mut members = Self::lazy_members(data, suggested_filename, options)?;
+        let mut datasets = Vec::new();
+        while let Some(lazy) = members.next_latin1(data) {
+            datasets.push(lazy?);
+        }
+
+        let encoding = options.encoding.unwrap_or_else(|| {
+            let samples: Vec<XPTDataset> = datasets.iter().map(|lazy| lazy.sample(data)).collect();
+            TextEncoding::detect(samples.iter().flat_map(XPTDataset::texts))
+        });
+        for lazy in &mut datasets {
+            lazy.dataset.apply_encoding(encoding);
+            lazy.dataset.name_after_file(suggested_filename);
+        }
+
+        Ok(datasets)
+    }
+
+    /// Reads the library header and finds the first member, leaving every member to be read
+    /// on demand with [`LazyMembers::next_member`]
+    ///
+    /// Unless an encoding is given, it is detected from the metadata and the first rows of the
+    /// first member and then used for every member.
+    pub fn lazy_members(
+        data: &[u8],
+        suggested_filename: Option<&str>,
+        options: ParseOptions,
+    ) -> XptResult<LazyMembers> {
+        if data.len() < constants::RECORD_SIZE {
+            return Err(XptError::new(
+                ErrorCode::FileTooSmall,
+                Section::LibraryHeader,
+                "File too small to be a valid XPT file",
+            )
+            .at(0));
+        }
+
+        let version = XportVersion::detect(data);
+        let first = find_headers(data, 0, data.len(), version.member_header())
+            .next()
+            .ok_or_else(|| {
+                XptError::new(
+                    ErrorCode::MissingMemberHeader,
+                    Section::LibraryHeader,
+                    "MEMBER header not found",
+                )
+            })?;
+        Ok(LazyMembers {
+            version,
+            library: Self::parse_library_header(data),
+            options,
+            suggested_filename: suggested_filename.map(str::to_string),
+            next: Some(first),
+            read: 0,
+        })
+    }
+
+    /// Finds the version, the library header and the byte range of every member
+    fn locate_members(data: &[u8]) -> XptResult<Members> {
+        if data.len() < constants::RECORD_SIZE {
+            return Err(XptError::new(
+                ErrorCode::FileTooSmall,
+                Section::LibraryHeader,
+                "File too small to be a valid XPT file",
+            )
+            .at(0));
+        }
+
+        let version = XportVersion::detect(data);
+        let library = Self::parse_library_header(data);
+        let member_positions: Vec<usize> =
+            find_headers(data, 0, data.len(), version.member_header()).collect();
+        if member_positions.is_empty() {
+            return Err(XptError::new(
+                ErrorCode::MissingMemberHeader,
+                Section::LibraryHeader,
+                "MEMBER header not found",
+            ));
+        }
+
+        let ranges = member_positions
+            .iter()
+            .enumerate()
+            .map(|(index, &member_start)| {
+                let member_end = member_positions
+                    .get(index + 1)
+                    .copied()
+                    .unwrap_or(data.len());
+                (member_start, member_end)
+            })
+            .collect();
+        Ok(Members {
+            version,
+            library,
+            ranges,
+        })
+    }
+
+    /// Parses a single member spanning `member_start..member_end`, decoding all of its rows
+    fn parse_member(
+        data: &[u8],
+        member_start: usize,
+        member_end: usize,
+        context: &MemberContext,
+    ) -> XptResult<XPTDataset> {
+        let (mut dataset, layout) =
+            Self::parse_member_layout(data, member_start, member_end, context)?;
+
+        let mut diagnostic_counts = DiagnosticCounts::new();
+        dataset.rows = (0..layout.row_count())
+            .map(|index| XPTRow {
+                values: Self::parse_row(
+                    &data[layout.row_range(index)],
+                    &dataset.variables,
+                    &layout.cell_offsets,
+                    &mut diagnostic_counts,
+                ),
+            })
+            .collect();
+
+        let warnings = diagnostic_warnings(&dataset.variables, &diagnostic_counts);
+        dataset.warnings.extend(warnings);
+
+        Ok(dataset)
+    }
+
+    /// Reads the metadata of a single member spanning `member_start..member_end` and works out
+    /// where its observations are, leaving `rows` empty
+    fn parse_member_layout(
+        data: &[u8],
+        member_start: usize,
+        member_end: usize,
+        context: &MemberContext,
+    ) -> XptResult<(XPTDataset, ObservationLayout)> {
+        let member = &data[member_start..member_end];
+        let header = Self::parse_member_header(member, member_start, context)?;
+        let observations = &data[header.obs_data_start..member_end];
+        header.into_layout(observations, observations.len(), context.options)
+    }
+
+    /// Reads the header records of the member held in `member`, which starts at file offset
+    /// `base`; only the bytes up to the end of its OBS header record are needed
+    pub(crate) fn parse_member_header(
+        member: &[u8],
+        base: usize,
+        context: &MemberContext,
+    ) -> XptResult<MemberHeader> {
+        let name_string_length = Self::name_string_length(member);
+        let mut warnings = Vec::new();
+
+        // Locate the two critical header sections within this member
+        let namestr_header_pos =
+            find_headers(member, 0, member.len(), context.version.namestr_header())
+                .next()
+                .ok_or_else(|| {
+                    XptError::new(
+                        ErrorCode::MissingNamestrHeader,
+                        Section::MemberHeader,
+                        "NAMESTR header not found",
+                    )
+                    .at(base)
+                })?;
+        let obs_header_pos = match find_headers(
+            member,
+            namestr_header_pos,
+            member.len(),
+            context.version.obs_header(),
+        )
+        .next()
+        {
+            Some(pos) => pos,
+            // A file truncated before its observations still has usable metadata
+            None if context.options.recover => {
+                warnings.push(
+                    "OBS header not found; the member is shown without observations".to_string(),
+                );
+                member.len()
+            }
+            None => {
+                return Err(XptError::new(
+                    ErrorCode::MissingObsHeader,
+                    Section::Namestr,
+                    "OBS header not found",
+                )
+                .at(base + namestr_header_pos));
+            }
+        };
+
+        // Version 8/9 members may carry long labels between the namestr block and the OBS header
+        let label_header_pos = match context.version {
+            XportVersion::V5 => None,
+            XportVersion::V8 => (namestr_header_pos..obs_header_pos)
+                .step_by(constants::RECORD_SIZE)
+                .find(|&pos| {
+                    member[pos..].starts_with(constants::LABEL_V8_HEADER)
+                        || member[pos..].starts_with(constants::LABEL_V9_HEADER)
+                }),
+        };
+
+        // Extract the variable metadata block between headers
+        let name_str_block_start = namestr_header_pos + constants::RECORD_SIZE;
+        let name_str_block_end = label_header_pos.unwrap_or(obs_header_pos);
+
+        if name_str_block_end <= name_str_block_start {
+            return Err(XptError::new(
+                ErrorCode::InvalidHeaderLayout,
+                Section::Namestr,
+                "Invalid header positions",
+            )
+            .at(base + namestr_header_pos));
+        }
+
+        let name_string_block = &member[name_str_block_start..name_str_block_end];
+
+        if name_string_block.len() < name_string_length {
+            return Err(XptError::new(
+                ErrorCode::NamestrBlockTooSmall,
+                Section::Namestr,
+                "Name string block too small",
+            )
+            .at(base + name_str_block_start));
+        }
+
+        // Each variable metadata record has a fixed length; trailing bytes are record padding
+        let record_count = name_string_block.len() / name_string_length;
+        if record_count == 0 {
+            return Err(XptError::new(
+                ErrorCode::NoVariables,
+                Section::Namestr,
+                "The file does not include variable metadata",
+            )
+            .at(base + name_str_block_start));
+        }
+
+        // Parse all variable metadata records
+        let mut name_records = Vec::with_capacity(record_count);
+        let mut skipped_records = 0;
+        for (index, chunk) in name_string_block
+            .chunks_exact(name_string_length)
+            .enumerate()
+        {
+            let record_start = base + name_str_block_start + index * name_string_length;
+            match Self::parse_name_string(chunk, context.version) {
+                Ok(record) => name_records.push(record),
+                Err(reason) if context.options.recover => {
+                    warnings.push(format!(
+                        "Skipped namestr record {} at byte {}: {}",
+                        index + 1,
+                        record_start,
+                        reason
+                    ));
+                    skipped_records += 1;
+                }
+                Err(reason) => {
+                    return Err(XptError::new(
+                        ErrorCode::InvalidNamestr,
+                        Section::Namestr,
+                        format!("Invalid namestr record {}: {}", index + 1, reason),
+                    )
+                    .at(record_start));
+                }
+            }
+        }
+
+        if let Some(label_header_pos) = label_header_pos {
+            let extensions =
+                Self::parse_label_extensions(&member[label_header_pos..obs_header_pos]);
+            for extension in extensions {
+                let target = name_records
+                    .iter_mut()
+                    .find(|record| record.position == extension.var_number);
+                if let Some(record) = target {
+                    if !extension.name.is_empty() {
+                        record.name = extension.name;
+                    }
+                    record.label = extension.label;
+                    if let Some(format) = extension.format.filter(|text| !text.is_empty()) {
+                        record.format.apply_description(&format);
+                    }
+                    if let Some(informat) = extension.informat.filter(|text| !text.is_empty()) {
+                        record.informat.apply_description(&informat);
+                    }
+                }
+            }
+        }
+
+        if name_records.is_empty() {
+            return Err(XptError::new(
+                ErrorCode::NoVariables,
+                Section::Namestr,
+                "Variable descriptors could not be parsed",
+            )
+            .at(base + name_str_block_start));
+        }
+
+        let descriptor = Self::parse_member_descriptor(member, context.version);
+
+        // Sort variables by their position field, using index as fallback if position is 0
+        let mut ordered_records: Vec<(usize, NameStringRecord)> =
+            name_records.into_iter().enumerate().collect();
+        ordered_records.sort_by(|(lhs_idx, lhs), (rhs_idx, rhs)| {
+            let lhs_order = if lhs.position > 0 {
+                lhs.position as usize
+            } else {
+                lhs_idx + 1
+            };
+            let rhs_order = if rhs.position > 0 {
+                rhs.position as usize
+            } else {
+                rhs_idx + 1
+            };
+            lhs_order.cmp(&rhs_order).then_with(|| lhs_idx.cmp(rhs_idx))
+        });
+
+        let npos_offsets: Vec<usize> = ordered_records
+            .iter()
+            .map(|(_, record)| record.offset)
+            .collect();
+
+        let variables: Vec<XPTVariable> = ordered_records
+            .into_iter()
+            .enumerate()
+            .map(|(index, (_, record))| {
+                let base_name = if record.name.is_empty() {
+                    format!("VAR{}", index + 1)
+                } else {
+                    record.name
+                };
+                let var_type = if record.var_type == 1 {
+                    VariableType::Numeric
+                } else {
+                    VariableType::Character
+                };
+                let length = if var_type == VariableType::Numeric {
+                    record.length.max(constants::MIN_NUMERIC_LENGTH as u16) as usize
+                } else {
+                    record.length.max(constants::MIN_CHARACTER_LENGTH as u16) as usize
+                };
+
+                XPTVariable {
+                    name: base_name,
+                    label: record.label,
+                    var_type,
+                    length,
+                    format: record.format,
+                    informat: record.informat,
+                    justification: record.justification,
+                    number: record.position,
+                    offset: record.offset,
+                }
+            })
+            .collect();
+
+        // Observation data runs from the OBS header up to the end of the member
+        let obs_data_start = base + (obs_header_pos + constants::RECORD_SIZE).min(member.len());
+
+        // Calculate the total storage width needed for all variables
+        let storage_width: usize = variables.iter().map(|v| v.length).sum();
+        if storage_width == 0 {
+            return Err(XptError::new(
+                ErrorCode::ZeroRowWidth,
+                Section::Namestr,
+                "Variables have zero length",
+            )
+            .at(base + name_str_block_start));
+        }
+
+        // Values are packed back to back, so each starts where the previous one ends. When
+        // recovery skipped namestr records, their values leave gaps that only the npos offsets
+        // of the remaining records account for.
+        let packed_offsets: Vec<usize> = variables
+            .iter()
+            .scan(0, |offset, variable| {
+                let start = *offset;
+                *offset += variable.length;
+                Some(start)
+            })
+            .collect();
+        let npos_width = npos_offsets
+            .iter()
+            .zip(&variables)
+            .map(|(offset, variable)| offset + variable.length)
+            .max()
+            .unwrap_or(0);
+        let cell_offsets = if skipped_records > 0 && npos_width >= storage_width {
+            npos_offsets
+        } else {
+            packed_offsets
+        };
+
+        // XPT format may pad rows to 8-byte boundaries for alignment
+        let mut row_width_candidates = vec![
+            storage_width,
+            ((storage_width as f64 / 8.0).ceil() as usize) * 8,
+        ];
+        if context.options.recover && npos_width > storage_width {
+            row_width_candidates.insert(0, npos_width);
+        }
+
+        Ok(MemberHeader {
+            dataset: XPTDataset {
+                title: descriptor.name,
+                label: descriptor.label,
+                dataset_type: descriptor.dataset_type,
+                sas_version: descriptor.sas_version,
+                os_name: descriptor.os_name,
+                created_date: descriptor.created,
+                modified_date: descriptor.modified,
+                library: context.library.clone(),
+                encoding: TextEncoding::Latin1,
+                variables,
+                rows: Vec::new(),
+                warnings,
+            },
+            obs_data_start,
+            cell_offsets,
+            row_width_candidates,
+            storage_width,
+        })
+    }
+
+    /// Reads the name string record length announced in the MEMBER header (140, or 136 on VMS)
+    fn name_string_length(member: &[u8]) -> usize {
+        let announced = member
+            .get(74..78)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| digits.trim().parse::<usize>().ok());
+        match announced {
+            Some(constants::VMS_NAME_STRING_RECORD_LENGTH) => {
+                constants::VMS_NAME_STRING_RECORD_LENGTH
+            }
+            _ => constants::NAME_STRING_RECORD_LENGTH,
+        }
+    }
+
+    /// Decodes the cells of one observation at the given offsets
+    ///
+    /// Cells that do not fit in `row_data` (only possible for a truncated final row) come back
+    /// as missing numerics or as the characters that are left.
+    fn parse_row(
+        row_data: &[u8],
+        variables: &[XPTVariable],
+        cell_offsets: &[usize],
+        diagnostic_counts: &mut DiagnosticCounts,
+    ) -> Vec<XPTValue> {
+        variables
+            .iter()
+            .zip(cell_offsets)
+            .enumerate()
+            .map(|(var_index, (variable, &offset))| {
+                let Some(cell_data) = row_data.get(offset..offset + variable.length) else {
+                    // Keep whatever text survived; a cut-off number cannot be decoded
+                    return match variable.var_type {
+                        VariableType::Character => XPTValue::Character(text_trimmed(
+                            row_data.get(offset..).unwrap_or_default(),
+                        )),
+                        VariableType::Numeric => XPTValue::Missing(MissingValue::Standard),
+                    };
+                };
+                let (value, diagnostic) = Self::parse_cell(cell_data, variable);
+                if let Some(diagnostic) = diagnostic {
+                    *diagnostic_counts
+                        .entry((var_index, diagnostic))
+                        .or_default() += 1;
+                }
+                value
+            })
+            .collect()
+    }
+
+    /// Parses a name string record (140 bytes, or 136 bytes on VMS)
+    ///
+    /// Version 8/9 records keep the full variable name (up to 32 characters) at offset 88; the
+    /// 8-byte name field then only holds a truncated copy.
+    fn parse_name_string(data: &[u8], version: XportVersion) -> Result<NameStringRecord, String> {
+        if data.len() < constants::VMS_NAME_STRING_RECORD_LENGTH {
+            return Err(format!("record is only {} bytes long", data.len()));
+        }
+
+        let read_u16 = |offset: usize| u16::from_be_bytes([data[offset], data[offset + 1]]);
+        let var_type = read_u16(0);
+        let length = read_u16(4);
+        let position = read_u16(6);
+        let offset = u32::from_be_bytes([data[84], data[85], data[86], data[87]]) as usize;
+        if var_type != 1 && var_type != 2 {
+            return Err(format!("unknown variable type {}", var_type));
+        }
+        if length == 0 {
+            return Err("variable length is zero".to_string());
+        }
+
+        let short_name = text_field(data, 8, 8);
+        let label = text_field(data, 16, 40);
+        let format = VariableFormat {
+            name: text_field(data, 56, 8),
+            width: read_u16(64),
+            decimals: read_u16(66),
+        };
+        let justification = if read_u16(68) == 1 {
+            Justification::Right
+        } else {
+            Justification::Left
+        };
+        let informat = VariableFormat {
+            name: text_field(data, 72, 8),
+            width: read_u16(80),
+            decimals: read_u16(82),
+        };
+
+        let long_name = match version {
+            XportVersion::V5 => String::new(),
+            XportVersion::V8 => text_field(data, 88, 32),
+        };
+        let name = if long_name.trim().is_empty() {
+            short_name
+        } else {
+            long_name
+        };
+
+        Ok(NameStringRecord {
+            var_type,
+            length,
+            name,
+            label,
+            format,
+            informat,
+            justification,
+            position,
+            offset,
+        })
+    }
+
+    /// Parses the LABELV8/LABELV9 section: a header record announcing the entry count, then
+    /// variable-length entries packed back to back and padded to a record boundary
+    ///
+    /// LABELV8 entries hold the variable number, name length and label length (2 bytes each)
+    /// followed by the name and label. LABELV9 entries add format and informat lengths and text.
+    fn parse_label_extensions(section: &[u8]) -> Vec<LabelExtension> {
+        let with_formats = section.starts_with(constants::LABEL_V9_HEADER);
+        let expected = section
+            .get(48..constants::RECORD_SIZE)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| digits.trim().parse::<usize>().ok())
+            .unwrap_or(usize::MAX);
+        let prefix_length = if with_formats { 10 } else { 6 };
+
+        let read_u16 = |offset: usize| u16::from_be_bytes([section[offset], section[offset + 1]]);
+
+        let mut extensions = Vec::new();
+        let mut offset = constants::RECORD_SIZE;
+        while extensions.len() < expected && offset + prefix_length <= section.len() {
+            let var_number = read_u16(offset);
+            let name_length = read_u16(offset + 2) as usize;
+            let label_length = read_u16(offset + 4) as usize;
+            let (format_length, informat_length) = if with_formats {
+                (read_u16(offset + 6) as usize, read_u16(offset + 8) as usize)
+            } else {
+                (0, 0)
+            };
+
+            // Zero or blank bytes here are the padding of the last record
+            if var_number == 0 || var_number == 0x2020 {
+                break;
+            }
+
+            let text_start = offset + prefix_length;
+            let text_end =
+                text_start + name_length + label_length + format_length + informat_length;
+            if text_end > section.len() {
+                break;
+            }
+
+            let name = text_field(section, text_start, name_length);
+            let label = text_field(section, text_start + name_length, label_length);
+            let (format, informat) = if with_formats {
+                let format_start = text_start + name_length + label_length;
+                (
+                    Some(text_field(section, format_start, format_length)),
+                    Some(text_field(
+                        section,
+                        format_start + format_length,
+                        informat_length,
+                    )),
+                )
+            } else {
+                (None, None)
+            };
+
+            extensions.push(LabelExtension {
+                var_number,
+                name,
+                label,
+                format,
+                informat,
+            });
+            offset = text_end;
+        }
+
+        extensions
+    }
+
+    /// Parses a cell value based on variable type, along with any conversion diagnostic
+    fn parse_cell(data: &[u8], variable: &XPTVariable) -> (XPTValue, Option<Diagnostic>) {
+        match variable.var_type {
+            VariableType::Character => (XPTValue::Character(text_trimmed(data)), None),
+            VariableType::Numeric => match MissingValue::decode(data) {
+                Some(missing) => (XPTValue::Missing(missing), None),
+                None => {
+                    let converted = ibm_float::ibm_to_ieee(Self::widen_numeric(data));
+                    (XPTValue::Numeric(converted.value), converted.diagnostic)
+                }
+            },
+        }
+    }
+
+    /// Widens a stored numeric to the full 8-byte IBM double
+    ///
+    /// Variables stored with a LENGTH below 8 keep only the leading bytes of the float, so the
+    /// missing low-order fraction bytes are restored as zeros.
+    fn widen_numeric(data: &[u8]) -> [u8; 8] {
+        let mut bytes = [0u8; constants::FULL_NUMERIC_LENGTH];
+        let stored = data.len().min(constants::FULL_NUMERIC_LENGTH);
+        bytes[..stored].copy_from_slice(&data[..stored]);
+        bytes
+    }
+
+    /// Reads the library header records that open the file
+    ///
+    /// The second record holds the SAS symbol, `SASLIB`, the SAS version, the OS name and the
+    /// creation timestamp; the third record holds the modification timestamp.
+    pub(crate) fn parse_library_header(data: &[u8]) -> XPTLibraryHeader {
+        let record = constants::RECORD_SIZE;
+        XPTLibraryHeader {
+            sas_symbol: text_field(data, record, 8),
+            sas_version: text_field(data, record + 24, 8),
+            os_name: text_field(data, record + 32, 8),
+            created: SasDateTime::parse(&text_field(data, record + 64, 16)),
+            modified: SasDateTime::parse(&text_field(data, record * 2, 16)),
+        }
+    }
+
+    /// Reads the member descriptor records that follow the MEMBER and DSCRPTR headers
+    ///
+    /// The first record holds the SAS symbol, the dataset name (8 characters, or 32 in Version
+    /// 8/9), `SASDATA`, the SAS version, the OS name and the creation timestamp. The second
+    /// holds the modification timestamp, the dataset label and the dataset type.
+    fn parse_member_descriptor(member: &[u8], version: XportVersion) -> MemberDescriptor {
+        let first = constants::RECORD_SIZE * 2;
+        let second = constants::RECORD_SIZE * 3;
+        let name_length = version.dataset_name_length();
+        MemberDescriptor {
+            name: text_field(member, first + 8, name_length),
+            sas_version: text_field(member, first + 16 + name_length, 8),
+            os_name: text_field(member, first + 24 + name_length, 8),
+            created: SasDateTime::parse(&text_field(member, first + 64, 16)),
+            modified: SasDateTime::parse(&text_field(member, second, 16)),
+            label: text_field(member, second + 32, 40),
+            dataset_type: text_field(member, second + 72, 8),
+        }
+    }
+}
+
+/// End of the header records of the member whose MEMBER header is at `start`: past its OBS
+/// header record, or at the next MEMBER header or the end of file when it has none
+fn header_records_end(data: &[u8], start: usize, version: XportVersion) -> usize {
+    let mut seen_namestr = false;
+    for pos in (start..data.len()).step_by(constants::RECORD_SIZE) {
+        let record = &data[pos..];
+        if pos > start && record.starts_with(version.member_header()) {
+            return pos;
+        }
+        if record.starts_with(version.namestr_header()) {
+            seen_namestr = true;
+        } else if seen_namestr && record.starts_with(version.obs_header()) {
+            return (pos + constants::RECORD_SIZE).min(data.len());
+        }
+    }
+    data.len()
+}
+
+/// Finds every header record starting with `tag` on an 80-byte boundary within `start..end`
+fn find_headers<'a>(
+    data: &'a [u8],
+    start: usize,
+    end: usize,
+    tag: &'a [u8],
+) -> impl Iterator<Item = usize> + 'a {
+    (start..end)
+        .step_by(constants::RECORD_SIZE)
+        .filter(move |&pos| data[pos..end].starts_with(tag))
+}
+
+/// Number of values decoded with each conversion diagnostic, by variable index
+pub(crate) type DiagnosticCounts = BTreeMap<(usize, Diagnostic), usize>;
+
+/// Describes the diagnostic counts of a member as dataset warnings
+pub(crate) fn diagnostic_warnings(
+    variables: &[XPTVariable],
+    diagnostic_counts: &DiagnosticCounts,
+) -> Vec<String> {
+    diagnostic_counts
+        .iter()
+        .map(|(&(var_index, diagnostic), count)| {
+            format!(
+                "{}: {} value(s) decoded from an {}",
+                variables[var_index].name,
+                count,
+                diagnostic.describe()
+            )
+        })
+        .collect()
+}
+
+/// Extracts a text field from data at a specific offset and length
+fn text_field(data: &[u8], offset: usize, length: usize) -> String {
+    if offset >= data.len() || offset + length > data.len() {
+        return String::new();
+    }
+    text_trimmed(&data[offset..offset + length])
+}
+
+/// Decodes a text field as Latin-1 and trims the trailing blanks and NULs that pad it
+///
+/// Only ASCII padding is trimmed, since bytes such as 0xA0 may be part of a UTF-8 sequence. The
+/// file encoding is applied once the whole file has been read.
+pub(crate) fn text_trimmed(data: &[u8]) -> String {
+    let end = data
+        .iter()
+        .rposition(|&byte| !byte.is_ascii_whitespace() && byte != 0)
+        .map_or(0, |last| last + 1);
+    latin1_string(&data[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xpt_writer::XPTWriter;
+
+    /// V5 library with three members holding different variables, written by an independent
+    /// implementation of the TS-140 record layout. The five 16-byte DM rows end exactly on a
+    /// record boundary, the three 24-byte VS rows leave 8 blanks before the next MEMBER header
+    /// and the three 20-byte AE rows leave a whole row of blanks at the end of the file.
+    const MULTIPLE_MEMBERS: &[u8] = include_bytes!("../tests/fixtures/multiple_members.xpt");
+
+    /// V8 library written by the same independent implementation, with a 20-character member
+    /// name, variable names longer than 8 characters and a 62-character label that only the
+    /// LABELV8 section holds in full
+    const LONG_NAMES_V8: &[u8] = include_bytes!("../tests/fixtures/long_names_v8.xpt");
+
+    /// One numeric variable holding `.`, then `.A` through `.Z`, then `._`, next to a character
+    /// variable spelling out each code
+    const SPECIAL_MISSING: &[u8] = include_bytes!("../tests/fixtures/special_missing.xpt");
+
+    /// V5 library with one member holding 3-, 4-, 6- and 8-byte numerics around a character
+    /// column, so any misaligned numeric shifts the columns that follow it
+    const TRUNCATED_NUMERICS: &[u8] = include_bytes!("../tests/fixtures/truncated_numerics.xpt");
+
+    fn character(text: &str) -> XPTValue {
+        XPTValue::Character(text.to_string())
+    }
+
+    fn names(dataset: &XPTDataset) -> Vec<&str> {
+        dataset.variables.iter().map(|v| v.name.as_str()).collect()
+    }
+
+    /// Member of `data` read in recovery mode
+    fn recover(data: &[u8]) -> XPTDataset {
+        let options = ParseOptions {
+            recover: true,
+            ..ParseOptions::default()
+        };
+        XPTParser::parse_with_options(data, None, options)
+            .unwrap()
+            .remove(0)
+    }
+
+    /// Rows of the undamaged TRUNCATED_NUMERICS member
+    fn intact_rows() -> Vec<Vec<XPTValue>> {
+        XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap()[0]
+            .rows
+            .iter()
+            .map(|row| row.values.clone())
+            .collect()
+    }
+
+    fn values(dataset: &XPTDataset) -> Vec<Vec<XPTValue>> {
+        dataset.rows.iter().map(|row| row.values.clone()).collect()
+    }
+
+    #[test]
+    fn parses_every_member_of_the_library() {
+        let datasets = XPTParser::parse(MULTIPLE_MEMBERS, None).unwrap();
+        let titles: Vec<&str> = datasets.iter().map(|d| d.title.as_str()).collect();
+        assert_eq!(titles, vec!["DM", "VS", "AE"]);
+
+        assert_eq!(names(&datasets[0]), vec!["USUBJID", "AGE"]);
+        assert_eq!(names(&datasets[1]), vec!["USUBJID", "VSTESTCD", "VSSTRESN"]);
+        assert_eq!(names(&datasets[2]), vec!["AESEQ", "AETERM"]);
+
+        let row_counts: Vec<usize> = datasets.iter().map(|d| d.rows.len()).collect();
+        assert_eq!(row_counts, vec![5, 3, 3]);
+
+        assert_eq!(
+            datasets[0].rows[4].values,
+            vec![character("01-005"), XPTValue::Numeric(62.0)]
+        );
+        assert_eq!(
+            datasets[1].rows[0].values,
+            vec![
+                character("01-001"),
+                character("SYSBP"),
+                XPTValue::Numeric(120.0)
+            ]
+        );
+        assert_eq!(
+            datasets[1].rows[2].values,
+            vec![
+                character("01-002"),
+                character("SYSBP"),
+                XPTValue::Numeric(135.5)
+            ]
+        );
+        assert_eq!(
+            datasets[2].rows[2].values,
+            vec![XPTValue::Numeric(3.0), character("FATIGUE")]
+        );
+    }
+
+    #[test]
+    fn reads_long_names_and_labels_of_version_8_files() {
+        let datasets = XPTParser::parse(LONG_NAMES_V8, None).unwrap();
+        assert_eq!(datasets.len(), 1);
+
+        let dataset = &datasets[0];
+        assert_eq!(dataset.title, "VITAL_SIGNS_BASELINE");
+        assert_eq!(
+            names(dataset),
+            vec!["SUBJECT_IDENTIFIER", "VISIT_NUMBER", "SYSTOLIC_BP"]
+        );
+        let labels: Vec<&str> = dataset.variables.iter().map(|v| v.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "Unique subject identifier",
+                "Number of the planned visit at which the measurement was taken",
+                "Systolic blood pressure (mmHg)",
+            ]
+        );
+        assert_eq!(dataset.variables[0].length, 10);
+
+        assert_eq!(dataset.rows.len(), 2);
+        assert_eq!(
+            dataset.rows[1].values,
+            vec![
+                character("STUDY1-002"),
+                XPTValue::Numeric(2.0),
+                XPTValue::Numeric(118.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_every_special_missing_value() {
+        let datasets = XPTParser::parse(SPECIAL_MISSING, None).unwrap();
+        let expected: Vec<MissingValue> = std::iter::once(MissingValue::Standard)
+            .chain((b'A'..=b'Z').map(|letter| MissingValue::Letter(letter as char)))
+            .chain(std::iter::once(MissingValue::Underscore))
+            .collect();
+
+        let rows = &datasets[0].rows;
+        assert_eq!(rows.len(), expected.len());
+        for (row, missing) in rows.iter().zip(expected) {
+            assert_eq!(row.values[0], character(&missing.code()));
+            assert_eq!(row.values[1], XPTValue::Missing(missing));
+        }
+    }
+
+    #[test]
+    fn decodes_truncated_numeric_variables() {
+        let datasets = XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap();
+        assert_eq!(datasets.len(), 1);
+
+        let dataset = &datasets[0];
+        let lengths: Vec<usize> = dataset.variables.iter().map(|v| v.length).collect();
+        assert_eq!(lengths, vec![3, 4, 6, 4, 8]);
+
+        let rows: Vec<&Vec<XPTValue>> = dataset.rows.iter().map(|row| &row.values).collect();
+        assert_eq!(
+            rows,
+            vec![
+                &vec![
+                    XPTValue::Numeric(1.0),
+                    XPTValue::Numeric(12345.0),
+                    XPTValue::Numeric(123456789.0),
+                    XPTValue::Character("ABCD".to_string()),
+                    XPTValue::Numeric(3.25),
+                ],
+                &vec![
+                    XPTValue::Numeric(-2.5),
+                    XPTValue::Numeric(0.5),
+                    XPTValue::Numeric(-0.125),
+                    XPTValue::Character("EFGH".to_string()),
+                    XPTValue::Numeric(100.0),
+                ],
+                &vec![
+                    XPTValue::Missing(MissingValue::Letter('A')),
+                    XPTValue::Missing(MissingValue::Standard),
+                    XPTValue::Numeric(7.0),
+                    XPTValue::Character("IJKL".to_string()),
+                    XPTValue::Missing(MissingValue::Standard),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_library_and_member_headers() {
+        let datasets = XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap();
+        let dataset = &datasets[0];
+        let timestamp = SasDateTime::parse("16OCT26:10:11:12");
+
+        assert_eq!(dataset.title, "TRUNC");
+        assert_eq!(dataset.label, "Truncated numerics");
+        assert_eq!(dataset.dataset_type, "DATA");
+        assert_eq!(dataset.sas_version, "9.4");
+        assert_eq!(dataset.os_name, "Linux");
+        assert_eq!(dataset.created_date, timestamp);
+        assert_eq!(dataset.modified_date, timestamp);
+        assert_eq!(dataset.library.sas_symbol, "SAS");
+        assert_eq!(dataset.library.sas_version, "9.4");
+        assert_eq!(dataset.library.created, timestamp);
+        assert_eq!(
+            timestamp.map(|t| t.to_string()).as_deref(),
+            Some("2026-10-16T10:11:12")
+        );
+    }
+
+    #[test]
+    fn formats_variable_specifications() {
+        let format = |name: &str, width, decimals| VariableFormat {
+            name: name.to_string(),
+            width,
+            decimals,
+        };
+        assert_eq!(format("DATE", 9, 0).to_string(), "DATE9.");
+        assert_eq!(format("", 8, 2).to_string(), "8.2");
+        assert_eq!(format("$CHAR", 200, 0).to_string(), "$CHAR200.");
+        assert_eq!(format("BEST", 0, 0).to_string(), "BEST.");
+        assert_eq!(format("", 0, 0).to_string(), "");
+
+        let mut extended = format("YYMMDD", 8, 0);
+        extended.apply_description("E8601DA10.");
+        assert_eq!(extended, format("E8601DA", 10, 0));
+        extended.apply_description("IS8601DA");
+        assert_eq!(extended, format("IS8601DA", 10, 0));
+    }
+
+    #[test]
+    fn reads_members_one_at_a_time() {
+        let first = XPTParser::parse(TRUNCATED_NUMERICS, None)
+            .unwrap()
+            .remove(0);
+        let mut second = first.clone();
+        second.title = "SECOND".to_string();
+        second.rows.truncate(1);
+        let datasets = [first.clone(), second.clone()];
+        let file = XPTWriter::to_bytes(&first.library, &datasets, XportVersion::V5).unwrap();
+
+        let mut members = XPTParser::lazy_members(&file, None, ParseOptions::default()).unwrap();
+        assert_eq!(members.library().sas_version, "9.4");
+        let lazy = members.next_member(&file).unwrap().unwrap();
+        assert_eq!(lazy.dataset.title, "TRUNC");
+        assert_eq!(lazy.row_count(), first.rows.len());
+        assert!(members.has_next());
+
+        let lazy = members.next_member(&file).unwrap().unwrap();
+        assert_eq!(lazy.dataset.title, "SECOND");
+        assert_eq!(lazy.row_count(), 1);
+        assert_eq!(lazy.row(&file, 0).unwrap().values, second.rows[0].values);
+        assert!(!members.has_next());
+        assert!(members.next_member(&file).is_none());
+    }
+
+    #[test]
+    fn lazy_rows_match_eager_rows() {
+        let eager = XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap();
+        let lazy =
+            XPTParser::parse_lazy(TRUNCATED_NUMERICS, None, ParseOptions::default()).unwrap();
+        assert_eq!(lazy.len(), eager.len());
+
+        for (lazy, eager) in lazy.iter().zip(&eager) {
+            assert!(lazy.dataset.rows.is_empty());
+            assert_eq!(lazy.row_count(), eager.rows.len());
+            for (index, row) in eager.rows.iter().enumerate() {
+                let decoded = lazy.row(TRUNCATED_NUMERICS, index).unwrap();
+                assert_eq!(decoded.values, row.values);
+            }
+            assert!(lazy.row(TRUNCATED_NUMERICS, lazy.row_count()).is_none());
+        }
+    }
+
+    #[test]
+    fn recovery_skips_a_corrupt_namestr_record() {
+        // Variable type 9 in the second namestr record, which describes N4 at byte 780
+        let mut file = TRUNCATED_NUMERICS.to_vec();
+        file[780..782].copy_from_slice(&[0, 9]);
+        let dataset = recover(&file);
+
+        assert_eq!(names(&dataset), vec!["N3", "N6", "CHR", "N8"]);
+        let expected: Vec<Vec<XPTValue>> = intact_rows()
+            .into_iter()
+            .map(|mut row| {
+                row.remove(1);
+                row
+            })
+            .collect();
+        assert_eq!(values(&dataset), expected);
+        assert_eq!(
+            dataset.warnings,
+            vec!["Skipped namestr record 2 at byte 780: unknown variable type 9"]
+        );
+    }
+
+    #[test]
+    fn recovery_keeps_the_variables_of_a_member_without_an_obs_header() {
+        let mut file = TRUNCATED_NUMERICS.to_vec();
+        file.drain(1360..1440);
+        let dataset = recover(&file);
+
+        assert_eq!(names(&dataset), vec!["N3", "N4", "N6", "CHR", "N8"]);
+        assert!(dataset.rows.is_empty());
+        assert_eq!(
+            dataset.warnings,
+            vec!["OBS header not found; the member is shown without observations"]
+        );
+    }
+
+    #[test]
+    fn recovery_keeps_a_row_cut_short_by_truncation() {
+        let file = &TRUNCATED_NUMERICS[..TRUNCATED_NUMERICS.len() - 13];
+        let dataset = recover(file);
+
+        // The cut leaves the first 17 of the 25 bytes of the last row, all but N8
+        let mut expected = intact_rows();
+        expected[2][4] = XPTValue::Missing(MissingValue::Standard);
+        assert_eq!(values(&dataset), expected);
+        assert_eq!(
+            dataset.warnings,
+            vec!["Row 3 is incomplete (17 of 25 bytes); its remaining cells are left missing"]
+        );
+    }
+
+    #[test]
+    fn recovery_reads_rows_padded_with_blanks() {
+        // Pad each 25-byte row to 32 bytes, then the block to whole records
+        let observations = &TRUNCATED_NUMERICS[1440..1440 + 75];
+        let mut file = TRUNCATED_NUMERICS[..1440].to_vec();
+        for row in observations.chunks(25) {
+            file.extend_from_slice(row);
+            file.extend_from_slice(&[b' '; 7]);
+        }
+        file.resize(1440 + 160, b' ');
+        let dataset = recover(&file);
+
+        assert_eq!(values(&dataset), intact_rows());
+        assert_eq!(
+            dataset.warnings,
+            vec!["Rows are 32 bytes long, 7 more than the variables take up; the extra bytes are ignored"]
+        );
+    }
+
+    #[test]
+    fn locates_errors_in_damaged_files() {
+        let error = |data: &[u8]| XPTParser::parse(data, None).unwrap_err();
+        let located = |error: &XptError| (error.code, error.section, error.offset, error.member);
+
+        let too_small = error(&TRUNCATED_NUMERICS[..40]);
+        assert_eq!(
+            located(&too_small),
+            (
+                ErrorCode::FileTooSmall,
+                Section::LibraryHeader,
+                Some(0),
+                None
+            )
+        );
+
+        // The NAMESTR header of the second member no longer reads as one
+        let mut file = MULTIPLE_MEMBERS.to_vec();
+        file[1440..1447].copy_from_slice(b"NAMESTX");
+        let missing_namestr = error(&file);
+        assert_eq!(
+            located(&missing_namestr),
+            (
+                ErrorCode::MissingNamestrHeader,
+                Section::MemberHeader,
+                Some(1120),
+                Some(2)
+            )
+        );
+        assert_eq!(missing_namestr.record, Some(15));
+        let mut members = XPTParser::lazy_members(&file, None, ParseOptions::default()).unwrap();
+        assert!(members.next_member(&file).unwrap().is_ok());
+        let lazy_error = members.next_member(&file).unwrap().unwrap_err();
+        assert_eq!(located(&lazy_error), located(&missing_namestr));
+
+        // Variable type 9 in the first namestr record of the third member
+        let mut file = MULTIPLE_MEMBERS.to_vec();
+        file[2560..2562].copy_from_slice(&[0, 9]);
+        let invalid_namestr = error(&file);
+        assert_eq!(
+            located(&invalid_namestr),
+            (
+                ErrorCode::InvalidNamestr,
+                Section::Namestr,
+                Some(2560),
+                Some(3)
+            )
+        );
+        assert_eq!(
+            invalid_namestr.message,
+            "Invalid namestr record 1: unknown variable type 9"
+        );
+
+        let mut file = TRUNCATED_NUMERICS.to_vec();
+        file.drain(1360..1440);
+        assert_eq!(
+            located(&error(&file)),
+            (
+                ErrorCode::MissingObsHeader,
+                Section::Namestr,
+                Some(560),
+                Some(1)
+            )
+        );
+
+        // 67 bytes of 25-byte rows, with no blank filler to explain the remainder
+        let truncated = error(&TRUNCATED_NUMERICS[..TRUNCATED_NUMERICS.len() - 13]);
+        assert_eq!(
+            located(&truncated),
+            (
+                ErrorCode::ObservationWidth,
+                Section::Observations,
+                Some(1440),
+                Some(1)
+            )
+        );
+        assert_eq!(
+            truncated.to_string(),
+            "Unable to determine observation width (observations, member 1, byte 1440, record 19)"
+        );
+    }
+
+    #[test]
+    fn names_a_blank_member_after_the_file_once_decoded() {
+        // Blank out the member name TRUNC in the first descriptor record
+        let mut file = TRUNCATED_NUMERICS.to_vec();
+        file[408..416].fill(b' ');
+        let detected = XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap()[0].encoding;
+
+        let datasets = XPTParser::parse(&file, Some("/data/Müller.xpt")).unwrap();
+        assert_eq!(datasets[0].title, "Müller");
+        assert_eq!(datasets[0].encoding, detected);
+
+        let utf8 = ParseOptions {
+            encoding: Some(TextEncoding::Utf8),
+            ..ParseOptions::default()
+        };
+        for filename in ["Müller.xpt", "受试者.xpt"] {
+            let title = filename.trim_end_matches(".xpt");
+            let eager = XPTParser::parse_with_options(&file, Some(filename), utf8).unwrap();
+            assert_eq!(eager[0].title, title);
+            let lazy = XPTParser::parse_lazy(&file, Some(filename), utf8).unwrap();
+            assert_eq!(lazy[0].dataset.title, title);
+            let mut members = XPTParser::lazy_members(&file, Some(filename), utf8).unwrap();
+            assert_eq!(
+                members.next_member(&file).unwrap().unwrap().dataset.title,
+                title
+            );
+        }
+
+        let lazy = XPTParser::parse_lazy(&file, Some("Müller.xpt"), ParseOptions::default());
+        assert_eq!(lazy.unwrap()[0].dataset.title, "Müller");
+        assert_eq!(
+            XPTParser::parse(&file, None).unwrap()[0].title,
+            "XPT Dataset"
+        );
+    }
+}
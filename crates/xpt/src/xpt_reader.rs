@@ -0,0 +1,503 @@
+//! Streaming access to transport files through any `Read + Seek` source
+//!
+//! [`XptReader`] reads the header records of the first member when it is created, and those of
+//! each later member when it is first asked for, then decodes observations one row at a time as
+//! they are iterated, so memory use depends on the number of variables and not on the number of
+//! rows. Reaching a member only reads the file up to the end of it. Rows are decoded exactly as
+//! [`XPTParser::parse_with_options`] decodes them; the warnings it adds about individual values
+//! are available from [`Rows::warnings`] instead.
+
+use crate::text_encoding::TextEncoding;
+use crate::xpt_error::{ErrorCode, Section, XptError, XptResult};
+use crate::xpt_parser::{
+    constants, diagnostic_warnings, DiagnosticCounts, LazyDataset, MemberContext, ParseOptions,
+    XPTDataset, XPTLibraryHeader, XPTParser, XPTRow, XportVersion, ENCODING_SAMPLE_ROWS,
+};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+
+/// Buffer size used when scanning the file and when reading rows
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Reads the members of a transport file from a seekable source, metadata first and then rows
+pub struct XptReader<R> {
+    source: R,
+    version: XportVersion,
+    library: XPTLibraryHeader,
+    options: ParseOptions,
+    suggested_filename: Option<String>,
+    /// Members read so far, in file order
+    members: Vec<LazyDataset>,
+    /// File offset of the next MEMBER header, `None` once every member has been read
+    next: Option<usize>,
+    encoding: TextEncoding,
+}
+
+impl<R: Read + Seek> XptReader<R> {
+    /// Reads the library header and the first member with the default options
+    pub fn new(source: R) -> XptResult<Self> {
+        Self::with_options(source, None, ParseOptions::default())
+    }
+
+    /// Reads the library header and the first member; `suggested_filename` names members whose
+    /// header leaves the name blank
+    ///
+    /// Unless an encoding is given, it is detected from the metadata and the first rows of the
+    /// first member and then used for every member.
+    pub fn with_options(
+        mut source: R,
+        suggested_filename: Option<&str>,
+        options: ParseOptions,
+    ) -> XptResult<Self> {
+        let (version, library, first) = scan_library(&mut source)?;
+        let mut reader = XptReader {
+            source,
+            version,
+            library,
+            options,
+            suggested_filename: suggested_filename.map(str::to_string),
+            members: Vec::new(),
+            next: Some(first),
+            // Rows decode as Latin-1 until the encoding is applied, which is what detection needs
+            encoding: options.encoding.unwrap_or(TextEncoding::Latin1),
+        };
+        if let Some(member) = reader.read_latin1()? {
+            if options.encoding.is_none() {
+                let mut sample = member.dataset.clone();
+                sample.rows = Rows::new(&mut reader.source, &member)
+                    .take(ENCODING_SAMPLE_ROWS)
+                    .collect::<XptResult<_>>()?;
+                reader.encoding = TextEncoding::detect(sample.texts());
+            }
+            reader.add_member(member);
+        }
+        Ok(reader)
+    }
+
+    /// Transport format revision of the file
+    pub fn version(&self) -> XportVersion {
+        self.version
+    }
+
+    /// Fields of the library header
+    pub fn library(&self) -> &XPTLibraryHeader {
+        &self.library
+    }
+
+    /// Encoding used for names, labels and character values, whether detected or requested
+    pub fn encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+
+    /// Metadata of member `member` (0-based), reading the file up to it; its `rows` are always
+    /// empty
+    pub fn dataset(&mut self, member: usize) -> XptResult<Option<&XPTDataset>> {
+        Ok(self.member(member)?.map(|member| &member.dataset))
+    }
+
+    /// Metadata of every member, in file order, reading the members not read yet; their `rows`
+    /// are always empty
+    pub fn datasets(&mut self) -> XptResult<impl Iterator<Item = &XPTDataset>> {
+        while self.read_member()? {}
+        Ok(self.members.iter().map(|member| &member.dataset))
+    }
+
+    /// Number of rows in member `member` (0-based)
+    pub fn row_count(&mut self, member: usize) -> XptResult<Option<usize>> {
+        Ok(self.member(member)?.map(LazyDataset::row_count))
+    }
+
+    /// Iterates over the rows of member `member` (0-based), reading them from the source as
+    /// they are needed
+    pub fn rows(&mut self, member: usize) -> XptResult<Option<Rows<'_, R>>> {
+        while self.members.len() <= member && self.read_member()? {}
+        let Some(member) = self.members.get(member) else {
+            return Ok(None);
+        };
+        Ok(Some(Rows::new(&mut self.source, member)))
+    }
+
+    /// Gives back the underlying source
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+
+    /// Reads members up to `member` (0-based) if they have not been read yet
+    fn member(&mut self, member: usize) -> XptResult<Option<&LazyDataset>> {
+        while self.members.len() <= member && self.read_member()? {}
+        Ok(self.members.get(member))
+    }
+
+    /// Reads the next member, returning whether there was one
+    ///
+    /// After an error no further member is read, since where the next one starts is not known.
+    fn read_member(&mut self) -> XptResult<bool> {
+        let Some(member) = self.read_latin1()? else {
+            return Ok(false);
+        };
+        self.add_member(member);
+        Ok(true)
+    }
+
+    /// Reads the next member with its text still decoded as Latin-1 and a blank name left blank
+    fn read_latin1(&mut self) -> XptResult<Option<LazyDataset>> {
+        let Some(start) = self.next.take() else {
+            return Ok(None);
+        };
+        let number = self.members.len() + 1;
+        let context = MemberContext {
+            version: self.version,
+            options: self.options,
+            library: &self.library,
+        };
+        let scanned = scan_member(&mut self.source, start, self.version)
+            .and_then(|scanned| {
+                let lazy = read_member(&mut self.source, &scanned, &context)?;
+                Ok((scanned, lazy))
+            })
+            .map_err(|error| error.in_member(number));
+        let (scanned, lazy) = scanned?;
+        self.next = scanned.next;
+        Ok(Some(lazy))
+    }
+
+    /// Decodes the text of a member read with [`XptReader::read_latin1`] and adds it
+    fn add_member(&mut self, mut member: LazyDataset) {
+        member.dataset.apply_encoding(self.encoding);
+        member
+            .dataset
+            .name_after_file(self.suggested_filename.as_deref());
+        self.members.push(member);
+    }
+}
+
+/// Iterator over the rows of one member, see [`XptReader::rows`]
+///
+/// Yields an error, and then stops, if the source cannot be read.
+pub struct Rows<'a, R> {
+    source: BufReader<&'a mut R>,
+    member: &'a LazyDataset,
+    next: usize,
+    /// Whether the source has to seek to row `next` first, as it does before the first row and
+    /// after skipped rows
+    seek_pending: bool,
+    buffer: Vec<u8>,
+    diagnostic_counts: DiagnosticCounts,
+}
+
+impl<'a, R: Read + Seek> Rows<'a, R> {
+    fn new(source: &'a mut R, member: &'a LazyDataset) -> Self {
+        Rows {
+            source: BufReader::with_capacity(BUFFER_SIZE, source),
+            member,
+            next: 0,
+            seek_pending: true,
+            buffer: Vec::new(),
+            diagnostic_counts: DiagnosticCounts::new(),
+        }
+    }
+
+    /// Warnings about the values decoded so far, such as numbers stored unnormalized, worded
+    /// as the parser words them in [`XPTDataset::warnings`]
+    pub fn warnings(&self) -> Vec<String> {
+        diagnostic_warnings(&self.member.dataset.variables, &self.diagnostic_counts)
+    }
+
+    fn read_row(&mut self) -> io::Result<XPTRow> {
+        let range = self.member.layout.row_range(self.next);
+        // Rows are stored back to back, so reading on needs no seek
+        if self.seek_pending {
+            self.source.seek(SeekFrom::Start(range.start as u64))?;
+            self.seek_pending = false;
+        }
+        self.buffer.resize(range.len(), 0);
+        self.source.read_exact(&mut self.buffer)?;
+
+        let mut row = self
+            .member
+            .decode(&self.buffer, &mut self.diagnostic_counts);
+        row.apply_encoding(self.member.dataset.encoding);
+        Ok(row)
+    }
+}
+
+impl<R: Read + Seek> Iterator for Rows<'_, R> {
+    type Item = XptResult<XPTRow>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.member.row_count() {
+            return None;
+        }
+        let offset = self.member.layout.row_range(self.next).start;
+        match self.read_row() {
+            Ok(row) => {
+                self.next += 1;
+                Some(Ok(row))
+            }
+            Err(error) => {
+                self.next = self.member.row_count();
+                Some(Err(io_error(error, Section::Observations, offset)))
+            }
+        }
+    }
+
+    /// Skips `n` rows without reading or decoding them
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        if n > 0 {
+            self.next = self.next.saturating_add(n).min(self.member.row_count());
+            self.seek_pending = true;
+        }
+        self.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.member.row_count().saturating_sub(self.next);
+        (remaining, Some(remaining))
+    }
+}
+
+impl<R: Read + Seek> ExactSizeIterator for Rows<'_, R> {}
+
+/// A member found by [`scan_member`]
+struct ScannedMember {
+    /// File offsets of its MEMBER header record and of the next member or the end of file
+    start: usize,
+    end: usize,
+    /// Its records up to and including the OBS header record, or all of them without one
+    header: Vec<u8>,
+    /// File offset of the next MEMBER header, if there is one
+    next: Option<usize>,
+}
+
+/// Reads the source up to the first MEMBER header, returning the version, the library header
+/// and the offset of that member
+fn scan_library<R: Read + Seek>(
+    source: &mut R,
+) -> XptResult<(XportVersion, XPTLibraryHeader, usize)> {
+    source
+        .seek(SeekFrom::Start(0))
+        .map_err(|error| io_error(error, Section::File, 0))?;
+    let mut reader = BufReader::with_capacity(BUFFER_SIZE, source);
+
+    let library_length = 3 * constants::RECORD_SIZE;
+    let mut library_records = Vec::with_capacity(library_length);
+    let mut record = [0u8; constants::RECORD_SIZE];
+    let mut position = 0;
+    loop {
+        let length = read_record(&mut reader, &mut record)
+            .map_err(|error| io_error(error, Section::LibraryHeader, position))?;
+        if length == 0 {
+            break;
+        }
+        let record = &record[..length];
+
+        if record.starts_with(constants::MEMBER_PREFIX) {
+            let version = XportVersion::from_headers(&library_records, Some(record));
+            let library = XPTParser::parse_library_header(&library_records);
+            return Ok((version, library, position));
+        }
+        if library_records.len() < library_length {
+            library_records.extend_from_slice(record);
+        }
+        position += length;
+    }
+
+    if position < constants::RECORD_SIZE {
+        return Err(XptError::new(
+            ErrorCode::FileTooSmall,
+            Section::LibraryHeader,
+            "File too small to be a valid XPT file",
+        )
+        .at(0));
+    }
+    Err(XptError::new(
+        ErrorCode::MissingMemberHeader,
+        Section::LibraryHeader,
+        "MEMBER header not found",
+    ))
+}
+
+/// Reads the member whose MEMBER header is at `start` record by record, keeping its header
+/// records, up to the next MEMBER header or the end of the source
+fn scan_member<R: Read + Seek>(
+    source: &mut R,
+    start: usize,
+    version: XportVersion,
+) -> XptResult<ScannedMember> {
+    source
+        .seek(SeekFrom::Start(start as u64))
+        .map_err(|error| io_error(error, Section::MemberHeader, start))?;
+    let mut reader = BufReader::with_capacity(BUFFER_SIZE, source);
+
+    let mut member = ScannedMember {
+        start,
+        end: start,
+        header: Vec::new(),
+        next: None,
+    };
+    // Whether the header records are still being read, and whether the NAMESTR header has
+    // been seen
+    let mut in_header = true;
+    let mut seen_namestr = false;
+    let mut record = [0u8; constants::RECORD_SIZE];
+    loop {
+        let section = if in_header {
+            Section::MemberHeader
+        } else {
+            Section::Observations
+        };
+        let length = read_record(&mut reader, &mut record)
+            .map_err(|error| io_error(error, section, member.end))?;
+        if length == 0 {
+            return Ok(member);
+        }
+        let record = &record[..length];
+        if member.end > start && record.starts_with(version.member_header()) {
+            member.next = Some(member.end);
+            return Ok(member);
+        }
+
+        if in_header {
+            member.header.extend_from_slice(record);
+            if record.starts_with(version.namestr_header()) {
+                seen_namestr = true;
+            } else if seen_namestr && record.starts_with(version.obs_header()) {
+                in_header = false;
+            }
+        }
+        member.end += length;
+    }
+}
+
+/// Parses the header records of a scanned member and reads the end of its observations to lay
+/// them out
+fn read_member<R: Read + Seek>(
+    source: &mut R,
+    member: &ScannedMember,
+    context: &MemberContext,
+) -> XptResult<LazyDataset> {
+    let header = XPTParser::parse_member_header(&member.header, member.start, context)?;
+    let observation_length = member.end - header.obs_data_start;
+    let tail_start = member.end - header.tail_length().min(observation_length);
+
+    let mut tail = vec![0; member.end - tail_start];
+    source
+        .seek(SeekFrom::Start(tail_start as u64))
+        .and_then(|_| source.read_exact(&mut tail))
+        .map_err(|error| io_error(error, Section::Observations, tail_start))?;
+
+    let (dataset, layout) = header.into_layout(&tail, observation_length, context.options)?;
+    Ok(LazyDataset { dataset, layout })
+}
+
+/// Fills `record` with the next 80-byte record, returning how many bytes were read; only the
+/// last record of the file may be short
+fn read_record(reader: &mut impl Read, record: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < record.len() {
+        match reader.read(&mut record[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(filled)
+}
+
+fn io_error(error: io::Error, section: Section, offset: usize) -> XptError {
+    XptError::new(
+        ErrorCode::Io,
+        section,
+        format!("Unable to read the file: {}", error),
+    )
+    .at(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xpt_writer::XPTWriter;
+    use std::io::Cursor;
+
+    const TRUNCATED_NUMERICS: &[u8] = include_bytes!("../tests/fixtures/truncated_numerics.xpt");
+
+    #[test]
+    fn streams_the_rows_the_parser_decodes() {
+        let parsed = XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap();
+        let mut reader = XptReader::new(Cursor::new(TRUNCATED_NUMERICS)).unwrap();
+
+        let names: Vec<String> = reader
+            .datasets()
+            .unwrap()
+            .map(|d| d.title.clone())
+            .collect();
+        assert_eq!(names, vec!["TRUNC".to_string()]);
+        assert_eq!(reader.library().sas_version, "9.4");
+        assert_eq!(reader.row_count(0).unwrap(), Some(parsed[0].rows.len()));
+
+        let rows = reader.rows(0).unwrap().unwrap();
+        assert_eq!(rows.len(), parsed[0].rows.len());
+        let values: Vec<_> = rows.map(|row| row.unwrap().values).collect();
+        let expected: Vec<_> = parsed[0]
+            .rows
+            .iter()
+            .map(|row| row.values.clone())
+            .collect();
+        assert_eq!(values, expected);
+
+        assert!(reader.rows(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn reads_later_members_only_when_asked_for() {
+        let first = XPTParser::parse(TRUNCATED_NUMERICS, None)
+            .unwrap()
+            .remove(0);
+        let mut second = first.clone();
+        second.title = "SECOND".to_string();
+        let datasets = [first.clone(), second];
+        let mut file = XPTWriter::to_bytes(&first.library, &datasets, XportVersion::V5).unwrap();
+        // Cut the second member short before its NAMESTR header
+        let start = (0..file.len())
+            .step_by(constants::RECORD_SIZE)
+            .filter(|&pos| file[pos..].starts_with(constants::MEMBER_HEADER))
+            .nth(1)
+            .unwrap();
+        file.truncate(start + 2 * constants::RECORD_SIZE);
+
+        let mut reader = XptReader::new(Cursor::new(file)).unwrap();
+        assert_eq!(reader.dataset(0).unwrap().unwrap().title, "TRUNC");
+        let rows = reader.rows(0).unwrap().unwrap();
+        assert_eq!(rows.count(), first.rows.len());
+
+        let error = reader.dataset(1).unwrap_err();
+        assert_eq!(error.code, ErrorCode::MissingNamestrHeader);
+        assert_eq!(error.member, Some(2));
+        assert!(reader.dataset(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn reports_a_source_cut_short() {
+        let error = XptReader::new(Cursor::new(&TRUNCATED_NUMERICS[..40]))
+            .err()
+            .unwrap();
+        assert_eq!(error.code, ErrorCode::FileTooSmall);
+    }
+
+    #[test]
+    fn names_a_blank_member_after_the_file_once_decoded() {
+        let mut file = TRUNCATED_NUMERICS.to_vec();
+        file[408..416].fill(b' ');
+        let mut reader = XptReader::with_options(
+            Cursor::new(file),
+            Some("Müller.xpt"),
+            ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            reader.encoding(),
+            XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap()[0].encoding
+        );
+        assert_eq!(reader.dataset(0).unwrap().unwrap().title, "Müller");
+    }
+}
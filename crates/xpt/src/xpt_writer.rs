@@ -0,0 +1,815 @@
+//! Writer for SAS XPORT Version 5 and Version 8 transport files
+//!
+//! The writer lays out the same records the parser reads: the library header, then for each
+//! member the MEMBER and DSCRPTR headers with the two descriptor records, the NAMESTR header with
+//! one 140-byte name string per variable, and the OBS header followed by the packed rows. Each
+//! block is padded with blanks to a whole 80-byte record. Version 8 files use the LIBV8, MEMBV8,
+//! DSCPTV8, NAMSTV8 and OBSV8 headers, and add a LABELV8 or LABELV9 section before the OBSV8
+//! header for labels and format names that do not fit in the name strings.
+
+use crate::ibm_float::{self, Diagnostic};
+use crate::text_encoding::TextEncoding;
+use crate::xpt_error::{ErrorCode, Section, XptError, XptResult};
+use crate::xpt_parser::constants::{self, RECORD_SIZE};
+use crate::xpt_parser::{
+    Justification, MissingValue, SasDateTime, VariableFormat, VariableType, XPTDataset,
+    XPTLibraryHeader, XPTRow, XPTValue, XPTVariable, XportVersion,
+};
+use std::io::Write;
+
+/// Most variables the four digits of the NAMESTR header can count
+const MAX_VARIABLES: usize = 9_999;
+
+/// Writes a transport library one member at a time
+///
+/// Rows are written as they come, so a member of any size can be streamed to a file:
+///
+/// ```
+/// use xpt::{XPTParser, XPTWriter};
+///
+/// # let bytes = std::fs::read("tests/fixtures/truncated_numerics.xpt")?;
+/// let datasets = XPTParser::parse(&bytes, None)?;
+/// let mut writer = XPTWriter::new(Vec::new(), &datasets[0].library)?;
+/// for dataset in &datasets {
+///     writer.write_dataset(dataset)?;
+/// }
+/// assert_eq!(writer.finish()?, bytes);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// [`XPTWriter::new`] writes Version 5 files; [`XPTWriter::with_version`] picks the revision.
+/// Variable numbers and value offsets are assigned from the order of the variables, and text is
+/// encoded with the encoding of each dataset. Like SAS, numerics stored in fewer than 8 bytes
+/// keep only the leading bytes of the IBM float. As in any transport file, a trailing row made
+/// only of blanks cannot be told apart from the padding of the last record and reads back as
+/// absent.
+pub struct XPTWriter<W: Write> {
+    out: W,
+    version: XportVersion,
+    /// Bytes written so far
+    position: usize,
+    /// Members started so far
+    members: usize,
+}
+
+impl<W: Write> XPTWriter<W> {
+    /// Starts a Version 5 library by writing its header records to `out`
+    pub fn new(out: W, library: &XPTLibraryHeader) -> XptResult<Self> {
+        Self::with_version(out, library, XportVersion::V5)
+    }
+
+    /// Starts a library of the given revision by writing its header records to `out`
+    pub fn with_version(
+        out: W,
+        library: &XPTLibraryHeader,
+        version: XportVersion,
+    ) -> XptResult<Self> {
+        let mut writer = XPTWriter {
+            out,
+            version,
+            position: 0,
+            members: 0,
+        };
+
+        let field = |value: &str, what: &str| {
+            ascii_field(value, 8, what).map_err(|message| {
+                XptError::new(ErrorCode::InvalidMetadata, Section::LibraryHeader, message)
+            })
+        };
+        let symbol = field(&library.sas_symbol, "SAS symbol")?;
+        let mut records = header_record(version.library_header(), &"0".repeat(30));
+        let mut second = blank_record();
+        put(&mut second, 0, &symbol);
+        put(&mut second, 8, &symbol);
+        put(&mut second, 16, b"SASLIB");
+        put(
+            &mut second,
+            24,
+            &field(&library.sas_version, "SAS version")?,
+        );
+        put(&mut second, 32, &field(&library.os_name, "OS name")?);
+        put(&mut second, 64, &timestamp(library.created));
+        records.extend_from_slice(&second);
+        let mut third = blank_record();
+        put(&mut third, 0, &timestamp(library.modified));
+        records.extend_from_slice(&third);
+
+        writer.write(&records, Section::LibraryHeader)?;
+        Ok(writer)
+    }
+
+    /// Writes the header records of a member described by `dataset`, whose `rows` are ignored,
+    /// and returns the writer for its rows
+    pub fn begin_member<'a>(
+        &'a mut self,
+        dataset: &'a XPTDataset,
+    ) -> XptResult<MemberWriter<'a, W>> {
+        self.members += 1;
+        let member = self.members;
+        self.pad_record(Section::Observations)?;
+
+        let records =
+            member_records(dataset, self.version).map_err(|error| error.in_member(member))?;
+        self.write(&records, Section::MemberHeader)
+            .map_err(|error| error.in_member(member))?;
+
+        Ok(MemberWriter {
+            writer: self,
+            dataset,
+            member,
+            rows: 0,
+        })
+    }
+
+    /// Writes a whole member: its header records followed by every row of `dataset`
+    pub fn write_dataset(&mut self, dataset: &XPTDataset) -> XptResult<()> {
+        let mut member = self.begin_member(dataset)?;
+        for row in &dataset.rows {
+            member.write_row(row)?;
+        }
+        Ok(())
+    }
+
+    /// Pads the last member to a whole record, flushes the output and gives it back
+    pub fn finish(mut self) -> XptResult<W> {
+        self.pad_record(Section::Observations)?;
+        let position = self.position;
+        self.out
+            .flush()
+            .map_err(|error| io_error(error, Section::Observations, position))?;
+        Ok(self.out)
+    }
+
+    fn write(&mut self, bytes: &[u8], section: Section) -> XptResult<()> {
+        self.out
+            .write_all(bytes)
+            .map_err(|error| io_error(error, section, self.position))?;
+        self.position += bytes.len();
+        Ok(())
+    }
+
+    /// Fills the rest of the current record with blanks
+    fn pad_record(&mut self, section: Section) -> XptResult<()> {
+        let padding = (RECORD_SIZE - self.position % RECORD_SIZE) % RECORD_SIZE;
+        self.write(&vec![b' '; padding], section)
+    }
+}
+
+impl XPTWriter<Vec<u8>> {
+    /// Writes a library of the given revision holding `datasets` to memory
+    pub fn to_bytes(
+        library: &XPTLibraryHeader,
+        datasets: &[XPTDataset],
+        version: XportVersion,
+    ) -> XptResult<Vec<u8>> {
+        let mut writer = XPTWriter::with_version(Vec::new(), library, version)?;
+        for dataset in datasets {
+            writer.write_dataset(dataset)?;
+        }
+        writer.finish()
+    }
+}
+
+/// Writes the rows of one member, see [`XPTWriter::begin_member`]
+///
+/// The member ends when the next one begins or the library is finished.
+pub struct MemberWriter<'a, W: Write> {
+    writer: &'a mut XPTWriter<W>,
+    dataset: &'a XPTDataset,
+    /// 1-based index of the member
+    member: usize,
+    /// Rows written so far
+    rows: usize,
+}
+
+impl<W: Write> MemberWriter<'_, W> {
+    /// Writes one row, which must hold a value of the right type for every variable
+    pub fn write_row(&mut self, row: &XPTRow) -> XptResult<()> {
+        let row_number = self.rows + 1;
+        let offset = self.writer.position;
+        let invalid = |message: String| {
+            XptError::new(
+                ErrorCode::InvalidValue,
+                Section::Observations,
+                format!("Row {}: {}", row_number, message),
+            )
+            .at(offset)
+            .in_member(self.member)
+        };
+
+        let variables = &self.dataset.variables;
+        if row.values.len() != variables.len() {
+            return Err(invalid(format!(
+                "{} values for {} variables",
+                row.values.len(),
+                variables.len()
+            )));
+        }
+
+        let mut bytes = Vec::with_capacity(variables.iter().map(|v| v.length).sum());
+        for (variable, value) in variables.iter().zip(&row.values) {
+            let cell = encode_cell(value, variable, self.dataset.encoding)
+                .map_err(|message| invalid(format!("{}: {}", variable.name, message)))?;
+            bytes.extend_from_slice(&cell);
+        }
+
+        self.writer
+            .write(&bytes, Section::Observations)
+            .map_err(|error| error.in_member(self.member))?;
+        self.rows += 1;
+        Ok(())
+    }
+}
+
+/// Longest names, labels and values each revision can hold
+struct Limits {
+    name: usize,
+    label: usize,
+    format_name: usize,
+    character_length: usize,
+}
+
+impl Limits {
+    fn of(version: XportVersion) -> Self {
+        match version {
+            XportVersion::V5 => Limits {
+                name: 8,
+                label: 40,
+                format_name: 8,
+                character_length: 200,
+            },
+            XportVersion::V8 => Limits {
+                name: 32,
+                label: 256,
+                format_name: 32,
+                character_length: 32_767,
+            },
+        }
+    }
+}
+
+/// Builds the header records of a member, from its MEMBER header to its OBS header
+fn member_records(dataset: &XPTDataset, version: XportVersion) -> XptResult<Vec<u8>> {
+    let metadata_error = |section: Section| {
+        move |message: String| XptError::new(ErrorCode::InvalidMetadata, section, message)
+    };
+    let encoding = dataset.encoding;
+    let name_length = version.dataset_name_length();
+
+    let name = text_field(&dataset.title, name_length, encoding, "dataset name")
+        .and_then(|name| non_empty(name, "dataset name"))
+        .map_err(metadata_error(Section::MemberHeader))?;
+    let label = text_field(&dataset.label, 40, encoding, "dataset label")
+        .map_err(metadata_error(Section::MemberHeader))?;
+    let dataset_type = ascii_field(&dataset.dataset_type, 8, "dataset type")
+        .map_err(metadata_error(Section::MemberHeader))?;
+    let sas_version = ascii_field(&dataset.sas_version, 8, "SAS version")
+        .map_err(metadata_error(Section::MemberHeader))?;
+    let os_name = ascii_field(&dataset.os_name, 8, "OS name")
+        .map_err(metadata_error(Section::MemberHeader))?;
+
+    let mut records = header_record(
+        version.member_header(),
+        &format!(
+            "{:0>20}{:0>10}",
+            "160",
+            constants::NAME_STRING_RECORD_LENGTH
+        ),
+    );
+    records.extend_from_slice(&header_record(version.descriptor_header(), &"0".repeat(30)));
+
+    // The dataset name is 8 bytes wide in Version 5 and 32 in Version 8, shifting what follows
+    let mut first = blank_record();
+    put(&mut first, 0, b"SAS");
+    put(&mut first, 8, &name);
+    put(&mut first, 8 + name_length, b"SASDATA");
+    put(&mut first, 16 + name_length, &sas_version);
+    put(&mut first, 24 + name_length, &os_name);
+    put(&mut first, 64, &timestamp(dataset.created_date));
+    records.extend_from_slice(&first);
+
+    let mut second = blank_record();
+    put(&mut second, 0, &timestamp(dataset.modified_date));
+    put(&mut second, 32, &label);
+    put(&mut second, 72, &dataset_type);
+    records.extend_from_slice(&second);
+
+    let variables = &dataset.variables;
+    if variables.is_empty() || variables.len() > MAX_VARIABLES {
+        return Err(XptError::new(
+            ErrorCode::InvalidMetadata,
+            Section::Namestr,
+            format!(
+                "A member needs between 1 and {} variables, not {}",
+                MAX_VARIABLES,
+                variables.len()
+            ),
+        ));
+    }
+    records.extend_from_slice(&header_record(
+        version.namestr_header(),
+        &format!("{:0>10}{:0>20}", variables.len(), 0),
+    ));
+
+    let mut offset = 0;
+    let mut extensions = Vec::new();
+    for (index, variable) in variables.iter().enumerate() {
+        let duplicate = variables[..index]
+            .iter()
+            .any(|other| other.name.eq_ignore_ascii_case(&variable.name));
+        if duplicate {
+            return Err(XptError::new(
+                ErrorCode::InvalidMetadata,
+                Section::Namestr,
+                format!("Variable {} appears more than once", variable.name),
+            ));
+        }
+
+        let (record, extension) =
+            name_string(variable, index, offset, encoding, version).map_err(|message| {
+                XptError::new(
+                    ErrorCode::InvalidMetadata,
+                    Section::Namestr,
+                    format!("Variable {}: {}", index + 1, message),
+                )
+            })?;
+        records.extend_from_slice(&record);
+        extensions.extend(extension);
+        offset += variable.length;
+    }
+    pad_to_record(&mut records);
+
+    if !extensions.is_empty() {
+        records.extend_from_slice(&label_section(&extensions));
+    }
+
+    records.extend_from_slice(&header_record(version.obs_header(), &"0".repeat(30)));
+    Ok(records)
+}
+
+/// Full label and format names of a variable whose name string only holds a shortened copy
+struct LabelExtension {
+    number: u16,
+    name: Vec<u8>,
+    label: Vec<u8>,
+    format: String,
+    informat: String,
+    /// Whether the format or informat name is too long for the name string
+    long_formats: bool,
+}
+
+/// Builds the 140-byte name string of the variable at `index`, whose values start `offset`
+/// bytes into each row, along with the LABELV8/LABELV9 entry it needs in a Version 8 file
+///
+/// Version 8 name strings also hold the full name at byte 88 and the label length at byte 120.
+fn name_string(
+    variable: &XPTVariable,
+    index: usize,
+    offset: usize,
+    encoding: TextEncoding,
+    version: XportVersion,
+) -> Result<(Vec<u8>, Option<LabelExtension>), String> {
+    let limits = Limits::of(version);
+    let name = text_field(&variable.name, limits.name, encoding, "name")
+        .and_then(|name| non_empty(name, "name"))?;
+    let label = text_field(&variable.label, limits.label, encoding, "label")?;
+    let (var_type, length_range) = match variable.var_type {
+        VariableType::Numeric => (
+            1u16,
+            constants::MIN_NUMERIC_LENGTH..=constants::FULL_NUMERIC_LENGTH,
+        ),
+        VariableType::Character => (
+            2u16,
+            constants::MIN_CHARACTER_LENGTH..=limits.character_length,
+        ),
+    };
+    if !length_range.contains(&variable.length) {
+        return Err(format!(
+            "length {} is outside {}-{}",
+            variable.length,
+            length_range.start(),
+            length_range.end()
+        ));
+    }
+    let format = format_fields(&variable.format, limits.format_name, "format")?;
+    let informat = format_fields(&variable.informat, limits.format_name, "informat")?;
+    let justification: u16 = match variable.justification {
+        Justification::Left => 0,
+        Justification::Right => 1,
+    };
+    let number = index as u16 + 1;
+
+    let mut record = vec![0u8; constants::NAME_STRING_RECORD_LENGTH];
+    put(&mut record, 0, &var_type.to_be_bytes());
+    put(&mut record, 4, &(variable.length as u16).to_be_bytes());
+    put(&mut record, 6, &number.to_be_bytes());
+    put(&mut record, 8, &padded(shortened(&name, 8, encoding), 8));
+    put(
+        &mut record,
+        16,
+        &padded(shortened(&label, 40, encoding), 40),
+    );
+    put(&mut record, 56, &format);
+    put(&mut record, 68, &justification.to_be_bytes());
+    put(&mut record, 72, &informat);
+    put(&mut record, 84, &(offset as u32).to_be_bytes());
+
+    let long_formats = variable.format.name.len() > 8 || variable.informat.name.len() > 8;
+    let extension = match version {
+        XportVersion::V5 => None,
+        XportVersion::V8 => {
+            put(&mut record, 88, &padded(&name, 32));
+            put(&mut record, 120, &(label.len() as u16).to_be_bytes());
+            (label.len() > 40 || long_formats).then(|| LabelExtension {
+                number,
+                name,
+                label,
+                format: variable.format.to_string(),
+                informat: variable.informat.to_string(),
+                long_formats,
+            })
+        }
+    };
+    Ok((record, extension))
+}
+
+/// Name, width and decimals of a format as stored in a name string, the name cut to 8 bytes
+fn format_fields(format: &VariableFormat, limit: usize, what: &str) -> Result<Vec<u8>, String> {
+    let name = ascii_field(&format.name, limit, &format!("{} name", what))?;
+    let mut fields = padded(&name[..name.len().min(8)], 8);
+    fields.extend_from_slice(&format.width.to_be_bytes());
+    fields.extend_from_slice(&format.decimals.to_be_bytes());
+    Ok(fields)
+}
+
+/// Builds the LABELV8 section, or LABELV9 when some format name needs the room: a header
+/// counting the entries, then the entries back to back, padded to a whole record
+fn label_section(extensions: &[LabelExtension]) -> Vec<u8> {
+    let with_formats = extensions.iter().any(|extension| extension.long_formats);
+    let tag = if with_formats {
+        constants::LABEL_V9_HEADER
+    } else {
+        constants::LABEL_V8_HEADER
+    };
+    let mut section = header_record(tag, &format!("{:<30}", extensions.len()));
+
+    let length = |bytes: &[u8]| (bytes.len() as u16).to_be_bytes();
+    for extension in extensions {
+        section.extend_from_slice(&extension.number.to_be_bytes());
+        section.extend_from_slice(&length(&extension.name));
+        section.extend_from_slice(&length(&extension.label));
+        if with_formats {
+            section.extend_from_slice(&length(extension.format.as_bytes()));
+            section.extend_from_slice(&length(extension.informat.as_bytes()));
+        }
+        section.extend_from_slice(&extension.name);
+        section.extend_from_slice(&extension.label);
+        if with_formats {
+            section.extend_from_slice(extension.format.as_bytes());
+            section.extend_from_slice(extension.informat.as_bytes());
+        }
+    }
+    pad_to_record(&mut section);
+    section
+}
+
+/// Encodes one cell as stored in a row
+fn encode_cell(
+    value: &XPTValue,
+    variable: &XPTVariable,
+    encoding: TextEncoding,
+) -> Result<Vec<u8>, String> {
+    let mut cell = match (variable.var_type, value) {
+        (VariableType::Numeric, XPTValue::Numeric(number)) => {
+            let converted = ibm_float::ieee_to_ibm(*number);
+            match converted.diagnostic {
+                Some(Diagnostic::NotANumber) => {
+                    return Err("NaN cannot be stored; use a missing value".to_string())
+                }
+                Some(Diagnostic::Overflow) => {
+                    return Err(format!("{} is beyond the IBM float range", number))
+                }
+                // Magnitudes below the IBM range are stored as zero, as SAS does
+                _ => converted.value.to_vec(),
+            }
+        }
+        (VariableType::Numeric, XPTValue::Missing(missing)) => {
+            let mut cell = vec![0u8; constants::FULL_NUMERIC_LENGTH];
+            cell[0] = match missing {
+                MissingValue::Standard => b'.',
+                MissingValue::Underscore => b'_',
+                MissingValue::Letter(letter @ 'A'..='Z') => *letter as u8,
+                MissingValue::Letter(letter) => {
+                    return Err(format!("no special missing value .{}", letter))
+                }
+            };
+            cell
+        }
+        (VariableType::Character, XPTValue::Character(text)) => {
+            let bytes = text_field(
+                text.trim_end_matches(' '),
+                variable.length,
+                encoding,
+                "value",
+            )?;
+            padded(&bytes, variable.length)
+        }
+        // A missing character value is stored as blanks
+        (VariableType::Character, XPTValue::Missing(MissingValue::Standard)) => {
+            vec![b' '; variable.length]
+        }
+        (VariableType::Numeric, XPTValue::Character(_)) => {
+            return Err("text given for a numeric variable".to_string())
+        }
+        (VariableType::Character, _) => {
+            return Err("number given for a character variable".to_string())
+        }
+    };
+    cell.truncate(variable.length);
+    Ok(cell)
+}
+
+/// A header record: the tag followed by 30 digits and two blanks
+fn header_record(tag: &[u8], digits: &str) -> Vec<u8> {
+    let mut record = blank_record();
+    put(&mut record, 0, tag);
+    put(&mut record, tag.len(), digits.as_bytes());
+    record
+}
+
+fn blank_record() -> Vec<u8> {
+    vec![b' '; RECORD_SIZE]
+}
+
+/// Pads `records` with blanks to a whole number of records
+fn pad_to_record(records: &mut Vec<u8>) {
+    let padding = (RECORD_SIZE - records.len() % RECORD_SIZE) % RECORD_SIZE;
+    records.resize(records.len() + padding, b' ');
+}
+
+/// Copies `bytes` into `record` at `offset`
+fn put(record: &mut [u8], offset: usize, bytes: &[u8]) {
+    record[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+/// Pads `bytes` with blanks to `length`
+fn padded(bytes: &[u8], length: usize) -> Vec<u8> {
+    let mut padded = bytes.to_vec();
+    padded.resize(length, b' ');
+    padded
+}
+
+/// Cuts encoded text to at most `length` bytes without splitting a UTF-8 sequence
+fn shortened(bytes: &[u8], length: usize, encoding: TextEncoding) -> &[u8] {
+    if bytes.len() <= length {
+        return bytes;
+    }
+    let mut end = length;
+    if encoding == TextEncoding::Utf8 {
+        while end > 0 && bytes[end] & 0xC0 == 0x80 {
+            end -= 1;
+        }
+    }
+    &bytes[..end]
+}
+
+/// Header timestamp, or blanks when it is not known
+fn timestamp(value: Option<SasDateTime>) -> Vec<u8> {
+    value.map_or_else(
+        || vec![b' '; 16],
+        |value| value.to_header_text().into_bytes(),
+    )
+}
+
+/// Encodes a text field, checking that it fits in `length` bytes
+fn text_field(
+    text: &str,
+    length: usize,
+    encoding: TextEncoding,
+    what: &str,
+) -> Result<Vec<u8>, String> {
+    let bytes = encoding.encode(text).map_err(|c| {
+        format!(
+            "{} {:?} holds {:?}, which the text encoding cannot represent",
+            what, text, c
+        )
+    })?;
+    if bytes.len() > length {
+        return Err(format!(
+            "{} {:?} is {} bytes long, more than the {} that fit",
+            what,
+            text,
+            bytes.len(),
+            length
+        ));
+    }
+    Ok(bytes)
+}
+
+/// Encodes a header field that is always plain ASCII
+fn ascii_field(text: &str, length: usize, what: &str) -> Result<Vec<u8>, String> {
+    if !text.is_ascii() {
+        return Err(format!("{} {:?} is not plain ASCII", what, text));
+    }
+    text_field(text, length, TextEncoding::Latin1, what)
+}
+
+fn non_empty(bytes: Vec<u8>, what: &str) -> Result<Vec<u8>, String> {
+    if bytes.iter().all(|&byte| byte == b' ') {
+        Err(format!("the {} is empty", what))
+    } else {
+        Ok(bytes)
+    }
+}
+
+fn io_error(error: std::io::Error, section: Section, offset: usize) -> XptError {
+    XptError::new(
+        ErrorCode::Io,
+        section,
+        format!("Unable to write the file: {}", error),
+    )
+    .at(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{dataset, variable};
+    use crate::xpt_parser::XPTParser;
+
+    const TRUNCATED_NUMERICS: &[u8] = include_bytes!("../tests/fixtures/truncated_numerics.xpt");
+
+    #[test]
+    fn rewrites_a_parsed_file_byte_for_byte() {
+        let datasets = XPTParser::parse(TRUNCATED_NUMERICS, None).unwrap();
+        let written =
+            XPTWriter::to_bytes(&datasets[0].library, &datasets, XportVersion::V5).unwrap();
+        assert_eq!(written, TRUNCATED_NUMERICS);
+    }
+
+    #[test]
+    fn round_trips_values_and_members_through_the_parser() {
+        let mut amount = variable("AMOUNT", VariableType::Numeric, 8);
+        amount.label = "Amount paid".to_string();
+        amount.format = VariableFormat {
+            name: "COMMA".to_string(),
+            width: 12,
+            decimals: 2,
+        };
+        amount.justification = Justification::Right;
+        let first = dataset(
+            "PAYMENTS",
+            vec![
+                variable("NAME", VariableType::Character, 10),
+                amount,
+                variable("SHORT", VariableType::Numeric, 4),
+            ],
+            vec![
+                vec![
+                    XPTValue::Character("Zoë".to_string()),
+                    XPTValue::Numeric(1234.5),
+                    XPTValue::Numeric(-0.1),
+                ],
+                vec![
+                    XPTValue::Missing(MissingValue::Standard),
+                    XPTValue::Missing(MissingValue::Letter('Q')),
+                    XPTValue::Missing(MissingValue::Underscore),
+                ],
+                vec![
+                    XPTValue::Character(" indented".to_string()),
+                    XPTValue::Numeric(1e-100),
+                    XPTValue::Numeric(0.5),
+                ],
+            ],
+        );
+        let second = dataset(
+            "EMPTY",
+            vec![variable("ID", VariableType::Numeric, 8)],
+            Vec::new(),
+        );
+
+        let library = XPTLibraryHeader {
+            sas_symbol: "SAS".to_string(),
+            sas_version: "9.4".to_string(),
+            os_name: "X64_10PR".to_string(),
+            created: first.created_date,
+            modified: first.modified_date,
+        };
+        let bytes =
+            XPTWriter::to_bytes(&library, &[first.clone(), second], XportVersion::V5).unwrap();
+        assert_eq!(bytes.len() % RECORD_SIZE, 0);
+
+        let parsed = XPTParser::parse(&bytes, None).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].title, "PAYMENTS");
+        assert_eq!(parsed[0].variables[1].label, "Amount paid");
+        assert_eq!(parsed[0].variables[1].format.to_string(), "COMMA12.2");
+        assert_eq!(parsed[0].variables[2].offset, 18);
+        assert_eq!(parsed[0].library.created, library.created);
+        assert_eq!(parsed[1].title, "EMPTY");
+        assert!(parsed[1].rows.is_empty());
+
+        let values: Vec<&Vec<XPTValue>> = parsed[0].rows.iter().map(|row| &row.values).collect();
+        assert_eq!(
+            values,
+            vec![
+                &vec![
+                    XPTValue::Character("Zoë".to_string()),
+                    XPTValue::Numeric(1234.5),
+                    // Four bytes keep the first three bytes of the fraction, 0x199999
+                    XPTValue::Numeric(-1677721.0 / 16777216.0),
+                ],
+                &vec![
+                    XPTValue::Character(String::new()),
+                    XPTValue::Missing(MissingValue::Letter('Q')),
+                    XPTValue::Missing(MissingValue::Underscore),
+                ],
+                &vec![
+                    XPTValue::Character(" indented".to_string()),
+                    XPTValue::Numeric(0.0),
+                    XPTValue::Numeric(0.5),
+                ],
+            ]
+        );
+
+        let rewritten = XPTWriter::to_bytes(&parsed[0].library, &parsed, XportVersion::V5).unwrap();
+        assert_eq!(rewritten, bytes);
+    }
+
+    #[test]
+    fn rejects_what_a_version_5_file_cannot_hold() {
+        let long_name = dataset(
+            "DM",
+            vec![variable("SUBJECTID", VariableType::Character, 8)],
+            Vec::new(),
+        );
+        let error =
+            XPTWriter::to_bytes(&XPTLibraryHeader::default(), &[long_name], XportVersion::V5)
+                .unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidMetadata);
+        assert_eq!(error.section, Section::Namestr);
+
+        let wrong_type = dataset(
+            "DM",
+            vec![variable("AGE", VariableType::Numeric, 8)],
+            vec![vec![XPTValue::Character("42".to_string())]],
+        );
+        let error = XPTWriter::to_bytes(
+            &XPTLibraryHeader::default(),
+            &[wrong_type],
+            XportVersion::V5,
+        )
+        .unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidValue);
+        assert_eq!(error.member, Some(1));
+        assert_eq!(error.record, Some(12));
+    }
+
+    #[test]
+    fn writes_long_names_labels_and_formats_as_version_8() {
+        let mut subject = variable("SUBJECT_IDENTIFIER", VariableType::Character, 300);
+        subject.label = format!("Unique subject identifier – {}", "x".repeat(60));
+        let mut onset = variable("ONSET", VariableType::Numeric, 8);
+        onset.label = "Onset date".to_string();
+        onset.format = VariableFormat {
+            name: "E8601DATETIME".to_string(),
+            width: 19,
+            decimals: 0,
+        };
+        let adverse_events = dataset(
+            "ADVERSE_EVENTS",
+            vec![subject, onset, variable("AESEQ", VariableType::Numeric, 8)],
+            vec![vec![
+                XPTValue::Character("STUDY-001-0001".to_string()),
+                XPTValue::Numeric(2_000_000_000.0),
+                XPTValue::Numeric(1.0),
+            ]],
+        );
+
+        let library = XPTLibraryHeader::default();
+        let bytes = XPTWriter::to_bytes(
+            &library,
+            std::slice::from_ref(&adverse_events),
+            XportVersion::V8,
+        )
+        .unwrap();
+        assert!(bytes.starts_with(constants::LIBRARY_V8_HEADER));
+        assert!(bytes
+            .chunks(RECORD_SIZE)
+            .any(|record| record.starts_with(constants::LABEL_V9_HEADER)));
+
+        let parsed = XPTParser::parse(&bytes, None).unwrap();
+        let dataset = &parsed[0];
+        assert_eq!(dataset.title, "ADVERSE_EVENTS");
+        for (parsed, written) in dataset.variables.iter().zip(&adverse_events.variables) {
+            assert_eq!(parsed.name, written.name);
+            assert_eq!(parsed.label, written.label);
+            assert_eq!(parsed.format, written.format);
+            assert_eq!(parsed.length, written.length);
+        }
+        assert_eq!(dataset.rows[0].values, adverse_events.rows[0].values);
+
+        let rewritten = XPTWriter::to_bytes(&library, &parsed, XportVersion::V8).unwrap();
+        assert_eq!(rewritten, bytes);
+    }
+}
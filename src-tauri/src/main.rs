@@ -1,14 +1,28 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod xpt_parser;
-
-use anyhow::{anyhow, Context, Result};
+use anyhow::anyhow;
+use memmap2::Mmap;
 use serde::Serialize;
-use std::collections::BTreeMap;
-use std::fs;
-use std::path::Path;
-use tauri::Manager;
-use xpt_parser::{XPTParser, VariableType};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::BufWriter;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{Manager, State};
+use xpt::{
+    read_dataset_json, read_sas7bdat, read_sas7bdat_lazy, write_dataset_json, write_delimited,
+    DatasetJsonOptions, DelimitedOptions, ErrorCode, Justification, LazyDataset, LazyMembers,
+    LazySas7bdat, MissingValue, ParseOptions, Section, TextEncoding, VariableType, XPTDataset,
+    XPTParser, XPTRow, XPTValue, XlsxOptions, XlsxWriter, XptError, XptResult,
+};
+
+/// Largest number of rows returned by a single `fetch_rows` call
+const MAX_FETCH_ROWS: usize = 5_000;
+/// Files larger than this are decoded row by row as the grid asks for them, instead of all at
+/// once, so opening them takes neither the time nor the memory to decode every row
+const LAZY_DECODING_THRESHOLD: usize = 64 * 1024 * 1024;
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -17,48 +31,485 @@ struct FieldMetadata {
     label: Option<String>,
     #[serde(rename = "type")]
     kind: String,
+    length: usize,
+    /// Format as SAS prints it, e.g. `DATE9.` or `8.2`
+    format: Option<String>,
+    informat: Option<String>,
+    justification: &'static str,
+    /// Variable number and byte offset within an observation, from the namestr
+    number: u16,
+    offset: usize,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct DatasetSummary {
+    /// Handle to pass to `fetch_rows`
+    handle: u64,
     name: String,
     label: Option<String>,
+    dataset_type: Option<String>,
+    sas_version: Option<String>,
+    os_name: Option<String>,
+    /// ISO 8601 timestamps read from the member descriptor
     created_date: Option<String>,
     modified_date: Option<String>,
+    library: LibrarySummary,
     observation_count: usize,
     fields: Vec<FieldMetadata>,
+    warnings: Vec<String>,
+}
+
+/// Rows `offset..offset + rows.len()` of an open dataset
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RowWindow {
+    offset: usize,
     rows: Vec<BTreeMap<String, serde_json::Value>>,
+    /// Numeric cells rendered with their SAS format, one map per row holding only the cells
+    /// whose format is supported
+    formatted_rows: Vec<BTreeMap<String, String>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LibrarySummary {
+    sas_symbol: Option<String>,
+    sas_version: Option<String>,
+    os_name: Option<String>,
+    created_date: Option<String>,
+    modified_date: Option<String>,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct XptFilePayload {
     path: String,
+    /// Encoding used for names, labels and character values, whether detected or requested
+    encoding: TextEncoding,
     datasets: Vec<DatasetSummary>,
+    /// Whether members remain to be read with `next_member`
+    more_members: bool,
+}
+
+/// Memory map of the most recently loaded file, kept so it can be decoded again with other
+/// options without opening it again
+#[derive(Default)]
+struct LoadedFile(Mutex<Option<(PathBuf, Arc<Mmap>)>>);
+
+/// A dataset of the open file
+enum OpenDataset {
+    /// Small files are decoded up front
+    Parsed(XPTDataset),
+    /// Large files keep only the metadata and decode rows from the mapped file on request
+    Mapped {
+        data: Arc<Mmap>,
+        dataset: LazyDataset,
+    },
+    /// Large SAS7BDAT files, likewise decoded row by row
+    MappedSas7bdat {
+        data: Arc<Mmap>,
+        dataset: LazySas7bdat,
+    },
+}
+
+impl OpenDataset {
+    /// Metadata of the dataset; its `rows` are empty for mapped datasets
+    fn metadata(&self) -> &XPTDataset {
+        match self {
+            OpenDataset::Parsed(dataset) => dataset,
+            OpenDataset::Mapped { dataset, .. } => &dataset.dataset,
+            OpenDataset::MappedSas7bdat { dataset, .. } => &dataset.dataset,
+        }
+    }
+
+    fn row_count(&self) -> usize {
+        match self {
+            OpenDataset::Parsed(dataset) => dataset.rows.len(),
+            OpenDataset::Mapped { dataset, .. } => dataset.row_count(),
+            OpenDataset::MappedSas7bdat { dataset, .. } => dataset.row_count(),
+        }
+    }
+
+    fn rows(&self, range: Range<usize>) -> Vec<XPTRow> {
+        match self {
+            OpenDataset::Parsed(dataset) => dataset.rows[range].to_vec(),
+            OpenDataset::Mapped { data, dataset } => {
+                range.filter_map(|index| dataset.row(data, index)).collect()
+            }
+            OpenDataset::MappedSas7bdat { data, dataset } => {
+                range.filter_map(|index| dataset.row(data, index)).collect()
+            }
+        }
+    }
+
+    fn row(&self, index: usize) -> Option<XPTRow> {
+        match self {
+            OpenDataset::Parsed(dataset) => dataset.rows.get(index).cloned(),
+            OpenDataset::Mapped { data, dataset } => dataset.row(data, index),
+            OpenDataset::MappedSas7bdat { data, dataset } => dataset.row(data, index),
+        }
+    }
+}
+
+/// Datasets of the open file, kept so the grid can fetch rows a window at a time instead of
+/// receiving every row in one payload
+#[derive(Default)]
+struct OpenDatasets {
+    datasets: Mutex<HashMap<u64, Arc<OpenDataset>>>,
+    /// Members of a large transport file not located yet. Locked before `datasets` whenever
+    /// both are, and held while a member is located, so a file opened meanwhile waits for it.
+    pending: Mutex<Option<(Arc<Mmap>, LazyMembers)>>,
+    /// Handles are never reused, so a stale handle cannot read another file's rows
+    next_handle: AtomicU64,
+}
+
+impl OpenDatasets {
+    /// Replaces the datasets of the previously open file and returns the new handles
+    fn replace(
+        &self,
+        datasets: Vec<OpenDataset>,
+        pending: Option<(Arc<Mmap>, LazyMembers)>,
+    ) -> Vec<(u64, Arc<OpenDataset>)> {
+        let mut pending_members = self.pending.lock().unwrap();
+        let opened: Vec<(u64, Arc<OpenDataset>)> = datasets
+            .into_iter()
+            .map(|dataset| self.handle(dataset))
+            .collect();
+        *self.datasets.lock().unwrap() = opened.iter().cloned().collect();
+        *pending_members = pending;
+        opened
+    }
+
+    /// Locates the next member of the open file and adds it to the open datasets
+    fn next_member(&self) -> XptResult<Option<(u64, Arc<OpenDataset>)>> {
+        let mut pending = self.pending.lock().unwrap();
+        let Some((data, members)) = pending.as_mut() else {
+            return Ok(None);
+        };
+        let data = Arc::clone(data);
+        let result = members.next_member(&data).transpose();
+        if !members.has_next() {
+            *pending = None;
+        }
+        let Some(dataset) = result? else {
+            return Ok(None);
+        };
+
+        let opened = self.handle(OpenDataset::Mapped { data, dataset });
+        let (handle, dataset) = &opened;
+        self.datasets
+            .lock()
+            .unwrap()
+            .insert(*handle, Arc::clone(dataset));
+        Ok(Some(opened))
+    }
+
+    /// Gives a dataset a new handle
+    fn handle(&self, dataset: OpenDataset) -> (u64, Arc<OpenDataset>) {
+        let handle = self.next_handle.fetch_add(1, Ordering::Relaxed);
+        (handle, Arc::new(dataset))
+    }
+
+    fn get(&self, handle: u64) -> XptResult<Arc<OpenDataset>> {
+        self.datasets
+            .lock()
+            .unwrap()
+            .get(&handle)
+            .cloned()
+            .ok_or_else(|| {
+                XptError::new(
+                    ErrorCode::NotLoaded,
+                    Section::File,
+                    format!("Dataset handle {} is not open", handle),
+                )
+            })
+    }
+}
+
+/// Loads every dataset of a transport file, or the dataset of a Dataset-JSON file; failures
+/// reach the frontend as a serialized `XptError` with its code, section and byte offset
+///
+/// With `recover` set, damaged files are loaded as far as possible and the repairs are listed
+/// in each dataset's warnings. Text is decoded with `encoding`, or a detected one when omitted.
+/// Large transport files come back with their first member alone, and `more_members` set
+/// when the others remain to be read with `next_member`. Runs off the main thread, so the
+/// window stays responsive while a large file is opened.
+#[tauri::command(async)]
+fn load_xpt(
+    path: String,
+    recover: Option<bool>,
+    encoding: Option<TextEncoding>,
+    loaded: State<'_, LoadedFile>,
+    open: State<'_, OpenDatasets>,
+) -> Result<XptFilePayload, XptError> {
+    let path = PathBuf::from(path);
+    let data = Arc::new(map_file(&path).map_err(|error| {
+        XptError::new(
+            ErrorCode::Io,
+            Section::File,
+            format!("Unable to read file {}: {}", path.display(), error),
+        )
+    })?);
+    *loaded.0.lock().unwrap() = Some((path.clone(), Arc::clone(&data)));
+
+    let options = ParseOptions {
+        recover: recover.unwrap_or(false),
+        encoding,
+    };
+    load_xpt_impl(&path, &data, options, &open)
+}
+
+/// Decodes the file loaded last again, e.g. with another encoding, without opening it again
+#[tauri::command(async)]
+fn reopen_xpt(
+    path: String,
+    recover: Option<bool>,
+    encoding: Option<TextEncoding>,
+    loaded: State<'_, LoadedFile>,
+    open: State<'_, OpenDatasets>,
+) -> Result<XptFilePayload, XptError> {
+    let path = PathBuf::from(path);
+    let data = match &*loaded.0.lock().unwrap() {
+        Some((loaded_path, data)) if *loaded_path == path => Arc::clone(data),
+        _ => {
+            return Err(XptError::new(
+                ErrorCode::NotLoaded,
+                Section::File,
+                format!("{} is not loaded; open it again", path.display()),
+            ))
+        }
+    };
+
+    let options = ParseOptions {
+        recover: recover.unwrap_or(false),
+        encoding,
+    };
+    load_xpt_impl(&path, &data, options, &open)
 }
 
-#[tauri::command]
-fn load_xpt(path: String) -> Result<XptFilePayload, String> {
-    match load_xpt_impl(Path::new(&path)) {
-        Ok(payload) => Ok(payload),
-        Err(error) => Err(error.to_string()),
+/// Returns up to `limit` rows of an open dataset, starting at row `offset`
+#[tauri::command(async)]
+fn fetch_rows(
+    handle: u64,
+    offset: usize,
+    limit: usize,
+    open: State<'_, OpenDatasets>,
+) -> Result<RowWindow, XptError> {
+    let dataset = open.get(handle)?;
+    let start = offset.min(dataset.row_count());
+    let end = start
+        .saturating_add(limit.min(MAX_FETCH_ROWS))
+        .min(dataset.row_count());
+    let window = dataset.rows(start..end);
+    let metadata = dataset.metadata();
+
+    Ok(RowWindow {
+        offset: start,
+        rows: window.iter().map(|row| row_values(metadata, row)).collect(),
+        formatted_rows: window
+            .iter()
+            .map(|row| formatted_values(metadata, row))
+            .collect(),
+    })
+}
+
+/// Locates the next member of a large transport file, whose members after the first are only
+/// read as the frontend asks for them; returns `null` once every member has been read
+#[tauri::command(async)]
+fn next_member(open: State<'_, OpenDatasets>) -> Result<Option<DatasetSummary>, XptError> {
+    Ok(open
+        .next_member()?
+        .map(|(handle, dataset)| summarize_dataset(handle, &dataset)))
+}
+
+/// Writes an open dataset to `path` as delimited text, one row at a time, so exporting a large
+/// file needs no more memory than viewing it
+///
+/// Runs off the main thread, so the window stays responsive while a large file is written.
+#[tauri::command(async)]
+fn export_dataset(
+    handle: u64,
+    path: String,
+    options: DelimitedOptions,
+    open: State<'_, OpenDatasets>,
+) -> Result<(), XptError> {
+    let dataset = open.get(handle)?;
+    let path = PathBuf::from(path);
+    let file = File::create(&path).map_err(|error| {
+        XptError::new(
+            ErrorCode::Io,
+            Section::File,
+            format!("Unable to write file {}: {}", path.display(), error),
+        )
+    })?;
+
+    // Only the requested rows are decoded, rather than skipping over the ones before them
+    let count = dataset.row_count();
+    let range = options.rows.clone().unwrap_or(0..count);
+    let end = range.end.min(count);
+    let rows = (range.start.min(end)..end)
+        .filter_map(|index| dataset.row(index))
+        .map(Ok::<_, XptError>);
+    let options = DelimitedOptions {
+        rows: None,
+        ..options
+    };
+    write_delimited(BufWriter::new(file), dataset.metadata(), rows, &options)?;
+    Ok(())
+}
+
+/// Writes an open dataset to `path` as CDISC Dataset-JSON, one row at a time
+#[tauri::command(async)]
+fn export_dataset_json(
+    handle: u64,
+    path: String,
+    options: DatasetJsonOptions,
+    open: State<'_, OpenDatasets>,
+) -> Result<(), XptError> {
+    let dataset = open.get(handle)?;
+    let path = PathBuf::from(path);
+    let file = File::create(&path).map_err(|error| {
+        XptError::new(
+            ErrorCode::Io,
+            Section::File,
+            format!("Unable to write file {}: {}", path.display(), error),
+        )
+    })?;
+
+    let count = dataset.row_count();
+    let rows = (0..count)
+        .filter_map(|index| dataset.row(index))
+        .map(Ok::<_, XptError>);
+    write_dataset_json(
+        BufWriter::new(file),
+        dataset.metadata(),
+        count,
+        rows,
+        &options,
+    )?;
+    Ok(())
+}
+
+/// Writes open datasets to `path` as an Excel workbook, a sheet for each in the order given
+#[tauri::command(async)]
+fn export_workbook(
+    handles: Vec<u64>,
+    path: String,
+    options: XlsxOptions,
+    open: State<'_, OpenDatasets>,
+) -> Result<(), XptError> {
+    let mut workbook = XlsxWriter::new(options);
+    for handle in handles {
+        let dataset = open.get(handle)?;
+        let rows = (0..dataset.row_count())
+            .filter_map(|index| dataset.row(index))
+            .map(Ok::<_, XptError>);
+        workbook.add_dataset(dataset.metadata(), rows)?;
     }
+
+    let path = PathBuf::from(path);
+    let file = File::create(&path).map_err(|error| {
+        XptError::new(
+            ErrorCode::Io,
+            Section::File,
+            format!("Unable to write file {}: {}", path.display(), error),
+        )
+    })?;
+    workbook.finish(BufWriter::new(file))?;
+    Ok(())
 }
 
-fn load_xpt_impl(path: &Path) -> Result<XptFilePayload> {
-    // Read the file
-    let data = fs::read(path)
-        .with_context(|| format!("Unable to read file: {}", path.display()))?;
+/// Whether `path` names a native SAS dataset rather than a transport file
+fn is_sas7bdat(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("sas7bdat"))
+}
+
+/// Whether `path` names a Dataset-JSON file rather than a transport file
+fn is_dataset_json(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("json") || extension.eq_ignore_ascii_case("ndjson")
+        })
+}
+
+/// Maps a file into memory, so only the pages that are read are loaded
+fn map_file(path: &Path) -> std::io::Result<Mmap> {
+    let file = File::open(path)?;
+    // SAFETY: the map is only read. If another program truncates the file while it is open,
+    // reading the lost pages faults, a risk every memory-mapped viewer accepts.
+    unsafe { Mmap::map(&file) }
+}
 
+fn load_xpt_impl(
+    path: &Path,
+    data: &Arc<Mmap>,
+    options: ParseOptions,
+    open: &OpenDatasets,
+) -> XptResult<XptFilePayload> {
     // Parse using our XPT parser
     let suggested_filename = path
         .file_name()
         .and_then(|n| n.to_str());
     
-    let dataset = XPTParser::parse(&data, suggested_filename)
-        .with_context(|| format!("Unable to parse SAS XPORT file: {}", path.display()))?;
+    let mut pending = None;
+    let datasets: Vec<OpenDataset> = if is_dataset_json(path) {
+        // Dataset-JSON holds a single dataset, always in UTF-8
+        vec![OpenDataset::Parsed(read_dataset_json(data)?)]
+    } else if is_sas7bdat(path) {
+        // A SAS7BDAT file holds a single dataset
+        if data.len() > LAZY_DECODING_THRESHOLD {
+            vec![OpenDataset::MappedSas7bdat {
+                data: Arc::clone(data),
+                dataset: read_sas7bdat_lazy(data, options)?,
+            }]
+        } else {
+            vec![OpenDataset::Parsed(read_sas7bdat(data, options)?)]
+        }
+    } else if data.len() > LAZY_DECODING_THRESHOLD {
+        // Only the first member is located now, and the others when `next_member` asks for them
+        let mut members = XPTParser::lazy_members(data, suggested_filename, options)?;
+        let first = members.next_member(data).transpose()?;
+        if members.has_next() {
+            pending = Some((Arc::clone(data), members));
+        }
+        first
+            .into_iter()
+            .map(|dataset| OpenDataset::Mapped {
+                data: Arc::clone(data),
+                dataset,
+            })
+            .collect()
+    } else {
+        XPTParser::parse_with_options(data, suggested_filename, options)?
+            .into_iter()
+            .map(OpenDataset::Parsed)
+            .collect()
+    };
+    // Every member is decoded with the same encoding
+    let encoding = datasets
+        .first()
+        .map_or(TextEncoding::Utf8, |dataset| dataset.metadata().encoding);
 
+    let more_members = pending.is_some();
+    Ok(XptFilePayload {
+        path: path.display().to_string(),
+        encoding,
+        datasets: open
+            .replace(datasets, pending)
+            .iter()
+            .map(|(handle, dataset)| summarize_dataset(*handle, dataset))
+            .collect(),
+        more_members,
+    })
+}
+
+/// Converts the metadata of an open member into the payload shape expected by the frontend
+fn summarize_dataset(handle: u64, open: &OpenDataset) -> DatasetSummary {
+    let dataset = open.metadata();
     // Convert to the expected format
     let fields: Vec<FieldMetadata> = dataset
         .variables
@@ -74,59 +525,85 @@ fn load_xpt_impl(path: &Path) -> Result<XptFilePayload> {
                 VariableType::Character => "Character".to_string(),
                 VariableType::Numeric => "Numeric".to_string(),
             },
+            length: var.length,
+            format: non_empty(var.format.to_string()),
+            informat: non_empty(var.informat.to_string()),
+            justification: match var.justification {
+                Justification::Left => "left",
+                Justification::Right => "right",
+            },
+            number: var.number,
+            offset: var.offset,
         })
         .collect();
 
-    let rows: Vec<BTreeMap<String, serde_json::Value>> = dataset
-        .rows
+    let library = &dataset.library;
+    DatasetSummary {
+        handle,
+        name: dataset.title.clone(),
+        label: non_empty(dataset.label.clone()),
+        dataset_type: non_empty(dataset.dataset_type.clone()),
+        sas_version: non_empty(dataset.sas_version.clone()),
+        os_name: non_empty(dataset.os_name.clone()),
+        created_date: dataset.created_date.map(|date| date.to_string()),
+        modified_date: dataset.modified_date.map(|date| date.to_string()),
+        library: LibrarySummary {
+            sas_symbol: non_empty(library.sas_symbol.clone()),
+            sas_version: non_empty(library.sas_version.clone()),
+            os_name: non_empty(library.os_name.clone()),
+            created_date: library.created.map(|date| date.to_string()),
+            modified_date: library.modified.map(|date| date.to_string()),
+        },
+        observation_count: open.row_count(),
+        fields,
+        warnings: dataset.warnings.clone(),
+    }
+}
+
+/// Converts one row into a JSON object keyed by variable name
+fn row_values(dataset: &XPTDataset, row: &XPTRow) -> BTreeMap<String, serde_json::Value> {
+    row.values
         .iter()
-        .map(|row| {
-            let mut map = BTreeMap::new();
-            for (i, value) in row.values.iter().enumerate() {
-                if i < fields.len() {
-                    let field_name = &fields[i].name;
-                    // Convert string values to appropriate JSON types
-                    let json_value = if value.is_empty() {
-                        serde_json::Value::Null
-                    } else if fields[i].kind == "Numeric" {
-                        // Try to parse as number
-                        value
-                            .parse::<f64>()
-                            .ok()
-                            .and_then(|n| serde_json::Number::from_f64(n))
-                            .map(serde_json::Value::Number)
-                            .unwrap_or_else(|| serde_json::Value::String(value.clone()))
-                    } else {
-                        serde_json::Value::String(value.clone())
-                    };
-                    map.insert(field_name.clone(), json_value);
-                }
-            }
-            map
+        .zip(&dataset.variables)
+        .map(|(value, variable)| {
+            // Convert cell values to appropriate JSON types
+            let json_value = match value {
+                XPTValue::Character(text) if text.is_empty() => serde_json::Value::Null,
+                XPTValue::Character(text) => serde_json::Value::String(text.clone()),
+                // Numbers are passed through untouched; the grid formats them for display
+                XPTValue::Numeric(number) => serde_json::Number::from_f64(*number)
+                    .map(serde_json::Value::Number)
+                    .unwrap_or(serde_json::Value::Null),
+                XPTValue::Missing(MissingValue::Standard) => serde_json::Value::Null,
+                // Special missing values travel as `{ "missing": ".N" }` so the grid can tell
+                // them apart from character data
+                XPTValue::Missing(missing) => serde_json::json!({ "missing": missing.code() }),
+            };
+            (variable.name.clone(), json_value)
         })
-        .collect();
+        .collect()
+}
 
-    // Debug: log the first row to verify data structure
-    if !rows.is_empty() {
-        eprintln!("First row keys: {:?}", rows[0].keys().collect::<Vec<_>>());
-        eprintln!("First row sample: {:?}", rows[0]);
-    }
-    eprintln!("Total rows: {}, Total fields: {}", rows.len(), fields.len());
-
-    let datasets = vec![DatasetSummary {
-        name: dataset.title,
-        label: None, // XPT format doesn't have dataset-level labels in the same way
-        created_date: dataset.created_date,
-        modified_date: dataset.modified_date,
-        observation_count: dataset.rows.len(),
-        fields,
-        rows,
-    }];
+/// Renders the numeric cells of one row whose format is supported
+fn formatted_values(dataset: &XPTDataset, row: &XPTRow) -> BTreeMap<String, String> {
+    row.values
+        .iter()
+        .zip(&dataset.variables)
+        .filter_map(|(value, variable)| match value {
+            XPTValue::Numeric(number) => xpt::format_numeric(*number, &variable.format)
+                .map(|text| (variable.name.clone(), text)),
+            _ => None,
+        })
+        .collect()
+}
 
-    Ok(XptFilePayload {
-        path: path.display().to_string(),
-        datasets,
-    })
+/// Maps blank header fields to `None` so the frontend can skip them
+fn non_empty(value: String) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
 }
 
 fn main() {
@@ -141,7 +618,17 @@ fn main() {
             }
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![load_xpt])
+        .manage(LoadedFile::default())
+        .manage(OpenDatasets::default())
+        .invoke_handler(tauri::generate_handler![
+            load_xpt,
+            reopen_xpt,
+            fetch_rows,
+            next_member,
+            export_dataset,
+            export_dataset_json,
+            export_workbook
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }